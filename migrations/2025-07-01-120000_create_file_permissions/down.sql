@@ -0,0 +1,2 @@
+DROP TABLE access_requests;
+DROP TABLE file_permissions;
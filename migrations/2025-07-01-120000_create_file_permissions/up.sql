@@ -0,0 +1,26 @@
+CREATE TABLE file_permissions (
+    id SERIAL PRIMARY KEY,
+    file_id INTEGER NOT NULL REFERENCES s3_files (file_id) ON DELETE CASCADE,
+    user_id VARCHAR NOT NULL,
+    -- Only the owner can change a file, so shares are read-only.
+    role VARCHAR NOT NULL DEFAULT 'reader' CHECK (role = 'reader'),
+    granted_by VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (file_id, user_id)
+);
+
+CREATE TABLE access_requests (
+    id SERIAL PRIMARY KEY,
+    file_id INTEGER NOT NULL REFERENCES s3_files (file_id) ON DELETE CASCADE,
+    requester_id VARCHAR NOT NULL,
+    message TEXT,
+    status VARCHAR NOT NULL DEFAULT 'pending',
+    role VARCHAR CHECK (role = 'reader'),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMP
+);
+
+CREATE INDEX access_requests_file_id_status_idx ON access_requests (file_id, status);
+CREATE UNIQUE INDEX access_requests_one_pending_idx
+    ON access_requests (file_id, requester_id)
+    WHERE status = 'pending';
@@ -0,0 +1,2 @@
+DROP TABLE email_outbox;
+ALTER TABLE users DROP COLUMN email_notifications;
@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN links_disabled BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,6 @@
+ALTER TABLE s3_files
+    DROP COLUMN file_request_id,
+    DROP COLUMN uploader_email,
+    DROP COLUMN uploader_name;
+
+DROP TABLE file_requests;
@@ -0,0 +1,21 @@
+CREATE TABLE file_requests (
+    id SERIAL PRIMARY KEY,
+    token VARCHAR NOT NULL UNIQUE,
+    owner_id VARCHAR NOT NULL,
+    title VARCHAR NOT NULL,
+    description TEXT,
+    max_file_size BIGINT,
+    allowed_mime_types TEXT[] NOT NULL DEFAULT '{}',
+    max_files INTEGER,
+    upload_count INTEGER NOT NULL DEFAULT 0,
+    expires_at TIMESTAMP,
+    revoked_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX file_requests_owner_id_idx ON file_requests (owner_id);
+
+ALTER TABLE s3_files
+    ADD COLUMN uploader_name VARCHAR,
+    ADD COLUMN uploader_email VARCHAR,
+    ADD COLUMN file_request_id INTEGER REFERENCES file_requests (id) ON DELETE SET NULL;
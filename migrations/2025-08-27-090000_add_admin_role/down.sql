@@ -0,0 +1,5 @@
+DROP TABLE admin_audit_log;
+
+ALTER TABLE users
+    DROP COLUMN suspended_at,
+    DROP COLUMN role;
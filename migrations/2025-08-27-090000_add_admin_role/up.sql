@@ -0,0 +1,15 @@
+ALTER TABLE users
+    ADD COLUMN role VARCHAR NOT NULL DEFAULT 'user',
+    ADD COLUMN suspended_at TIMESTAMP;
+
+CREATE TABLE admin_audit_log (
+    id SERIAL PRIMARY KEY,
+    admin_id VARCHAR NOT NULL,
+    action VARCHAR NOT NULL,
+    target_type VARCHAR NOT NULL,
+    target_id VARCHAR NOT NULL,
+    details TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX admin_audit_log_created_at_idx ON admin_audit_log (created_at);
@@ -0,0 +1,3 @@
+DROP TABLE account_jobs;
+
+ALTER TABLE users DROP COLUMN deleted_at;
@@ -0,0 +1,23 @@
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMP;
+
+CREATE TABLE account_jobs (
+    id UUID PRIMARY KEY,
+    user_id VARCHAR NOT NULL,
+    kind VARCHAR NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'pending',
+    transfer_to VARCHAR,
+    archive_key VARCHAR,
+    archive_size BIGINT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMP,
+    expires_at TIMESTAMP
+);
+
+CREATE INDEX account_jobs_user_id_idx ON account_jobs (user_id);
+CREATE INDEX account_jobs_due_idx ON account_jobs (next_attempt_at)
+    WHERE status IN ('pending', 'running');
+CREATE UNIQUE INDEX account_jobs_one_active_idx ON account_jobs (user_id, kind)
+    WHERE status IN ('pending', 'running');
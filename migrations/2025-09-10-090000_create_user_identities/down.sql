@@ -0,0 +1,22 @@
+ALTER TABLE users
+    ADD COLUMN oauth_provider VARCHAR NOT NULL DEFAULT 'deleted',
+    ADD COLUMN oauth_user_id VARCHAR NOT NULL DEFAULT '';
+
+-- Accounts with several identities keep the oldest one
+UPDATE users
+SET oauth_provider = identity.provider,
+    oauth_user_id = identity.subject
+FROM (
+    SELECT DISTINCT ON (user_id) user_id, provider, subject
+    FROM user_identities
+    ORDER BY user_id, created_at, id
+) AS identity
+WHERE identity.user_id = users.id;
+
+UPDATE users SET oauth_user_id = id::VARCHAR WHERE oauth_user_id = '';
+
+ALTER TABLE users
+    ALTER COLUMN oauth_provider DROP DEFAULT,
+    ALTER COLUMN oauth_user_id DROP DEFAULT;
+
+DROP TABLE user_identities;
@@ -0,0 +1,22 @@
+CREATE TABLE user_identities (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    provider VARCHAR NOT NULL,
+    subject VARCHAR NOT NULL,
+    email VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    last_login_at TIMESTAMP,
+    UNIQUE (provider, subject)
+);
+
+CREATE INDEX user_identities_user_id_idx ON user_identities (user_id);
+
+-- Every existing account keeps the identity it was created with
+INSERT INTO user_identities (user_id, provider, subject, email, created_at)
+SELECT id, oauth_provider, oauth_user_id, email, created_at
+FROM users
+WHERE deleted_at IS NULL;
+
+ALTER TABLE users
+    DROP COLUMN oauth_provider,
+    DROP COLUMN oauth_user_id;
@@ -0,0 +1,6 @@
+DROP TABLE service_account_assertions;
+DROP TABLE service_account_keys;
+DROP TABLE service_accounts;
+
+-- The former service accounts have no identity, so nobody can sign in as them
+UPDATE users SET role = 'user' WHERE role = 'service';
@@ -0,0 +1,33 @@
+CREATE TABLE service_accounts (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL UNIQUE REFERENCES users (id) ON DELETE CASCADE,
+    owner_id VARCHAR NOT NULL,
+    name VARCHAR NOT NULL,
+    scopes TEXT[] NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE service_account_keys (
+    id SERIAL PRIMARY KEY,
+    service_account_id INTEGER NOT NULL REFERENCES service_accounts (id) ON DELETE CASCADE,
+    key_id VARCHAR NOT NULL UNIQUE,
+    algorithm VARCHAR NOT NULL,
+    public_key TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMP,
+    revoked_at TIMESTAMP
+);
+
+CREATE INDEX service_account_keys_service_account_id_idx
+    ON service_account_keys (service_account_id);
+
+-- IDs of accepted assertions, kept until they expire so none can be replayed
+CREATE TABLE service_account_assertions (
+    service_account_id INTEGER NOT NULL REFERENCES service_accounts (id) ON DELETE CASCADE,
+    jti VARCHAR NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (service_account_id, jti)
+);
+
+CREATE INDEX service_account_assertions_expires_at_idx
+    ON service_account_assertions (expires_at);
@@ -0,0 +1,6 @@
+ALTER TABLE sessions
+    ADD COLUMN user_agent VARCHAR,
+    ADD COLUMN ip_address VARCHAR,
+    ADD COLUMN last_seen_at TIMESTAMP NOT NULL DEFAULT NOW();
+
+UPDATE sessions SET last_seen_at = created_at;
@@ -0,0 +1,19 @@
+CREATE TABLE file_contents (
+    file_id INTEGER PRIMARY KEY REFERENCES s3_files (file_id) ON DELETE CASCADE,
+    search_vector TSVECTOR NOT NULL,
+    content TEXT,
+    extraction_error TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    indexed_at TIMESTAMP
+);
+
+CREATE INDEX file_contents_search_idx ON file_contents USING GIN (search_vector);
+CREATE INDEX file_contents_due_idx ON file_contents (next_attempt_at)
+    WHERE indexed_at IS NULL;
+
+-- Existing files are searchable by name right away; the indexer replaces
+-- these vectors once it has extracted their contents.
+INSERT INTO file_contents (file_id, search_vector)
+SELECT file_id, setweight(to_tsvector('simple', regexp_replace(name, '[^[:alnum:]]+', ' ', 'g')), 'A')
+FROM s3_files;
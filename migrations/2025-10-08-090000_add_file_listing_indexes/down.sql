@@ -0,0 +1,7 @@
+DROP INDEX file_permissions_user_id_idx;
+DROP INDEX s3_files_user_id_idx;
+DROP INDEX s3_files_modified_at_idx;
+DROP INDEX s3_files_created_at_idx;
+DROP INDEX s3_files_size_idx;
+DROP INDEX s3_files_name_idx;
+ALTER TABLE s3_files DROP COLUMN modified_at;
@@ -0,0 +1,14 @@
+-- Last change to a file's metadata, such as a transfer to a new owner.
+ALTER TABLE s3_files ADD COLUMN modified_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP;
+UPDATE s3_files SET modified_at = created_at;
+
+-- Keyset pagination: one index per sort order, with the file ID as the
+-- tie-breaker the listings sort by.
+CREATE INDEX s3_files_name_idx ON s3_files (name, file_id);
+CREATE INDEX s3_files_size_idx ON s3_files (size, file_id);
+CREATE INDEX s3_files_created_at_idx ON s3_files (created_at, file_id);
+CREATE INDEX s3_files_modified_at_idx ON s3_files (modified_at, file_id);
+
+-- Searches are limited to files the user owns or has been granted access to.
+CREATE INDEX s3_files_user_id_idx ON s3_files (user_id);
+CREATE INDEX file_permissions_user_id_idx ON file_permissions (user_id);
@@ -0,0 +1,2 @@
+DROP INDEX s3_files_name_trgm_idx;
+DROP FUNCTION search_normalize(TEXT);
@@ -0,0 +1,13 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE EXTENSION IF NOT EXISTS unaccent;
+
+-- Lowercased and unaccented text, so "Resume" finds "résumé.pdf". unaccent()
+-- is only STABLE because its dictionary can change; naming the dictionary
+-- makes the wrapper safe to index.
+CREATE FUNCTION search_normalize(TEXT) RETURNS TEXT
+    LANGUAGE sql IMMUTABLE PARALLEL SAFE STRICT
+    AS $$ SELECT lower(public.unaccent('public.unaccent'::regdictionary, $1)) $$;
+
+-- Typo-tolerant (`<%`) and substring (`LIKE`) matching of file names.
+CREATE INDEX s3_files_name_trgm_idx ON s3_files
+    USING GIN (search_normalize(name) gin_trgm_ops);
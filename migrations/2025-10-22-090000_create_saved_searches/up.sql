@@ -0,0 +1,20 @@
+CREATE TABLE saved_searches (
+    id SERIAL PRIMARY KEY,
+    owner_id VARCHAR NOT NULL,
+    name VARCHAR NOT NULL,
+    query TEXT NOT NULL,
+    order_by VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (owner_id, name)
+);
+
+CREATE TABLE saved_search_shares (
+    saved_search_id INTEGER NOT NULL REFERENCES saved_searches (id) ON DELETE CASCADE,
+    user_id VARCHAR NOT NULL,
+    shared_by VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (saved_search_id, user_id)
+);
+
+CREATE INDEX saved_search_shares_user_id_idx ON saved_search_shares (user_id);
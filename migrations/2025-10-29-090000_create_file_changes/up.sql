@@ -0,0 +1,18 @@
+-- One row per user a change is visible to, so a user's changes feed is a
+-- range scan. Rows outlive the files they describe.
+--
+-- `tx_id` is the transaction that recorded the change. IDs come from the
+-- sequence without any locking, so they do not follow commit order; the
+-- feed is ordered by transaction instead and only shows transactions older
+-- than every running one.
+CREATE TABLE file_changes (
+    id BIGSERIAL PRIMARY KEY,
+    tx_id BIGINT NOT NULL DEFAULT pg_current_xact_id()::text::bigint,
+    user_id VARCHAR NOT NULL,
+    file_id INTEGER NOT NULL,
+    change_type VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX file_changes_user_id_idx ON file_changes (user_id, tx_id, id);
+CREATE INDEX file_changes_position_idx ON file_changes (tx_id, id);
@@ -0,0 +1,2 @@
+DROP INDEX users_email_lower_idx;
+ALTER TABLE users DROP COLUMN email_verified;
@@ -0,0 +1,4 @@
+-- Emails stored before providers were required to verify them stay
+-- unverified until the user signs in again
+ALTER TABLE users ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT FALSE;
+CREATE INDEX users_email_lower_idx ON users (lower(email));
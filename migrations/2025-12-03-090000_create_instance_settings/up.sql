@@ -0,0 +1,8 @@
+-- Instance-wide settings administrators can change at runtime, as JSON
+-- under a name. Missing settings are filled in from the environment at
+-- startup; after that the stored value wins.
+CREATE TABLE instance_settings (
+    name VARCHAR PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
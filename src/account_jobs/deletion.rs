@@ -0,0 +1,106 @@
+use super::JobError;
+use crate::database::DbPool;
+use crate::models::account_jobs::AccountJob;
+use crate::policy::SharingPolicy;
+use crate::repositories::account_jobs::{list_export_archives_by_owner, mark_export_expired};
+use crate::repositories::s3_files::list_s3_files_by_owner;
+use crate::repositories::service_accounts::list_service_accounts_by_owner;
+use crate::repositories::users::{erase_user, find_user_by_id_str};
+use crate::storage::S3Storage;
+use log::{debug, info};
+
+/// Deletes an account: its files are transferred to `job.transfer_to` or
+/// removed from S3, export archives are removed, and all remaining data is
+/// erased with the user row anonymised. Service accounts the user created
+/// are erased the same way first, which revokes their keys. Every step can
+/// safely be repeated, so an interrupted deletion is simply run again.
+pub async fn run_deletion(
+    pool: &DbPool,
+    storage: &S3Storage,
+    policy: &SharingPolicy,
+    job: &AccountJob,
+) -> Result<(), JobError> {
+    let user_id = job
+        .user_id
+        .parse::<i32>()
+        .map_err(|_| JobError::Permanent(format!("Invalid user id '{}'", job.user_id)))?;
+
+    if let Some(new_owner) = &job.transfer_to {
+        // The target or the policy may have changed since the job was queued
+        let target = match find_user_by_id_str(pool, new_owner) {
+            Ok(user) if user.can_receive_transfer() => user,
+            Ok(_) | Err(diesel::result::Error::NotFound) => {
+                return Err(unavailable_target(new_owner));
+            }
+            Err(e) => return Err(JobError::transient(e)),
+        };
+        let account = find_user_by_id_str(pool, &job.user_id).map_err(JobError::transient)?;
+        policy
+            .check_share(&account, &target)
+            .map_err(|reason| JobError::Permanent(format!("Cannot transfer files: {}", reason)))?;
+    }
+
+    let service_accounts =
+        list_service_accounts_by_owner(pool, &job.user_id).map_err(JobError::transient)?;
+    for service_account in service_accounts {
+        erase_account(
+            pool,
+            storage,
+            service_account.user_id,
+            job.transfer_to.as_deref(),
+        )
+        .await?;
+        info!(
+            "Erased service account {} of user {}",
+            service_account.id, job.user_id
+        );
+    }
+
+    erase_account(pool, storage, user_id, job.transfer_to.as_deref()).await
+}
+
+/// Removes or transfers the files of one account, removes its export
+/// archives and erases the rest of its data.
+async fn erase_account(
+    pool: &DbPool,
+    storage: &S3Storage,
+    user_id: i32,
+    transfer_to: Option<&str>,
+) -> Result<(), JobError> {
+    let owner = user_id.to_string();
+
+    if transfer_to.is_none() {
+        let files = list_s3_files_by_owner(pool, &owner).map_err(JobError::transient)?;
+        for file in &files {
+            debug!("Deleting {} of user {}", file.s3_key, owner);
+            storage
+                .delete_file(&file.s3_key)
+                .await
+                .map_err(JobError::transient)?;
+        }
+        info!("Deleted {} files of user {} from S3", files.len(), owner);
+    }
+
+    let exports = list_export_archives_by_owner(pool, &owner).map_err(JobError::transient)?;
+    for export in exports {
+        if let Some(key) = &export.archive_key {
+            storage
+                .delete_file(key)
+                .await
+                .map_err(JobError::transient)?;
+        }
+        mark_export_expired(pool, export.id).map_err(JobError::transient)?;
+    }
+
+    erase_user(pool, user_id, transfer_to).map_err(|e| match (e, transfer_to) {
+        (diesel::result::Error::NotFound, Some(new_owner)) => unavailable_target(new_owner),
+        (e, _) => JobError::transient(e),
+    })
+}
+
+fn unavailable_target(new_owner: &str) -> JobError {
+    JobError::Permanent(format!(
+        "Transfer target {} can no longer receive files",
+        new_owner
+    ))
+}
@@ -0,0 +1,173 @@
+use super::JobError;
+use crate::database::DbPool;
+use crate::models::account_jobs::AccountJob;
+use crate::repositories::access_requests::{
+    list_access_requests_by_requester, list_access_requests_for_owner,
+};
+use crate::repositories::file_permissions::{
+    list_permissions_for_owner, list_permissions_for_user,
+};
+use crate::repositories::file_requests::list_file_requests_by_owner;
+use crate::repositories::personal_access_tokens::list_personal_access_tokens;
+use crate::repositories::s3_files::list_s3_files_by_owner;
+use crate::repositories::saved_searches::list_saved_searches_for_user;
+use crate::repositories::user_identities::list_user_identities;
+use crate::repositories::users::find_user_by_id_str;
+use crate::storage::S3Storage;
+use actix_web::web;
+use aws_sdk_s3::primitives::ByteStream;
+use log::debug;
+use std::fs::File;
+use std::io::Write;
+use tempfile::NamedTempFile;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Bytes of a file buffered before they are written to the archive.
+const WRITE_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// A finished export archive in S3.
+pub struct ExportArchive {
+    pub key: String,
+    pub size: i64,
+}
+
+/// Builds a zip archive with `data.json` (the user's account, file metadata
+/// and sharing data) and every file they own under `files/`, and uploads it
+/// to S3 under `exports/`.
+pub async fn run_export(
+    pool: &DbPool,
+    storage: &S3Storage,
+    job: &AccountJob,
+) -> Result<ExportArchive, JobError> {
+    let user = find_user_by_id_str(pool, &job.user_id).map_err(JobError::transient)?;
+    if user.is_deleted() {
+        return Err(JobError::Permanent("Account has been deleted".to_string()));
+    }
+
+    let owner = job.user_id.as_str();
+    let files = list_s3_files_by_owner(pool, owner).map_err(JobError::transient)?;
+
+    let data = serde_json::json!({
+        "exported_at": chrono::Utc::now().naive_utc(),
+        "user": user,
+        "identities": list_user_identities(pool, user.id).map_err(JobError::transient)?,
+        "files": files,
+        "shared_by_me": list_permissions_for_owner(pool, owner).map_err(JobError::transient)?,
+        "shared_with_me": list_permissions_for_user(pool, owner).map_err(JobError::transient)?,
+        "access_requests_made": list_access_requests_by_requester(pool, owner)
+            .map_err(JobError::transient)?,
+        "access_requests_received": list_access_requests_for_owner(pool, owner, None)
+            .map_err(JobError::transient)?,
+        "file_requests": list_file_requests_by_owner(pool, owner).map_err(JobError::transient)?,
+        "personal_access_tokens": list_personal_access_tokens(pool, owner)
+            .map_err(JobError::transient)?,
+        "saved_searches": list_saved_searches_for_user(pool, owner)
+            .map_err(JobError::transient)?,
+    });
+    let data = serde_json::to_vec_pretty(&data).map_err(JobError::transient)?;
+
+    // The archive is assembled on disk and files are streamed into it, so
+    // exports are not limited by memory
+    let archive = NamedTempFile::new().map_err(JobError::transient)?;
+    let mut writer = ZipWriter::new(archive.reopen().map_err(JobError::transient)?);
+
+    let data_size = data.len() as u64;
+    writer = add_entry(
+        writer,
+        "data.json".to_string(),
+        data_size,
+        ByteStream::from(data),
+    )
+    .await?;
+
+    for file in &files {
+        debug!("Adding file {} to export {}", file.file_id, job.id);
+        let content = storage
+            .download_file(&file.s3_key)
+            .await
+            .map_err(JobError::transient)?;
+
+        let entry_name = format!("files/{}-{}", file.file_id, entry_file_name(&file.name));
+        writer = add_entry(writer, entry_name, file.size.max(0) as u64, content).await?;
+    }
+
+    let archive_file = web::block(move || writer.finish())
+        .await
+        .map_err(JobError::transient)?
+        .map_err(JobError::transient)?;
+    let size = archive_file.metadata().map_err(JobError::transient)?.len() as i64;
+
+    let key = format!("exports/{}.zip", job.id);
+    storage
+        .upload_path(&key, archive.path())
+        .await
+        .map_err(JobError::transient)?;
+
+    Ok(ExportArchive { key, size })
+}
+
+/// Streams one entry into the archive, `WRITE_CHUNK_SIZE` bytes at a time,
+/// writing on the blocking thread pool. `size` is only used to decide
+/// whether the entry needs zip64 headers.
+async fn add_entry(
+    writer: ZipWriter<File>,
+    name: String,
+    size: u64,
+    mut content: ByteStream,
+) -> Result<ZipWriter<File>, JobError> {
+    let mut writer = web::block(move || {
+        let mut writer = writer;
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(size >= u32::MAX as u64);
+
+        writer.start_file(name, options)?;
+        Ok::<_, zip::result::ZipError>(writer)
+    })
+    .await
+    .map_err(JobError::transient)?
+    .map_err(JobError::transient)?;
+
+    let mut buffer = Vec::with_capacity(WRITE_CHUNK_SIZE);
+    while let Some(chunk) = content.next().await {
+        buffer.extend_from_slice(&chunk.map_err(JobError::transient)?);
+        if buffer.len() >= WRITE_CHUNK_SIZE {
+            writer = write_chunk(writer, std::mem::take(&mut buffer)).await?;
+        }
+    }
+    if !buffer.is_empty() {
+        writer = write_chunk(writer, buffer).await?;
+    }
+
+    Ok(writer)
+}
+
+/// Appends data to the current archive entry on the blocking thread pool.
+async fn write_chunk(writer: ZipWriter<File>, chunk: Vec<u8>) -> Result<ZipWriter<File>, JobError> {
+    web::block(move || {
+        let mut writer = writer;
+        writer.write_all(&chunk)?;
+        Ok::<_, std::io::Error>(writer)
+    })
+    .await
+    .map_err(JobError::transient)?
+    .map_err(JobError::transient)
+}
+
+/// Makes a stored file name safe to use as a single archive path component.
+fn entry_file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    match name.trim_start_matches('.') {
+        "" => "file".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
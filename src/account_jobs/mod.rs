@@ -0,0 +1,29 @@
+pub mod deletion;
+pub mod export;
+pub mod worker;
+
+use std::env;
+
+/// Why a job attempt failed.
+pub enum JobError {
+    /// A temporary problem (storage, database); the job is retried.
+    Transient(String),
+    /// The job can never succeed; it is marked as failed right away.
+    Permanent(String),
+}
+
+impl JobError {
+    fn transient(e: impl std::fmt::Display) -> Self {
+        JobError::Transient(e.to_string())
+    }
+}
+
+/// How long a finished export can be downloaded before its archive is removed.
+pub fn export_retention() -> chrono::Duration {
+    chrono::Duration::days(
+        env::var("EXPORT_RETENTION_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(7),
+    )
+}
@@ -0,0 +1,168 @@
+use super::deletion::run_deletion;
+use super::export::run_export;
+use super::{JobError, export_retention};
+use crate::database::DbPool;
+use crate::models::account_jobs::{AccountJob, KIND_DELETION, KIND_EXPORT};
+use crate::policy::SharingPolicy;
+use crate::repositories::account_jobs::{
+    claim_due_account_job, complete_deletion_job, complete_export_job, fail_account_deletion,
+    list_expired_exports, mark_account_job_failed, mark_export_expired,
+};
+use crate::storage::S3Storage;
+use chrono::{Duration, Utc};
+use log::{error, info, warn};
+
+/// How often the queue is polled for due jobs.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// How long a claimed job is hidden from other workers.
+const CLAIM_LEASE_SECS: i64 = 3600;
+/// Attempts before a job is marked as failed.
+const MAX_ATTEMPTS: i32 = 5;
+/// Delay before the first retry; doubled on every further attempt.
+const RETRY_BASE_SECS: i64 = 60;
+/// Longest delay between retries of a deletion, which is never given up on
+/// for a temporary problem: the account is already locked.
+const MAX_RETRY_DELAY_SECS: i64 = 6 * 3600;
+
+/// Runs export and deletion jobs until the process exits, and removes
+/// export archives once their retention period is over.
+pub async fn run_account_job_worker(pool: DbPool, storage: S3Storage) {
+    info!("Account job worker started");
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        remove_expired_exports(&pool, &storage).await;
+
+        loop {
+            let lease_until = (Utc::now() + Duration::seconds(CLAIM_LEASE_SECS)).naive_utc();
+            match claim_due_account_job(&pool, lease_until) {
+                Ok(Some(job)) => run_job(&pool, &storage, job).await,
+                Ok(None) => break,
+                Err(e) => {
+                    error!("Failed to claim account job: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn run_job(pool: &DbPool, storage: &S3Storage, job: AccountJob) {
+    info!(
+        "Running {} job {} for user {} (attempt {})",
+        job.kind,
+        job.id,
+        job.user_id,
+        job.attempts + 1
+    );
+
+    let result = match job.kind.as_str() {
+        KIND_EXPORT => match run_export(pool, storage, &job).await {
+            Ok(archive) => {
+                let expires_at = (Utc::now() + export_retention()).naive_utc();
+                complete_export_job(pool, job.id, &archive.key, archive.size, expires_at)
+                    .map(|_| ())
+                    .map_err(JobError::transient)
+            }
+            Err(e) => Err(e),
+        },
+        KIND_DELETION => match SharingPolicy::load(pool) {
+            Ok(policy) => run_deletion(pool, storage, &policy, &job)
+                .await
+                .and_then(|_| complete_deletion_job(pool, job.id).map_err(JobError::transient))
+                .map(|_| ()),
+            Err(e) => Err(JobError::transient(e)),
+        },
+        other => Err(JobError::Permanent(format!("Unknown job kind '{}'", other))),
+    };
+
+    let update = match result {
+        Ok(()) => {
+            info!("Finished {} job {}", job.kind, job.id);
+            return;
+        }
+        Err(JobError::Transient(e)) => {
+            let attempts = job.attempts + 1;
+            let retry_at = (attempts < MAX_ATTEMPTS || job.kind == KIND_DELETION).then(|| {
+                (Utc::now() + Duration::seconds(retry_delay_secs(job.attempts))).naive_utc()
+            });
+
+            warn!(
+                "{} job {} failed (attempt {}): {}",
+                job.kind, job.id, attempts, e
+            );
+            mark_account_job_failed(pool, job.id, &e, retry_at).map(|_| ())
+        }
+        Err(JobError::Permanent(e)) if job.kind == KIND_DELETION => {
+            error!(
+                "Deletion job {} failed permanently, unlocking user {}: {}",
+                job.id, job.user_id, e
+            );
+            match job.user_id.parse::<i32>() {
+                Ok(owner) => fail_account_deletion(pool, job.id, owner, &e),
+                Err(_) => mark_account_job_failed(pool, job.id, &e, None).map(|_| ()),
+            }
+        }
+        Err(JobError::Permanent(e)) => {
+            error!("{} job {} failed permanently: {}", job.kind, job.id, e);
+            mark_account_job_failed(pool, job.id, &e, None).map(|_| ())
+        }
+    };
+
+    if let Err(e) = update {
+        error!("Failed to update account job {}: {}", job.id, e);
+    }
+}
+
+/// Delay before the next attempt of a job that failed `attempts` times:
+/// doubled on every attempt, up to `MAX_RETRY_DELAY_SECS`.
+fn retry_delay_secs(attempts: i32) -> i64 {
+    2_i64
+        .checked_pow(attempts.max(0) as u32)
+        .and_then(|factor| factor.checked_mul(RETRY_BASE_SECS))
+        .map_or(MAX_RETRY_DELAY_SECS, |delay| {
+            delay.min(MAX_RETRY_DELAY_SECS)
+        })
+}
+
+/// Removes the archives of exports past their retention period.
+async fn remove_expired_exports(pool: &DbPool, storage: &S3Storage) {
+    let expired = match list_expired_exports(pool, Utc::now().naive_utc()) {
+        Ok(expired) => expired,
+        Err(e) => {
+            error!("Failed to load expired exports: {}", e);
+            return;
+        }
+    };
+
+    for export in expired {
+        if let Some(key) = &export.archive_key
+            && let Err(e) = storage.delete_file(key).await
+        {
+            warn!("Failed to delete expired export {}: {}", export.id, e);
+            continue;
+        }
+
+        match mark_export_expired(pool, export.id) {
+            Ok(_) => info!("Removed expired export {}", export.id),
+            Err(e) => error!("Failed to mark export {} as expired: {}", export.id, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_maximum() {
+        assert_eq!(retry_delay_secs(0), RETRY_BASE_SECS);
+        assert_eq!(retry_delay_secs(1), 2 * RETRY_BASE_SECS);
+        assert_eq!(retry_delay_secs(4), 16 * RETRY_BASE_SECS);
+        assert_eq!(retry_delay_secs(20), MAX_RETRY_DELAY_SECS);
+        assert_eq!(retry_delay_secs(i32::MAX), MAX_RETRY_DELAY_SECS);
+    }
+}
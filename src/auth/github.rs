@@ -0,0 +1,227 @@
+use crate::auth::login::ProviderIdentity;
+use oauth2::basic::BasicClient;
+use oauth2::reqwest::async_http_client;
+use oauth2::{
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge,
+    PkceCodeVerifier, RedirectUrl, Scope, TokenResponse, TokenUrl,
+};
+use reqwest::header::{ACCEPT, USER_AGENT};
+use serde::Deserialize;
+
+/// Provider name stored in `user_identities.provider`.
+pub const PROVIDER: &str = "github";
+
+const AUTHORIZE_URL: &str = "https://github.com/login/oauth/authorize";
+const TOKEN_URL: &str = "https://github.com/login/oauth/access_token";
+const API_URL: &str = "https://api.github.com";
+
+/// GitHub rejects API requests without a `User-Agent`.
+const API_USER_AGENT: &str = "google-drive";
+
+/// Settings of the GitHub OAuth app.
+#[derive(Clone, Debug)]
+pub struct GitHubSettings {
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_uri: String,
+}
+
+/// Profile returned by `GET /user`.
+#[derive(Debug, Deserialize)]
+struct GitHubUser {
+    id: i64,
+    login: String,
+    name: Option<String>,
+    avatar_url: Option<String>,
+}
+
+/// Entry returned by `GET /user/emails`.
+#[derive(Debug, Deserialize)]
+struct GitHubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+/// GitHub login through a GitHub OAuth app.
+/// GitHub does not implement OpenID Connect, so the user is read from its REST API.
+pub struct GitHubProvider {
+    client: BasicClient,
+}
+
+impl GitHubProvider {
+    pub fn new(settings: GitHubSettings) -> Result<Self, String> {
+        let client = BasicClient::new(
+            ClientId::new(settings.client_id),
+            Some(ClientSecret::new(settings.client_secret)),
+            AuthUrl::new(AUTHORIZE_URL.to_string()).map_err(|e| e.to_string())?,
+            Some(TokenUrl::new(TOKEN_URL.to_string()).map_err(|e| e.to_string())?),
+        )
+        .set_redirect_uri(
+            RedirectUrl::new(settings.redirect_uri)
+                .map_err(|e| format!("Invalid redirect URI: {}", e))?,
+        );
+
+        Ok(GitHubProvider { client })
+    }
+
+    /// Builds the authorization URL. Returns the URL together with the CSRF
+    /// state and PKCE verifier that must be kept until the callback.
+    pub fn authorize_url(&self) -> (String, CsrfToken, PkceCodeVerifier) {
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+        let (url, csrf_token) = self
+            .client
+            .authorize_url(CsrfToken::new_random)
+            .add_scope(Scope::new("read:user".to_string()))
+            .add_scope(Scope::new("user:email".to_string()))
+            .set_pkce_challenge(pkce_challenge)
+            .url();
+
+        (url.to_string(), csrf_token, pkce_verifier)
+    }
+
+    /// Exchanges the authorization code and reads the user's profile and
+    /// primary verified email from the GitHub API.
+    pub async fn authenticate(
+        &self,
+        code: String,
+        pkce_verifier: PkceCodeVerifier,
+    ) -> Result<ProviderIdentity, String> {
+        let token_response = self
+            .client
+            .exchange_code(AuthorizationCode::new(code))
+            .set_pkce_verifier(pkce_verifier)
+            .request_async(async_http_client)
+            .await
+            .map_err(|e| format!("Token exchange failed: {:?}", e))?;
+
+        let access_token = token_response.access_token().secret();
+
+        let user: GitHubUser = api_get(access_token, "/user").await?;
+        let emails: Vec<GitHubEmail> = api_get(access_token, "/user/emails").await?;
+
+        Ok(identity(user, emails))
+    }
+}
+
+/// Builds the identity of a GitHub user from their profile and email addresses.
+fn identity(user: GitHubUser, emails: Vec<GitHubEmail>) -> ProviderIdentity {
+    // Only the primary address is used, and only once GitHub has verified it
+    let email = emails
+        .into_iter()
+        .find(|e| e.primary && e.verified)
+        .map(|e| e.email);
+
+    ProviderIdentity {
+        provider: PROVIDER.to_string(),
+        subject: user.id.to_string(),
+        email,
+        username: user.name.or(Some(user.login)),
+        avatar_url: user.avatar_url,
+    }
+}
+
+async fn api_get<T: serde::de::DeserializeOwned>(
+    access_token: &str,
+    path: &str,
+) -> Result<T, String> {
+    reqwest::Client::new()
+        .get(format!("{}{}", API_URL, path))
+        .bearer_auth(access_token)
+        .header(USER_AGENT, API_USER_AGENT)
+        .header(ACCEPT, "application/vnd.github+json")
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("GitHub API request {} failed: {}", path, e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid GitHub API response for {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(name: Option<&str>) -> GitHubUser {
+        GitHubUser {
+            id: 583231,
+            login: "octocat".to_string(),
+            name: name.map(str::to_string),
+            avatar_url: Some("https://avatars.githubusercontent.com/u/583231".to_string()),
+        }
+    }
+
+    fn email(email: &str, primary: bool, verified: bool) -> GitHubEmail {
+        GitHubEmail {
+            email: email.to_string(),
+            primary,
+            verified,
+        }
+    }
+
+    #[test]
+    fn identity_uses_the_primary_verified_email() {
+        let identity = identity(
+            user(Some("The Octocat")),
+            vec![
+                email("other@example.com", false, true),
+                email("octocat@example.com", true, true),
+            ],
+        );
+
+        assert_eq!(identity.provider, PROVIDER);
+        assert_eq!(identity.subject, "583231");
+        assert_eq!(identity.email.as_deref(), Some("octocat@example.com"));
+        assert_eq!(identity.username.as_deref(), Some("The Octocat"));
+    }
+
+    #[test]
+    fn unverified_or_secondary_emails_are_ignored() {
+        let identity = identity(
+            user(None),
+            vec![
+                email("octocat@example.com", true, false),
+                email("other@example.com", false, true),
+            ],
+        );
+
+        assert_eq!(identity.email, None);
+        assert_eq!(identity.username.as_deref(), Some("octocat"));
+    }
+
+    #[test]
+    fn emails_response_is_parsed() {
+        let emails: Vec<GitHubEmail> = serde_json::from_str(
+            r#"[{"email":"octocat@example.com","primary":true,"verified":true,"visibility":"public"}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            identity(user(None), emails).email.as_deref(),
+            Some("octocat@example.com")
+        );
+    }
+
+    #[test]
+    fn authorize_url_requests_email_access_with_pkce() {
+        let provider = GitHubProvider::new(GitHubSettings {
+            client_id: "client-1".to_string(),
+            client_secret: "secret".to_string(),
+            redirect_uri: "https://drive.example.com/auth/github/callback".to_string(),
+        })
+        .unwrap();
+
+        let (url, csrf_token, _) = provider.authorize_url();
+        let url = reqwest::Url::parse(&url).unwrap();
+        let params: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+
+        assert!(url.as_str().starts_with(AUTHORIZE_URL));
+        assert_eq!(params["client_id"], "client-1");
+        assert_eq!(params["state"], *csrf_token.secret());
+        assert_eq!(params["scope"], "read:user user:email");
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert!(params.contains_key("code_challenge"));
+    }
+}
@@ -1,73 +1,481 @@
-use actix_web::{Error, HttpRequest};
+use crate::auth::keys::jwt_keys;
+use crate::auth::scopes;
+use crate::auth::session::hash_token;
+use crate::database::DbPool;
+use crate::models::users::User;
+use crate::repositories::personal_access_tokens::{
+    find_personal_access_token_by_hash, touch_personal_access_token,
+};
+use crate::repositories::sessions::{find_session_by_id, touch_session};
+use crate::repositories::users::find_user_by_id_str;
+use actix_web::error::InternalError;
+use actix_web::http::header;
+use actix_web::{Error, HttpRequest, HttpResponse, web};
 use futures_util::future::{Ready, ready};
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use log::{error, warn};
 use serde::{Deserialize, Serialize};
-use std::env;
+use uuid::Uuid;
+
+/// Audience of access tokens. Services that check our tokens against
+/// `/.well-known/jwks.json` must require it, since the same keys sign the
+/// short-lived tokens used during login.
+pub const ACCESS_TOKEN_AUDIENCE: &str = "api";
 
 #[derive(Debug, Serialize, Deserialize)]
-struct Claims {
-    sub: String, // Subject (user ID)
-    exp: usize,  // Expiration timestamp (unix seconds)
+pub struct Claims {
+    pub sub: String, // Subject (user ID)
+    pub exp: usize,  // Expiration timestamp (unix seconds)
+    pub aud: String, // Always ACCESS_TOKEN_AUDIENCE
+    /// Session the token was issued for; absent in service account tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
+    /// Space-separated scopes of a service account token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
 }
 
-// Fetch the JWT secret from environment variable at runtime
-fn jwt_secret() -> Vec<u8> {
-    env::var("JWT_SECRET")
-        .expect("JWT_SECRET must be set in environment")
-        .into_bytes()
+/// Creates an access token for a given user ID and session, valid for `ttl_secs`.
+pub fn create_jwt(
+    user_id: &str,
+    session_id: Uuid,
+    ttl_secs: u64,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let claims = Claims {
+        sub: user_id.to_owned(),
+        exp: expiration(ttl_secs),
+        aud: ACCESS_TOKEN_AUDIENCE.to_string(),
+        sid: Some(session_id),
+        scope: None,
+    };
+
+    jwt_keys().sign(&claims)
 }
 
-/// Creates a JWT token for a given user ID with 24H expiration.
-pub fn create_jwt(user_id: &str) -> Result<String, jsonwebtoken::errors::Error> {
+/// Creates an access token for a service account, limited to `scopes` and
+/// valid for `ttl_secs`. It belongs to no session.
+pub fn create_service_jwt(
+    user_id: &str,
+    scopes: &[String],
+    ttl_secs: u64,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let claims = Claims {
+        sub: user_id.to_owned(),
+        exp: expiration(ttl_secs),
+        aud: ACCESS_TOKEN_AUDIENCE.to_string(),
+        sid: None,
+        scope: Some(scopes.join(" ")),
+    };
+
+    jwt_keys().sign(&claims)
+}
+
+/// Expiration time of a token issued now: current time + token lifetime.
+fn expiration(ttl_secs: u64) -> usize {
     use std::time::{SystemTime, UNIX_EPOCH};
 
-    // Calculate expiration time: current time + 24 hours
-    let expiration = SystemTime::now()
+    let now = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("time went backwards")
-        .as_secs()
-        + 60 * 60 * 24;
+        .as_secs();
 
-    let claims = Claims {
-        sub: user_id.to_owned(),
-        exp: expiration as usize,
+    (now + ttl_secs) as usize
+}
+
+/// Validates the JWT token and returns its claims if valid.
+/// This only checks the signature, expiry and audience, not whether the session is still active.
+pub fn validate_jwt(token: &str) -> Option<Claims> {
+    jwt_keys().verify(token, ACCESS_TOKEN_AUDIENCE)
+}
+
+/// Prefix of personal access tokens, which tells them apart from JWTs.
+pub const PAT_PREFIX: &str = "gdpat_";
+
+/// A session's `last_seen_at` and a personal access token's `last_used_at`
+/// are updated at most this often.
+const LAST_SEEN_INTERVAL_SECS: i64 = 60;
+
+/// Realm advertised in `WWW-Authenticate` challenges.
+const AUTH_REALM: &str = "google-drive";
+
+/// Builds a 401 response carrying a `WWW-Authenticate: Bearer` challenge.
+/// `error` is the RFC 6750 error code, omitted when no credentials were sent.
+fn unauthorized(error: Option<&str>, description: &str) -> Error {
+    let challenge = match error {
+        Some(error) => format!(
+            "Bearer realm=\"{}\", error=\"{}\", error_description=\"{}\"",
+            AUTH_REALM, error, description
+        ),
+        None => format!("Bearer realm=\"{}\"", AUTH_REALM),
     };
 
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(&jwt_secret()),
-    )
+    let response = HttpResponse::Unauthorized()
+        .insert_header((header::WWW_AUTHENTICATE, challenge))
+        .json(description);
+
+    InternalError::from_response(description.to_string(), response).into()
 }
 
-/// Validates the JWT token and returns the user_id (subject) if valid.
-pub fn validate_jwt(token: &str) -> Option<String> {
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(&jwt_secret()),
-        &Validation::default(),
-    )
-    .map(|data| data.claims.sub)
-    .ok()
+/// Returns the access token sent with the request.
+/// An `Authorization` header takes precedence over the `auth_token` cookie:
+/// when the header is present the cookie is ignored, even if the header is invalid.
+fn extract_token(req: &HttpRequest) -> Result<Option<String>, Error> {
+    if let Some(value) = req.headers().get(header::AUTHORIZATION) {
+        let value = value
+            .to_str()
+            .map_err(|_| unauthorized(Some("invalid_request"), "Malformed Authorization header"))?;
+
+        return match value.split_once(' ') {
+            Some((scheme, token))
+                if scheme.eq_ignore_ascii_case("bearer") && !token.trim().is_empty() =>
+            {
+                Ok(Some(token.trim().to_string()))
+            }
+            _ => Err(unauthorized(
+                Some("invalid_request"),
+                "Authorization header must use the Bearer scheme",
+            )),
+        };
+    }
+
+    Ok(req
+        .cookie("auth_token")
+        .map(|cookie| cookie.value().to_string()))
 }
 
-/// Extractor for authenticated user from the "auth_token" cookie.
+/// Builds a 403 response for a credential that lacks `scope`.
+fn insufficient_scope(scope: &str) -> Error {
+    let challenge = format!(
+        "Bearer realm=\"{}\", error=\"insufficient_scope\", scope=\"{}\"",
+        AUTH_REALM, scope
+    );
+    let description = format!("Token is missing the '{}' scope", scope);
+
+    let response = HttpResponse::Forbidden()
+        .insert_header((header::WWW_AUTHENTICATE, challenge))
+        .json(&description);
+
+    InternalError::from_response(description, response).into()
+}
+
+/// Extractor for the authenticated user of a request.
+/// The credential is read from an `Authorization: Bearer` header or,
+/// if there is no such header, from the "auth_token" cookie. It is an access
+/// JWT of a login session or a service account, or a personal access token.
 pub struct AuthenticatedUser {
     pub user_id: String,
+    /// Login session the access token belongs to; `None` for personal access
+    /// tokens and service accounts.
+    pub session_id: Option<Uuid>,
+    /// Scopes the credential is limited to; `None` means unrestricted.
+    pub scopes: Option<Vec<String>>,
+    pub is_admin: bool,
+}
+
+impl AuthenticatedUser {
+    /// The numeric `users.id` behind the string user ID the token carries.
+    pub fn id(&self) -> Result<i32, Error> {
+        self.user_id.parse::<i32>().map_err(|_| {
+            warn!("Invalid user id in token: {}", self.user_id);
+            actix_web::error::ErrorUnauthorized("Unauthorized")
+        })
+    }
+
+    /// Fails with 403 unless the credential grants `scope`.
+    pub fn require_scope(&self, scope: &str) -> Result<(), Error> {
+        match &self.scopes {
+            Some(scopes) if !scopes.iter().any(|s| s == scope) => {
+                warn!("User {} lacks scope {}", self.user_id, scope);
+                Err(insufficient_scope(scope))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails with 403 unless the user is an administrator and the credential
+    /// grants the admin scope.
+    pub fn require_admin(&self) -> Result<(), Error> {
+        if !self.is_admin {
+            warn!("User {} is not an administrator", self.user_id);
+            return Err(actix_web::error::ErrorForbidden(
+                "Administrator role required",
+            ));
+        }
+        self.require_scope(scopes::ADMIN)
+    }
+
+    /// Fails with 403 unless the user signed in interactively.
+    /// Used for routes that manage credentials themselves.
+    pub fn require_session(&self) -> Result<(), Error> {
+        if self.session_id.is_none() {
+            return Err(actix_web::error::ErrorForbidden(
+                "This action requires a login session",
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl actix_web::FromRequest for AuthenticatedUser {
     type Error = Error;
     type Future = Ready<Result<Self, Self::Error>>;
 
-    /// Extract user info from JWT stored in "auth_token" cookie.
-    /// Returns Unauthorized error if missing or invalid.
+    /// Extract user info from the bearer token or the "auth_token" cookie.
+    /// Returns Unauthorized error with a `WWW-Authenticate` challenge if the
+    /// credential is missing or invalid, revoked or expired.
     fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
-        if let Some(cookie) = req.cookie("auth_token") {
-            if let Some(user_id) = validate_jwt(cookie.value()) {
-                return ready(Ok(AuthenticatedUser { user_id }));
+        ready(authenticate(req))
+    }
+}
+
+fn authenticate(req: &HttpRequest) -> Result<AuthenticatedUser, Error> {
+    let Some(token) = extract_token(req)? else {
+        return Err(unauthorized(None, "Unauthorized"));
+    };
+
+    let Some(pool) = req.app_data::<web::Data<DbPool>>() else {
+        error!("Database pool is not configured");
+        return Err(actix_web::error::ErrorInternalServerError(
+            "Server misconfiguration",
+        ));
+    };
+
+    let now = chrono::Utc::now().naive_utc();
+
+    let mut service_token = false;
+    let (user_id, session_id, scopes) = if token.starts_with(PAT_PREFIX) {
+        let pat = find_personal_access_token_by_hash(pool, &hash_token(&token)).map_err(|e| {
+            error!("Database error while checking access token: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?;
+
+        match pat {
+            Some(pat) if pat.is_active(now) => {
+                if pat.last_used_at.is_none_or(|last_used_at| {
+                    now - last_used_at > chrono::Duration::seconds(LAST_SEEN_INTERVAL_SECS)
+                }) && let Err(e) = touch_personal_access_token(pool, pat.id)
+                {
+                    warn!("Failed to record use of access token {}: {}", pat.id, e);
+                }
+                (pat.user_id, None, Some(pat.scopes))
+            }
+            _ => {
+                return Err(unauthorized(
+                    Some("invalid_token"),
+                    "Invalid, expired or revoked access token",
+                ));
+            }
+        }
+    } else {
+        let Some(claims) = validate_jwt(&token) else {
+            return Err(unauthorized(
+                Some("invalid_token"),
+                "Invalid or expired token",
+            ));
+        };
+
+        match (claims.sid, claims.scope) {
+            (Some(sid), _) => match find_session_by_id(pool, sid) {
+                Ok(Some(session)) if session.is_active(now) && session.user_id == claims.sub => {
+                    if now - session.last_seen_at
+                        > chrono::Duration::seconds(LAST_SEEN_INTERVAL_SECS)
+                        && let Err(e) = touch_session(pool, sid)
+                    {
+                        warn!("Failed to record use of session {}: {}", sid, e);
+                    }
+                    (claims.sub, Some(sid), None)
+                }
+                Ok(_) => {
+                    warn!("Rejecting token of inactive session {}", sid);
+                    return Err(unauthorized(
+                        Some("invalid_token"),
+                        "Session expired or revoked",
+                    ));
+                }
+                Err(e) => {
+                    error!("Database error while checking session: {}", e);
+                    return Err(actix_web::error::ErrorInternalServerError("Database error"));
+                }
+            },
+            // Service account tokens are short-lived and carry their scopes
+            (None, Some(scope)) => {
+                service_token = true;
+                let scopes = scope.split_whitespace().map(str::to_string).collect();
+                (claims.sub, None, Some(scopes))
             }
+            (None, None) => {
+                return Err(unauthorized(Some("invalid_token"), "Invalid token"));
+            }
+        }
+    };
+
+    let account = load_account(pool, &user_id)?;
+    if service_token && !account.is_service_account() {
+        warn!("Rejecting service account token of user {}", user_id);
+        return Err(unauthorized(Some("invalid_token"), "Invalid token"));
+    }
+
+    Ok(AuthenticatedUser {
+        user_id,
+        session_id,
+        scopes,
+        is_admin: account.is_admin(),
+    })
+}
+
+/// Loads the user a credential belongs to, rejecting suspended and deleted accounts.
+pub fn load_account(pool: &DbPool, user_id: &str) -> Result<User, Error> {
+    match find_user_by_id_str(pool, user_id) {
+        Ok(user) if user.is_deleted() => {
+            warn!("Rejecting credential of deleted user {}", user_id);
+            Err(unauthorized(
+                Some("invalid_token"),
+                "Account has been deleted",
+            ))
+        }
+        Ok(user) if user.is_suspended() => {
+            warn!("Rejecting credential of suspended user {}", user_id);
+            Err(unauthorized(Some("invalid_token"), "Account is suspended"))
+        }
+        Ok(user) => Ok(user),
+        Err(diesel::result::Error::NotFound) => {
+            Err(unauthorized(Some("invalid_token"), "Unknown user"))
+        }
+        Err(e) => {
+            error!("Database error while loading user: {}", e);
+            Err(actix_web::error::ErrorInternalServerError("Database error"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::cookie::Cookie;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+
+    fn token_of(req: TestRequest) -> Result<Option<String>, Error> {
+        extract_token(&req.to_http_request())
+    }
+
+    /// Status and `WWW-Authenticate` header of an error's response, which
+    /// can only be taken once.
+    fn challenge_of(error: &Error) -> (StatusCode, String) {
+        let response = error.error_response();
+        let challenge = response
+            .headers()
+            .get(header::WWW_AUTHENTICATE)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        (response.status(), challenge)
+    }
+
+    #[test]
+    fn bearer_header_is_extracted() {
+        let req = TestRequest::default().insert_header((header::AUTHORIZATION, "Bearer abc.def"));
+        assert_eq!(token_of(req).unwrap().as_deref(), Some("abc.def"));
+
+        let req = TestRequest::default().insert_header((header::AUTHORIZATION, "bearer  abc "));
+        assert_eq!(token_of(req).unwrap().as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn cookie_is_used_without_a_header() {
+        let req = TestRequest::default().cookie(Cookie::new("auth_token", "from-cookie"));
+        assert_eq!(token_of(req).unwrap().as_deref(), Some("from-cookie"));
+
+        assert_eq!(token_of(TestRequest::default()).unwrap(), None);
+    }
+
+    #[test]
+    fn header_takes_precedence_over_cookie() {
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Bearer from-header"))
+            .cookie(Cookie::new("auth_token", "from-cookie"));
+        assert_eq!(token_of(req).unwrap().as_deref(), Some("from-header"));
+
+        // An invalid header is not replaced by the cookie
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Basic dXNlcjpwYXNz"))
+            .cookie(Cookie::new("auth_token", "from-cookie"));
+        assert!(token_of(req).is_err());
+    }
+
+    #[test]
+    fn malformed_headers_are_rejected() {
+        for value in ["Basic dXNlcjpwYXNz", "Bearer", "Bearer   ", "abc.def"] {
+            let req = TestRequest::default().insert_header((header::AUTHORIZATION, value));
+            let (status, challenge) = challenge_of(&token_of(req).unwrap_err());
+
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert!(challenge.contains("error=\"invalid_request\""));
         }
-        ready(Err(actix_web::error::ErrorUnauthorized("Unauthorized")))
+    }
+
+    #[test]
+    fn unauthorized_carries_a_bearer_challenge() {
+        assert_eq!(
+            challenge_of(&unauthorized(None, "Missing credentials")),
+            (
+                StatusCode::UNAUTHORIZED,
+                format!("Bearer realm=\"{}\"", AUTH_REALM)
+            )
+        );
+        assert_eq!(
+            challenge_of(&unauthorized(Some("invalid_token"), "Token expired")),
+            (
+                StatusCode::UNAUTHORIZED,
+                format!(
+                    "Bearer realm=\"{}\", error=\"invalid_token\", error_description=\"Token expired\"",
+                    AUTH_REALM
+                )
+            )
+        );
+    }
+
+    fn user_with_scopes(scopes: Option<&[&str]>, is_admin: bool) -> AuthenticatedUser {
+        AuthenticatedUser {
+            user_id: "42".to_string(),
+            session_id: None,
+            scopes: scopes.map(|scopes| scopes.iter().map(|s| s.to_string()).collect()),
+            is_admin,
+        }
+    }
+
+    #[test]
+    fn unrestricted_credentials_have_every_scope() {
+        let user = user_with_scopes(None, false);
+        assert!(user.require_scope(scopes::FILES_READ).is_ok());
+        assert!(user.require_scope(scopes::FILES_DELETE).is_ok());
+    }
+
+    #[test]
+    fn scoped_credentials_are_limited_to_their_scopes() {
+        let user = user_with_scopes(Some(&[scopes::FILES_READ]), false);
+        assert!(user.require_scope(scopes::FILES_READ).is_ok());
+
+        let (status, challenge) =
+            challenge_of(&user.require_scope(scopes::FILES_WRITE).unwrap_err());
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(challenge.contains("error=\"insufficient_scope\""));
+        assert!(challenge.contains("scope=\"files.write\""));
+    }
+
+    #[test]
+    fn admin_routes_need_the_role_and_the_scope() {
+        assert!(user_with_scopes(None, true).require_admin().is_ok());
+        assert!(user_with_scopes(None, false).require_admin().is_err());
+        assert!(
+            user_with_scopes(Some(&[scopes::FILES_READ]), true)
+                .require_admin()
+                .is_err()
+        );
+        assert!(
+            user_with_scopes(Some(&[scopes::ADMIN]), true)
+                .require_admin()
+                .is_ok()
+        );
     }
 }
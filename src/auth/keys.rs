@@ -0,0 +1,322 @@
+use actix_web::HttpResponse;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use ring::signature::{Ed25519KeyPair, KeyPair, RsaKeyPair, RsaPublicKeyComponents};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::{env, fs};
+
+/// Key ID used when `JWT_KEY_ID` is not set.
+const DEFAULT_KEY_ID: &str = "primary";
+
+/// A key that may verify tokens, with the only algorithm it is accepted for.
+struct VerificationKey {
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// Keys used to sign and verify the JWTs issued by this server.
+pub struct JwtKeys {
+    /// Header of new tokens: the signing algorithm and key ID.
+    header: Header,
+    encoding_key: EncodingKey,
+    /// Keys that still verify tokens, by key ID. Includes the signing key.
+    verification_keys: HashMap<String, VerificationKey>,
+    /// Public keys published at `/.well-known/jwks.json`; empty for HS256.
+    public_keys: JwkSet,
+}
+
+static JWT_KEYS: OnceLock<JwtKeys> = OnceLock::new();
+
+/// Returns the JWT keys, loading them from the environment on first use.
+/// Panics if the keys are misconfigured, so call it at startup.
+pub fn jwt_keys() -> &'static JwtKeys {
+    JWT_KEYS.get_or_init(|| {
+        JwtKeys::from_env().unwrap_or_else(|e| panic!("Invalid JWT key configuration: {}", e))
+    })
+}
+
+impl JwtKeys {
+    /// Loads the keys from the environment.
+    /// `JWT_ALGORITHM` selects `HS256` (default, signed with `JWT_SECRET`),
+    /// `RS256` or `EdDSA` (signed with the PEM private key at
+    /// `JWT_PRIVATE_KEY_PATH`). New tokens carry `JWT_KEY_ID` as `kid`.
+    /// `JWT_PREVIOUS_KEYS_PATH` may point to a JWKS file of retired public keys
+    /// that keep verifying tokens issued before a rotation.
+    fn from_env() -> Result<Self, String> {
+        let algorithm = env::var("JWT_ALGORITHM").unwrap_or_else(|_| "HS256".to_string());
+        let algorithm = Algorithm::from_str(&algorithm)
+            .map_err(|_| format!("Unsupported JWT_ALGORITHM '{}'", algorithm))?;
+        let key_id = env::var("JWT_KEY_ID").unwrap_or_else(|_| DEFAULT_KEY_ID.to_string());
+
+        let mut header = Header::new(algorithm);
+        header.kid = Some(key_id.clone());
+
+        let mut verification_keys = HashMap::new();
+        let mut public_keys = JwkSet { keys: Vec::new() };
+
+        let encoding_key = match algorithm {
+            Algorithm::HS256 => {
+                let secret = env::var("JWT_SECRET").map_err(|_| "JWT_SECRET must be set")?;
+                verification_keys.insert(
+                    key_id,
+                    VerificationKey {
+                        algorithm,
+                        key: DecodingKey::from_secret(secret.as_bytes()),
+                    },
+                );
+                EncodingKey::from_secret(secret.as_bytes())
+            }
+            Algorithm::RS256 | Algorithm::EdDSA => {
+                let path = env::var("JWT_PRIVATE_KEY_PATH")
+                    .map_err(|_| "JWT_PRIVATE_KEY_PATH must be set")?;
+                let pem_data =
+                    fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+                let (encoding_key, jwk) = if algorithm == Algorithm::RS256 {
+                    (
+                        EncodingKey::from_rsa_pem(&pem_data).map_err(|e| e.to_string())?,
+                        rsa_public_jwk(&pem_data, &key_id)?,
+                    )
+                } else {
+                    (
+                        EncodingKey::from_ed_pem(&pem_data).map_err(|e| e.to_string())?,
+                        ed25519_public_jwk(&pem_data, &key_id)?,
+                    )
+                };
+
+                add_public_key(&mut verification_keys, &mut public_keys, jwk)?;
+                encoding_key
+            }
+            other => return Err(format!("JWT_ALGORITHM {:?} is not supported", other)),
+        };
+
+        if let Ok(path) = env::var("JWT_PREVIOUS_KEYS_PATH") {
+            let data = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            let previous: JwkSet = serde_json::from_slice(&data)
+                .map_err(|e| format!("Invalid JWKS in {}: {}", path, e))?;
+
+            for jwk in previous.keys {
+                let kid = jwk.common.key_id.clone().unwrap_or_default();
+                if verification_keys.contains_key(&kid) {
+                    continue;
+                }
+                add_public_key(&mut verification_keys, &mut public_keys, jwk)?;
+            }
+        }
+
+        Ok(JwtKeys {
+            header,
+            encoding_key,
+            verification_keys,
+            public_keys,
+        })
+    }
+
+    /// Signs `claims` with the current signing key.
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        encode(&self.header, claims, &self.encoding_key)
+    }
+
+    /// Verifies the signature, expiry and audience of `token` and returns its
+    /// claims. Every token we sign names what it is for in `aud`, so a token
+    /// issued for one purpose is never accepted for another.
+    /// The key is picked by the `kid` header; tokens without one are checked
+    /// against the current signing key.
+    pub fn verify<T: DeserializeOwned>(&self, token: &str, audience: &str) -> Option<T> {
+        let header = jsonwebtoken::decode_header(token).ok()?;
+        let kid = header.kid.as_ref().or(self.header.kid.as_ref())?;
+        let key = self.verification_keys.get(kid)?;
+
+        // The algorithm comes from our key, never from the token
+        if header.alg != key.algorithm {
+            return None;
+        }
+
+        let mut validation = Validation::new(key.algorithm);
+        validation.set_audience(&[audience]);
+        validation.set_required_spec_claims(&["exp", "aud"]);
+
+        decode::<T>(token, &key.key, &validation)
+            .map(|data| data.claims)
+            .ok()
+    }
+}
+
+fn add_public_key(
+    verification_keys: &mut HashMap<String, VerificationKey>,
+    public_keys: &mut JwkSet,
+    jwk: Jwk,
+) -> Result<(), String> {
+    let kid = jwk
+        .common
+        .key_id
+        .clone()
+        .ok_or("Verification keys must have a 'kid'")?;
+    let algorithm = jwk
+        .common
+        .key_algorithm
+        .and_then(|alg| Algorithm::from_str(&alg.to_string()).ok())
+        .ok_or_else(|| format!("Verification key '{}' must have a supported 'alg'", kid))?;
+
+    if matches!(
+        algorithm,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        return Err(format!("Verification key '{}' must not be symmetric", kid));
+    }
+
+    let key = DecodingKey::from_jwk(&jwk)
+        .map_err(|e| format!("Invalid verification key '{}': {}", kid, e))?;
+
+    verification_keys.insert(kid, VerificationKey { algorithm, key });
+    public_keys.keys.push(jwk);
+    Ok(())
+}
+
+/// Derives the public JWK of an RSA private key (PKCS#1 or PKCS#8 PEM).
+fn rsa_public_jwk(pem_data: &[u8], kid: &str) -> Result<Jwk, String> {
+    let pem = pem::parse(pem_data).map_err(|e| format!("Invalid PEM: {}", e))?;
+    let key_pair = match pem.tag() {
+        "RSA PRIVATE KEY" => RsaKeyPair::from_der(pem.contents()),
+        _ => RsaKeyPair::from_pkcs8(pem.contents()),
+    }
+    .map_err(|e| format!("Invalid RSA private key: {}", e))?;
+
+    let components = RsaPublicKeyComponents::<Vec<u8>>::from(key_pair.public());
+
+    public_jwk(serde_json::json!({
+        "kty": "RSA",
+        "use": "sig",
+        "alg": "RS256",
+        "kid": kid,
+        "n": URL_SAFE_NO_PAD.encode(&components.n),
+        "e": URL_SAFE_NO_PAD.encode(&components.e),
+    }))
+}
+
+/// Derives the public JWK of an Ed25519 private key (PKCS#8 PEM).
+fn ed25519_public_jwk(pem_data: &[u8], kid: &str) -> Result<Jwk, String> {
+    let pem = pem::parse(pem_data).map_err(|e| format!("Invalid PEM: {}", e))?;
+    let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(pem.contents())
+        .map_err(|e| format!("Invalid Ed25519 private key: {}", e))?;
+
+    public_jwk(serde_json::json!({
+        "kty": "OKP",
+        "crv": "Ed25519",
+        "use": "sig",
+        "alg": "EdDSA",
+        "kid": kid,
+        "x": URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref()),
+    }))
+}
+
+fn public_jwk(value: serde_json::Value) -> Result<Jwk, String> {
+    serde_json::from_value(value).map_err(|e| format!("Failed to build JWK: {}", e))
+}
+
+/// GET /.well-known/jwks.json
+/// Publishes the public keys that verify our access tokens, so other services
+/// can check them without sharing a secret. Empty when HS256 is used.
+pub async fn jwks() -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "public, max-age=300"))
+        .json(&jwt_keys().public_keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_keys() -> JwtKeys {
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some(DEFAULT_KEY_ID.to_string());
+
+        let mut verification_keys = HashMap::new();
+        verification_keys.insert(
+            DEFAULT_KEY_ID.to_string(),
+            VerificationKey {
+                algorithm: Algorithm::HS256,
+                key: DecodingKey::from_secret(b"test secret"),
+            },
+        );
+
+        JwtKeys {
+            header,
+            encoding_key: EncodingKey::from_secret(b"test secret"),
+            verification_keys,
+            public_keys: JwkSet { keys: Vec::new() },
+        }
+    }
+
+    fn claims(audience: Option<&str>) -> serde_json::Value {
+        let exp = chrono::Utc::now().timestamp() + 60;
+        match audience {
+            Some(audience) => serde_json::json!({ "sub": "7", "exp": exp, "aud": audience }),
+            None => serde_json::json!({ "sub": "7", "exp": exp }),
+        }
+    }
+
+    #[test]
+    fn tokens_verify_only_for_their_audience() {
+        let keys = test_keys();
+        let token = keys.sign(&claims(Some("two_factor"))).unwrap();
+
+        assert!(
+            keys.verify::<serde_json::Value>(&token, "two_factor")
+                .is_some()
+        );
+        assert!(keys.verify::<serde_json::Value>(&token, "api").is_none());
+        assert!(
+            keys.verify::<serde_json::Value>(&token, "oauth_state")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn tokens_without_audience_are_rejected_when_one_is_required() {
+        let keys = test_keys();
+        let token = keys.sign(&claims(None)).unwrap();
+
+        assert!(keys.verify::<serde_json::Value>(&token, "api").is_none());
+    }
+
+    #[test]
+    fn expired_and_tampered_tokens_are_rejected() {
+        let keys = test_keys();
+        let expired = keys
+            .sign(&serde_json::json!({ "sub": "7", "exp": 1, "aud": "api" }))
+            .unwrap();
+        assert!(keys.verify::<serde_json::Value>(&expired, "api").is_none());
+
+        // The payload of one token under the signature of another
+        let token = keys.sign(&claims(Some("api"))).unwrap();
+        let other = keys
+            .sign(&serde_json::json!({ "sub": "8", "exp": 4102444800u64, "aud": "api" }))
+            .unwrap();
+        let parts: Vec<&str> = token.split('.').collect();
+        let other_parts: Vec<&str> = other.split('.').collect();
+        let tampered = format!("{}.{}.{}", parts[0], other_parts[1], parts[2]);
+        assert!(keys.verify::<serde_json::Value>(&tampered, "api").is_none());
+    }
+
+    #[test]
+    fn tokens_with_another_algorithm_are_rejected() {
+        let keys = test_keys();
+        let mut header = Header::new(Algorithm::HS384);
+        header.kid = Some(DEFAULT_KEY_ID.to_string());
+        let token = encode(
+            &header,
+            &claims(Some("api")),
+            &EncodingKey::from_secret(b"test secret"),
+        )
+        .unwrap();
+
+        assert!(keys.verify::<serde_json::Value>(&token, "api").is_none());
+    }
+}
@@ -0,0 +1,220 @@
+use crate::auth::oauth_state::state_removal_cookie;
+use crate::auth::session::{ClientInfo, session_cookies, start_session};
+use crate::auth::two_factor::{begin_two_factor_login, has_two_factor};
+use crate::database::DbPool;
+use crate::models::user_identities::NewUserIdentity;
+use crate::models::users::{NewUser, User};
+use crate::policy::TwoFactorPolicy;
+use crate::repositories::user_identities::{
+    LinkOutcome, find_identity, link_identity, record_identity_login,
+};
+use crate::repositories::users::{
+    find_user_by_id, find_user_by_id_str, insert_user_with_identity, update_user_profile,
+};
+use actix_web::{Error, HttpResponse};
+use log::{error, info, warn};
+
+/// A user as identified by an external login provider.
+pub struct ProviderIdentity {
+    /// Provider name, stored in `user_identities.provider`.
+    pub provider: String,
+    /// Stable user ID at the provider, stored in `user_identities.subject`.
+    pub subject: String,
+    pub email: Option<String>,
+    pub username: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+/// Finishes a successful provider login: finds or creates the user, starts a
+/// session and redirects to `/auth-success` with the session cookies set.
+/// Users with two-factor authentication, or who must set it up, are sent to
+/// the two-factor step instead and get no session yet.
+pub fn complete_login(
+    pool: &DbPool,
+    two_factor: &TwoFactorPolicy,
+    identity: ProviderIdentity,
+    client: ClientInfo,
+) -> Result<HttpResponse, Error> {
+    info!(
+        "Completing {} login: email={:?}, id={:?}",
+        identity.provider, identity.email, identity.subject
+    );
+
+    // Find the account the identity is linked to, or create one
+    let account = find_account(pool, &identity)?;
+    let profile = NewUser {
+        // Providers only report verified emails
+        email_verified: identity.email.as_ref().map(|_| true),
+        email: identity.email,
+        username: identity.username,
+        avatar_url: identity.avatar_url,
+    };
+
+    let user = match account {
+        Some(user) if user.is_deleted() => {
+            warn!("Deleted user {} tried to log in", user.id);
+            return Ok(login_error_redirect("account_deleted"));
+        }
+        Some(user) if user.is_suspended() => {
+            warn!("Suspended user {} tried to log in", user.id);
+            return Ok(login_error_redirect("account_suspended"));
+        }
+        Some(user) => {
+            info!("User already exists in DB: id={}", user.id);
+
+            // The provider is the source of truth for the profile
+            match update_user_profile(pool, user.id, &profile) {
+                Ok(updated) => updated,
+                Err(e) => {
+                    warn!("Failed to update profile of user {}: {}", user.id, e);
+                    user
+                }
+            }
+        }
+        None => {
+            info!("User not found in DB, inserting new user");
+            insert_user_with_identity(pool, &profile, &identity.provider, &identity.subject)
+                .map_err(|e| {
+                    error!("Failed to insert user: {}", e);
+                    actix_web::error::ErrorInternalServerError("Failed to insert user")
+                })?
+        }
+    };
+    let user_id = user.id.to_string();
+
+    // Hold the session back until the second factor is verified
+    let enrolled = has_two_factor(pool, &user_id)?;
+    if enrolled || two_factor.required {
+        let mut response = begin_two_factor_login(&user_id, !enrolled)?;
+        response.add_cookie(&state_removal_cookie())?;
+        return Ok(response);
+    }
+
+    // Start a session and issue the access/refresh token pair
+    let tokens = start_session(pool, &user_id, client)?;
+    let [access_cookie, refresh_cookie] = session_cookies(&tokens);
+
+    info!("Setting session cookies and redirecting to /auth-success");
+
+    // Redirect to /auth-success with the cookies set
+    Ok(HttpResponse::Found()
+        .append_header(("Location", "/auth-success"))
+        .cookie(access_cookie)
+        .cookie(refresh_cookie)
+        .cookie(state_removal_cookie())
+        .finish())
+}
+
+/// Loads the account an identity is linked to and records the login.
+fn find_account(pool: &DbPool, identity: &ProviderIdentity) -> Result<Option<User>, Error> {
+    let db_error = |e: diesel::result::Error| {
+        error!("Database error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    };
+
+    let Some(linked) =
+        find_identity(pool, &identity.provider, &identity.subject).map_err(db_error)?
+    else {
+        return Ok(None);
+    };
+
+    record_identity_login(pool, linked.id, identity.email.as_deref()).map_err(db_error)?;
+    find_user_by_id(pool, linked.user_id)
+        .map(Some)
+        .map_err(db_error)
+}
+
+/// Finishes a provider flow started by a signed-in user to link another
+/// identity to their account, and redirects to `/auth-linked`. An identity
+/// that already belongs to another account is refused with `identity_in_use`.
+pub fn complete_link(
+    pool: &DbPool,
+    user_id: &str,
+    identity: ProviderIdentity,
+) -> Result<HttpResponse, Error> {
+    let user = match find_user_by_id_str(pool, user_id) {
+        Ok(user) if !user.is_deleted() && !user.is_suspended() => user,
+        Ok(_) | Err(diesel::result::Error::NotFound) => {
+            warn!("Rejecting identity link for unavailable user {}", user_id);
+            return Ok(login_error_redirect("link_failed"));
+        }
+        Err(e) => {
+            error!("Database error: {}", e);
+            return Err(actix_web::error::ErrorInternalServerError("Database error"));
+        }
+    };
+
+    let new_identity = NewUserIdentity {
+        user_id: user.id,
+        provider: identity.provider.clone(),
+        subject: identity.subject,
+        email: identity.email,
+    };
+
+    match link_identity(pool, &new_identity) {
+        Ok(LinkOutcome::Linked(linked)) => {
+            info!(
+                "Linked {} identity {} to user {}",
+                linked.provider, linked.id, user.id
+            );
+        }
+        Ok(LinkOutcome::AlreadyLinked) => {
+            info!(
+                "{} identity is already linked to user {}",
+                identity.provider, user.id
+            );
+        }
+        Ok(LinkOutcome::InUse) => {
+            warn!(
+                "User {} tried to link a {} identity of another account",
+                user.id, identity.provider
+            );
+            return Ok(login_error_redirect("identity_in_use"));
+        }
+        Err(e) => {
+            error!("Failed to link identity: {}", e);
+            return Ok(login_error_redirect("link_failed"));
+        }
+    }
+
+    Ok(HttpResponse::Found()
+        .append_header((
+            "Location",
+            page_url("/auth-linked", "provider", &identity.provider),
+        ))
+        .cookie(state_removal_cookie())
+        .finish())
+}
+
+/// Redirects to `/auth-error` with the given error code and clears the OAuth state.
+pub fn login_error_redirect(error: &str) -> HttpResponse {
+    HttpResponse::Found()
+        .append_header(("Location", page_url("/auth-error", "error", error)))
+        .cookie(state_removal_cookie())
+        .finish()
+}
+
+/// Builds a redirect target with a single, percent-encoded query parameter.
+fn page_url(path: &str, name: &str, value: &str) -> String {
+    let query = oauth2::url::form_urlencoded::Serializer::new(String::new())
+        .append_pair(name, value)
+        .finish();
+    format!("{}?{}", path, query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_url_encodes_the_value() {
+        assert_eq!(
+            page_url("/auth-error", "error", "invalid_state"),
+            "/auth-error?error=invalid_state"
+        );
+        assert_eq!(
+            page_url("/auth-linked", "provider", "a&b=c d\r\nSet-Cookie:x"),
+            "/auth-linked?provider=a%26b%3Dc+d%0D%0ASet-Cookie%3Ax"
+        );
+    }
+}
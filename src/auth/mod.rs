@@ -1,2 +1,11 @@
-pub mod google;
+pub mod github;
 pub mod jwt;
+pub mod keys;
+pub mod login;
+pub mod oauth_state;
+pub mod oidc;
+pub mod providers;
+pub mod scopes;
+pub mod service_accounts;
+pub mod session;
+pub mod two_factor;
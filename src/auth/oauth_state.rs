@@ -0,0 +1,181 @@
+use crate::auth::keys::jwt_keys;
+use actix_web::HttpRequest;
+use actix_web::cookie::{Cookie, SameSite, time::Duration};
+use oauth2::PkceCodeVerifier;
+use serde::{Deserialize, Serialize};
+
+/// Cookie holding the signed OAuth state between the redirect and the callback.
+pub const OAUTH_STATE_COOKIE: &str = "oauth_state";
+
+/// How long a login attempt may take before the state expires.
+const STATE_TTL_SECS: u64 = 10 * 60;
+
+/// Audience of state cookies, so they are never taken for another token.
+const STATE_AUDIENCE: &str = "oauth_state";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StateClaims {
+    state: String,         // CSRF token sent to the provider as `state`
+    pkce_verifier: String, // PKCE code verifier for the token exchange
+    nonce: String,         // OpenID Connect nonce expected in the ID token
+    provider: String,      // Provider the login was started with
+    aud: String,           // Always STATE_AUDIENCE
+    exp: usize,            // Expiration timestamp (unix seconds)
+    /// Account to link the identity to, instead of signing in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    link_user: Option<String>,
+}
+
+/// Values remembered between the redirect to the provider and the callback.
+pub struct VerifiedState {
+    pub pkce_verifier: PkceCodeVerifier,
+    pub nonce: String,
+    /// Set when a signed-in user started linking `provider` to their account.
+    pub link_user: Option<String>,
+}
+
+/// Builds the short-lived, signed, HTTP-only cookie that remembers the
+/// CSRF state, PKCE verifier and nonce of a login started with `provider`.
+/// `link_user` is set when the flow links the identity to that account.
+pub fn create_state_cookie(
+    provider: &str,
+    state: &str,
+    pkce_verifier: &str,
+    nonce: &str,
+    link_user: Option<&str>,
+) -> Result<Cookie<'static>, jsonwebtoken::errors::Error> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let expiration = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time went backwards")
+        .as_secs()
+        + STATE_TTL_SECS;
+
+    let claims = StateClaims {
+        state: state.to_owned(),
+        pkce_verifier: pkce_verifier.to_owned(),
+        nonce: nonce.to_owned(),
+        provider: provider.to_owned(),
+        link_user: link_user.map(str::to_owned),
+        aud: STATE_AUDIENCE.to_string(),
+        exp: expiration as usize,
+    };
+
+    let token = jwt_keys().sign(&claims)?;
+
+    Ok(Cookie::build(OAUTH_STATE_COOKIE, token)
+        .http_only(true)
+        .secure(false) // should be true in production with HTTPS
+        .path("/auth")
+        .same_site(SameSite::Lax)
+        .max_age(Duration::seconds(STATE_TTL_SECS as i64))
+        .finish())
+}
+
+/// Cookie that removes the OAuth state cookie from the browser.
+pub fn state_removal_cookie() -> Cookie<'static> {
+    let mut cookie = Cookie::build(OAUTH_STATE_COOKIE, "").path("/auth").finish();
+    cookie.make_removal();
+    cookie
+}
+
+/// Validates the `state` returned by `provider` against the state cookie and
+/// returns the PKCE verifier and nonce of the login.
+/// Fails if the cookie is missing, expired, tampered with, was issued for
+/// another provider, or does not match the returned state.
+pub fn verify_state(
+    req: &HttpRequest,
+    provider: &str,
+    returned_state: Option<&str>,
+) -> Result<VerifiedState, &'static str> {
+    let cookie = req
+        .cookie(OAUTH_STATE_COOKIE)
+        .ok_or("missing OAuth state cookie")?;
+
+    let claims: StateClaims = jwt_keys()
+        .verify(cookie.value(), STATE_AUDIENCE)
+        .ok_or("invalid or expired OAuth state cookie")?;
+
+    claims.check(provider, returned_state)
+}
+
+impl StateClaims {
+    /// Checks verified claims against the provider of the callback and the
+    /// `state` it returned.
+    fn check(
+        self,
+        provider: &str,
+        returned_state: Option<&str>,
+    ) -> Result<VerifiedState, &'static str> {
+        if self.provider != provider {
+            return Err("OAuth state was issued for another provider");
+        }
+
+        match returned_state {
+            Some(state) if state == self.state => Ok(VerifiedState {
+                pkce_verifier: PkceCodeVerifier::new(self.pkce_verifier),
+                nonce: self.nonce,
+                link_user: self.link_user,
+            }),
+            Some(_) => Err("OAuth state mismatch"),
+            None => Err("missing OAuth state parameter"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims() -> StateClaims {
+        StateClaims {
+            state: "csrf-token".to_string(),
+            pkce_verifier: "verifier".to_string(),
+            nonce: "nonce".to_string(),
+            provider: "google".to_string(),
+            aud: STATE_AUDIENCE.to_string(),
+            exp: 0,
+            link_user: None,
+        }
+    }
+
+    #[test]
+    fn matching_state_returns_the_login_values() {
+        let verified = claims().check("google", Some("csrf-token")).unwrap();
+        assert_eq!(verified.pkce_verifier.secret(), "verifier");
+        assert_eq!(verified.nonce, "nonce");
+        assert_eq!(verified.link_user, None);
+    }
+
+    #[test]
+    fn mismatched_or_missing_state_is_rejected() {
+        assert!(claims().check("google", Some("other-token")).is_err());
+        assert!(claims().check("google", Some("")).is_err());
+        assert!(claims().check("google", None).is_err());
+    }
+
+    #[test]
+    fn state_of_another_provider_is_rejected() {
+        assert!(claims().check("github", Some("csrf-token")).is_err());
+    }
+
+    #[test]
+    fn link_user_is_kept() {
+        let claims = StateClaims {
+            link_user: Some("42".to_string()),
+            ..claims()
+        };
+        let verified = claims.check("google", Some("csrf-token")).unwrap();
+        assert_eq!(verified.link_user.as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn link_user_is_optional_in_the_cookie() {
+        let json = serde_json::to_value(claims()).unwrap();
+        assert!(json.get("link_user").is_none());
+
+        let parsed: StateClaims = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.link_user, None);
+    }
+}
@@ -0,0 +1,266 @@
+use crate::auth::github::{self, GitHubProvider, GitHubSettings};
+use crate::auth::jwt::AuthenticatedUser;
+use crate::auth::login::{ProviderIdentity, complete_link, complete_login, login_error_redirect};
+use crate::auth::oauth_state::{create_state_cookie, verify_state};
+use crate::auth::oidc::{OidcProvider, OidcSettings};
+use crate::auth::session::ClientInfo;
+use crate::database::DbPool;
+use crate::policy::TwoFactorPolicy;
+use crate::requests::oauth::OAuthCallbackQuery;
+use actix_web::{Error, HttpRequest, HttpResponse, web};
+use log::{error, info, warn};
+use oauth2::{CsrfToken, PkceCodeVerifier};
+use std::collections::HashMap;
+use std::env;
+
+/// Issuer used for the `google` provider when none is configured.
+const GOOGLE_ISSUER: &str = "https://accounts.google.com";
+
+/// Scopes requested when a provider does not configure its own.
+const DEFAULT_SCOPES: &str = "openid email profile";
+
+/// A login provider: any OpenID Connect server, or GitHub.
+pub enum LoginProvider {
+    Oidc(OidcProvider),
+    GitHub(GitHubProvider),
+}
+
+impl LoginProvider {
+    /// Builds the authorization URL. Returns the URL together with the CSRF
+    /// state, PKCE verifier and nonce that must be kept until the callback.
+    fn authorize_url(&self) -> (String, CsrfToken, PkceCodeVerifier, String) {
+        match self {
+            LoginProvider::Oidc(provider) => provider.authorize_url(),
+            LoginProvider::GitHub(provider) => {
+                let (url, csrf_token, pkce_verifier) = provider.authorize_url();
+                // GitHub issues no ID token, the nonce is kept only for a uniform state
+                let nonce = CsrfToken::new_random().secret().clone();
+                (url, csrf_token, pkce_verifier, nonce)
+            }
+        }
+    }
+
+    async fn authenticate(
+        &self,
+        code: String,
+        pkce_verifier: PkceCodeVerifier,
+        nonce: &str,
+    ) -> Result<ProviderIdentity, String> {
+        match self {
+            LoginProvider::Oidc(provider) => {
+                provider.authenticate(code, pkce_verifier, nonce).await
+            }
+            LoginProvider::GitHub(provider) => provider.authenticate(code, pkce_verifier).await,
+        }
+    }
+}
+
+/// Login providers enabled on this server, keyed by the name used in
+/// `/auth/{provider}` and stored in `user_identities.provider`.
+pub struct ProviderRegistry {
+    providers: HashMap<String, LoginProvider>,
+}
+
+impl ProviderRegistry {
+    /// Builds the registry from the environment.
+    /// `AUTH_PROVIDERS` lists the enabled providers (default: `google`); each one
+    /// is configured with `OIDC_<NAME>_ISSUER`, `_CLIENT_ID`, `_CLIENT_SECRET`,
+    /// `_REDIRECT_URI` and optionally `_SCOPES`, except `github` which uses
+    /// `GITHUB_CLIENT_ID`, `GITHUB_CLIENT_SECRET` and `GITHUB_REDIRECT_URI`.
+    /// Providers that are misconfigured or fail discovery are logged and left out.
+    pub async fn from_env() -> Self {
+        let names = env::var("AUTH_PROVIDERS").unwrap_or_else(|_| "google".to_string());
+        let mut providers = HashMap::new();
+
+        for name in names
+            .split(',')
+            .map(|n| n.trim().to_lowercase())
+            .filter(|n| !n.is_empty())
+        {
+            let provider = if name == github::PROVIDER {
+                github_settings()
+                    .and_then(GitHubProvider::new)
+                    .map(LoginProvider::GitHub)
+            } else {
+                match oidc_settings(&name) {
+                    Ok(settings) => OidcProvider::discover(&name, settings)
+                        .await
+                        .map(LoginProvider::Oidc),
+                    Err(e) => Err(e),
+                }
+            };
+
+            match provider {
+                Ok(provider) => {
+                    info!("Enabled login provider '{}'", name);
+                    providers.insert(name, provider);
+                }
+                Err(e) => error!("Login provider '{}' is disabled: {}", name, e),
+            }
+        }
+
+        if providers.is_empty() {
+            warn!("No login providers are available");
+        }
+
+        ProviderRegistry { providers }
+    }
+
+    fn get(&self, name: &str) -> Result<&LoginProvider, Error> {
+        self.providers.get(name).ok_or_else(|| {
+            warn!("Unknown login provider '{}'", name);
+            actix_web::error::ErrorNotFound(format!("Unknown login provider '{}'", name))
+        })
+    }
+}
+
+/// Reads the `OIDC_<NAME>_*` settings of a provider.
+/// The `google` provider also accepts the legacy `CLIENT_ID`, `CLIENT_SECRET`
+/// and `REDIRECT_URI` variables and defaults to Google's issuer.
+fn oidc_settings(name: &str) -> Result<OidcSettings, String> {
+    let prefix = format!("OIDC_{}_", name.to_uppercase().replace('-', "_"));
+    let legacy = name == "google";
+
+    let setting = |key: &str| {
+        env::var(format!("{}{}", prefix, key))
+            .ok()
+            .or_else(|| legacy.then(|| env::var(key).ok()).flatten())
+            .ok_or_else(|| format!("{}{} must be set", prefix, key))
+    };
+
+    let issuer = match setting("ISSUER") {
+        Ok(issuer) => issuer,
+        Err(_) if legacy => GOOGLE_ISSUER.to_string(),
+        Err(e) => return Err(e),
+    };
+
+    let scopes = env::var(format!("{}SCOPES", prefix)).unwrap_or_else(|_| DEFAULT_SCOPES.into());
+
+    Ok(OidcSettings {
+        issuer,
+        client_id: setting("CLIENT_ID")?,
+        client_secret: setting("CLIENT_SECRET")?,
+        redirect_uri: setting("REDIRECT_URI")?,
+        scopes: scopes.split_whitespace().map(str::to_string).collect(),
+    })
+}
+
+/// Reads the `GITHUB_*` settings of the GitHub OAuth app.
+fn github_settings() -> Result<GitHubSettings, String> {
+    let setting = |key: &str| env::var(key).map_err(|_| format!("{} must be set", key));
+
+    Ok(GitHubSettings {
+        client_id: setting("GITHUB_CLIENT_ID")?,
+        client_secret: setting("GITHUB_CLIENT_SECRET")?,
+        redirect_uri: setting("GITHUB_REDIRECT_URI")?,
+    })
+}
+
+/// GET /auth/{provider}
+/// Redirects the user to the provider's authorization endpoint.
+/// The CSRF state, PKCE verifier and nonce are kept in a signed cookie until the callback.
+pub async fn provider_auth(
+    registry: web::Data<ProviderRegistry>,
+    name: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    redirect_to_provider(&registry, &name, None)
+}
+
+/// GET /auth/{provider}/link
+/// Like `/auth/{provider}`, but the callback links the identity to the
+/// signed-in user's account instead of signing in.
+pub async fn provider_link(
+    registry: web::Data<ProviderRegistry>,
+    name: web::Path<String>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+    info!("User {} starts linking a {} identity", user.user_id, name);
+    redirect_to_provider(&registry, &name, Some(&user.user_id))
+}
+
+fn redirect_to_provider(
+    registry: &ProviderRegistry,
+    name: &str,
+    link_user: Option<&str>,
+) -> Result<HttpResponse, Error> {
+    let provider = registry.get(name)?;
+    let (auth_url, csrf_token, pkce_verifier, nonce) = provider.authorize_url();
+
+    let state_cookie = create_state_cookie(
+        name,
+        csrf_token.secret(),
+        pkce_verifier.secret(),
+        &nonce,
+        link_user,
+    )
+    .map_err(|e| {
+        error!("Failed to sign OAuth state: {:?}", e);
+        actix_web::error::ErrorInternalServerError("Failed to start login")
+    })?;
+
+    info!("Redirecting to {} authorization URL", name);
+
+    Ok(HttpResponse::Found()
+        .append_header(("Location", auth_url))
+        .cookie(state_cookie)
+        .finish())
+}
+
+/// GET /auth/{provider}/callback
+/// Handles the authorization callback: checks the state, exchanges the code,
+/// identifies the user with the provider and signs them in.
+pub async fn provider_callback(
+    req: HttpRequest,
+    query: web::Query<OAuthCallbackQuery>,
+    registry: web::Data<ProviderRegistry>,
+    name: web::Path<String>,
+    db_pool: web::Data<DbPool>,
+    two_factor: TwoFactorPolicy,
+) -> Result<HttpResponse, Error> {
+    let provider = registry.get(&name)?;
+
+    match (&query.code, &query.error) {
+        (Some(auth_code), None) => {
+            info!("Received {} callback with code", name);
+
+            // Check the returned state against the state cookie (login CSRF)
+            let state = match verify_state(&req, &name, query.state.as_deref()) {
+                Ok(state) => state,
+                Err(reason) => {
+                    warn!("Rejecting {} callback: {}", name, reason);
+                    return Ok(login_error_redirect("invalid_state"));
+                }
+            };
+
+            let identity = match provider
+                .authenticate(auth_code.clone(), state.pkce_verifier, &state.nonce)
+                .await
+            {
+                Ok(identity) => identity,
+                Err(e) => {
+                    error!("{} login failed: {}", name, e);
+                    return Ok(login_error_redirect("login_failed"));
+                }
+            };
+
+            match state.link_user {
+                Some(user_id) => complete_link(&db_pool, &user_id, identity),
+                None => complete_login(
+                    &db_pool,
+                    &two_factor,
+                    identity,
+                    ClientInfo::from_request(&req),
+                ),
+            }
+        }
+        (None, Some(err)) => {
+            error!("OAuth error received from {}: {}", name, err);
+            Ok(login_error_redirect(err))
+        }
+        _ => {
+            error!("Invalid OAuth callback request");
+            Ok(login_error_redirect("invalid_request"))
+        }
+    }
+}
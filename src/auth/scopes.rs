@@ -0,0 +1,14 @@
+/// Download files and read their metadata.
+pub const FILES_READ: &str = "files.read";
+/// Upload files.
+pub const FILES_WRITE: &str = "files.write";
+/// Delete files.
+pub const FILES_DELETE: &str = "files.delete";
+/// Manage access requests and file request links.
+pub const SHARING: &str = "sharing";
+
+/// Use the admin API; only granted to administrators.
+pub const ADMIN: &str = "admin";
+
+/// Every scope a personal access token can be granted.
+pub const ALL_SCOPES: [&str; 5] = [FILES_READ, FILES_WRITE, FILES_DELETE, SHARING, ADMIN];
@@ -0,0 +1,409 @@
+use crate::auth::jwt::create_service_jwt;
+use crate::auth::session::access_token_ttl_secs;
+use crate::database::DbPool;
+use crate::models::service_accounts::{NewServiceAccountAssertion, ServiceAccount};
+use crate::repositories::service_accounts::{
+    find_service_account_by_id, find_service_account_key, record_assertion,
+    touch_service_account_key,
+};
+use crate::repositories::users::find_user_by_id;
+use crate::requests::service_accounts::TokenRequest;
+use actix_web::error::InternalError;
+use actix_web::http::StatusCode;
+use actix_web::{Error, HttpResponse, web};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
+use log::{error, info, warn};
+use serde::Deserialize;
+use std::env;
+use std::str::FromStr;
+
+/// `grant_type` of a JWT-bearer token request (RFC 7523).
+pub const JWT_BEARER_GRANT: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+
+/// Assertions may not be valid for longer than this.
+const MAX_ASSERTION_LIFETIME_SECS: i64 = 3600;
+/// Clock difference allowed between the service account and this server.
+const CLOCK_LEEWAY_SECS: i64 = 60;
+
+/// Claims read from a service account's assertion.
+#[derive(Debug, Deserialize)]
+struct AssertionClaims {
+    iss: String,
+    sub: String,
+    exp: i64,
+    iat: Option<i64>,
+    nbf: Option<i64>,
+    jti: Option<String>,
+}
+
+impl AssertionClaims {
+    /// Checks the claims of an assertion with a valid signature, audience and
+    /// expiry, issued by the service account acting as user `account_id`, at
+    /// `now` (unix seconds). Returns the `jti`.
+    fn check(self, account_id: &str, now: i64) -> Result<String, &'static str> {
+        if self.sub != account_id || self.iss != account_id {
+            return Err("'iss' and 'sub' must be the service account's user ID");
+        }
+        if self.exp - now > MAX_ASSERTION_LIFETIME_SECS {
+            return Err("The assertion must expire within an hour");
+        }
+        if self.iat.is_some_and(|iat| iat > now + CLOCK_LEEWAY_SECS) {
+            return Err("The assertion was issued in the future");
+        }
+        if self.nbf.is_some_and(|nbf| nbf > now + CLOCK_LEEWAY_SECS) {
+            return Err("The assertion is not valid yet");
+        }
+
+        self.jti
+            .filter(|jti| !jti.is_empty())
+            .ok_or("The assertion has no 'jti' claim")
+    }
+}
+
+/// Audience assertions must be addressed to: the URL of the token endpoint.
+fn token_audience() -> String {
+    format!(
+        "{}/auth/token",
+        env::var("APP_BASE_URL")
+            .unwrap_or_else(|_| "http://localhost:8080".to_string())
+            .trim_end_matches('/')
+    )
+}
+
+/// Parses a PEM public key for `algorithm`. Private keys are refused so they
+/// are never stored on the server.
+pub fn decoding_key(algorithm: &str, public_key: &str) -> Result<(Algorithm, DecodingKey), String> {
+    let pem = pem::parse(public_key).map_err(|e| format!("Invalid PEM: {}", e))?;
+    if !pem.tag().ends_with("PUBLIC KEY") {
+        return Err("Expected a public key, not a private key or certificate".to_string());
+    }
+
+    let algorithm = Algorithm::from_str(algorithm)
+        .map_err(|_| format!("Unsupported algorithm '{}'", algorithm))?;
+    let key = match algorithm {
+        Algorithm::RS256 => DecodingKey::from_rsa_pem(public_key.as_bytes()),
+        Algorithm::ES256 => DecodingKey::from_ec_pem(public_key.as_bytes()),
+        Algorithm::EdDSA => DecodingKey::from_ed_pem(public_key.as_bytes()),
+        other => return Err(format!("Unsupported algorithm '{:?}'", other)),
+    }
+    .map_err(|e| format!("Invalid {:?} public key: {}", algorithm, e))?;
+
+    Ok((algorithm, key))
+}
+
+/// Builds an OAuth 2.0 error response (RFC 6749 section 5.2).
+fn token_error(status: StatusCode, error: &str, description: &str) -> Error {
+    let response = HttpResponse::build(status)
+        .insert_header(("Cache-Control", "no-store"))
+        .json(serde_json::json!({
+            "error": error,
+            "error_description": description,
+        }));
+
+    InternalError::from_response(description.to_string(), response).into()
+}
+
+fn invalid_grant(description: &str) -> Error {
+    token_error(StatusCode::BAD_REQUEST, "invalid_grant", description)
+}
+
+fn server_error(e: diesel::result::Error) -> Error {
+    error!(
+        "Database error while issuing a service account token: {}",
+        e
+    );
+    token_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "server_error",
+        "Database error",
+    )
+}
+
+/// POST /auth/token
+/// Exchanges a JWT assertion signed with a service account's private key for
+/// an access token (RFC 7523). The assertion names the registered key with
+/// `kid`, has the service account's user ID as `iss` and `sub`, this
+/// endpoint's URL as `aud`, a unique `jti` and an `exp` at most an hour ahead.
+/// Assertions whose `iat` or `nbf` lies in the future are refused.
+pub async fn token(
+    pool: web::Data<DbPool>,
+    form: web::Form<TokenRequest>,
+) -> Result<HttpResponse, Error> {
+    let form = form.into_inner();
+
+    if form.grant_type != JWT_BEARER_GRANT {
+        return Err(token_error(
+            StatusCode::BAD_REQUEST,
+            "unsupported_grant_type",
+            &format!("Only the {} grant is supported", JWT_BEARER_GRANT),
+        ));
+    }
+    let Some(assertion) = form.assertion else {
+        return Err(token_error(
+            StatusCode::BAD_REQUEST,
+            "invalid_request",
+            "The assertion parameter is required",
+        ));
+    };
+
+    let account = verify_assertion(&pool, &assertion)?;
+
+    let scopes = match form.scope.as_deref().map(str::split_whitespace) {
+        Some(requested) => {
+            let mut requested: Vec<String> = requested.map(str::to_string).collect();
+            if let Some(denied) = requested.iter().find(|s| !account.scopes.contains(s)) {
+                warn!(
+                    "Service account {} requested scope {} it is not allowed",
+                    account.id, denied
+                );
+                return Err(token_error(
+                    StatusCode::BAD_REQUEST,
+                    "invalid_scope",
+                    &format!("The service account is not allowed the '{}' scope", denied),
+                ));
+            }
+            requested.sort();
+            requested.dedup();
+            requested
+        }
+        None => account.scopes.clone(),
+    };
+    if scopes.is_empty() {
+        return Err(token_error(
+            StatusCode::BAD_REQUEST,
+            "invalid_scope",
+            "At least one scope is required",
+        ));
+    }
+
+    let ttl = access_token_ttl_secs();
+    let access_token =
+        create_service_jwt(&account.user_id.to_string(), &scopes, ttl).map_err(|e| {
+            error!("Failed to sign service account token: {}", e);
+            token_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "server_error",
+                "Failed to issue token",
+            )
+        })?;
+
+    info!(
+        "Issued a token to service account {} with scopes {:?}",
+        account.id, scopes
+    );
+
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(serde_json::json!({
+            "access_token": access_token,
+            "token_type": "Bearer",
+            "expires_in": ttl,
+            "scope": scopes.join(" "),
+        })))
+}
+
+/// Checks the signature and claims of an assertion and returns the service
+/// account it was issued by. Each `jti` is accepted once.
+fn verify_assertion(pool: &DbPool, assertion: &str) -> Result<ServiceAccount, Error> {
+    let header = decode_header(assertion).map_err(|_| invalid_grant("Malformed assertion"))?;
+    let Some(kid) = header.kid else {
+        return Err(invalid_grant("The assertion has no 'kid' header"));
+    };
+
+    let key = match find_service_account_key(pool, &kid).map_err(server_error)? {
+        Some(key) if !key.is_revoked() => key,
+        _ => {
+            warn!(
+                "Rejecting assertion signed with unknown or revoked key {}",
+                kid
+            );
+            return Err(invalid_grant("Unknown or revoked key"));
+        }
+    };
+    let account = find_service_account_by_id(pool, key.service_account_id).map_err(server_error)?;
+    let account_id = account.user_id.to_string();
+
+    let (algorithm, decoding_key) = decoding_key(&key.algorithm, &key.public_key).map_err(|e| {
+        error!("Stored key {} cannot be used: {}", key.key_id, e);
+        invalid_grant("Unknown or revoked key")
+    })?;
+    // The algorithm comes from the registered key, never from the assertion
+    if header.alg != algorithm {
+        return Err(invalid_grant(
+            "The assertion is not signed with the key's algorithm",
+        ));
+    }
+
+    let mut validation = Validation::new(algorithm);
+    validation.leeway = CLOCK_LEEWAY_SECS as u64;
+    validation.validate_nbf = true;
+    validation.set_audience(&[token_audience()]);
+    validation.set_issuer(&[&account_id]);
+    validation.set_required_spec_claims(&["exp", "aud", "iss", "sub"]);
+
+    let claims = decode::<AssertionClaims>(assertion, &decoding_key, &validation)
+        .map_err(|e| {
+            warn!(
+                "Rejecting assertion for service account {}: {}",
+                account.id, e
+            );
+            invalid_grant(&format!("Invalid assertion: {}", e))
+        })?
+        .claims;
+
+    let now = chrono::Utc::now();
+    let exp = claims.exp;
+    let jti = claims
+        .check(&account_id, now.timestamp())
+        .map_err(invalid_grant)?;
+
+    let user = find_user_by_id(pool, account.user_id).map_err(server_error)?;
+    if user.is_suspended() || user.is_deleted() {
+        warn!(
+            "Rejecting assertion of disabled service account {}",
+            account.id
+        );
+        return Err(invalid_grant("The service account is disabled"));
+    }
+
+    let expires_at = chrono::DateTime::from_timestamp(exp, 0)
+        .unwrap_or(now)
+        .naive_utc();
+    let first_use = record_assertion(
+        pool,
+        &NewServiceAccountAssertion {
+            service_account_id: account.id,
+            jti,
+            expires_at,
+        },
+    )
+    .map_err(server_error)?;
+    if !first_use {
+        warn!(
+            "Rejecting replayed assertion of service account {}",
+            account.id
+        );
+        return Err(invalid_grant("The assertion has already been used"));
+    }
+
+    if let Err(e) = touch_service_account_key(pool, key.id) {
+        warn!(
+            "Failed to record use of service account key {}: {}",
+            key.id, e
+        );
+    }
+
+    Ok(account)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    const NOW: i64 = 1_750_000_000;
+
+    fn claims() -> AssertionClaims {
+        AssertionClaims {
+            iss: "42".to_string(),
+            sub: "42".to_string(),
+            exp: NOW + 300,
+            iat: Some(NOW),
+            nbf: Some(NOW),
+            jti: Some("a1b2c3".to_string()),
+        }
+    }
+
+    #[test]
+    fn valid_claims_return_the_jti() {
+        assert_eq!(claims().check("42", NOW), Ok("a1b2c3".to_string()));
+        // iat and nbf are optional
+        let minimal = AssertionClaims {
+            iat: None,
+            nbf: None,
+            ..claims()
+        };
+        assert!(minimal.check("42", NOW).is_ok());
+    }
+
+    #[test]
+    fn issuer_and_subject_must_be_the_account() {
+        assert!(claims().check("43", NOW).is_err());
+        let other_subject = AssertionClaims {
+            sub: "43".to_string(),
+            ..claims()
+        };
+        assert!(other_subject.check("42", NOW).is_err());
+    }
+
+    #[test]
+    fn long_lived_assertions_are_rejected() {
+        let long_lived = AssertionClaims {
+            exp: NOW + MAX_ASSERTION_LIFETIME_SECS + 1,
+            ..claims()
+        };
+        assert!(long_lived.check("42", NOW).is_err());
+    }
+
+    #[test]
+    fn future_dated_assertions_are_rejected() {
+        let issued_later = AssertionClaims {
+            iat: Some(NOW + CLOCK_LEEWAY_SECS + 1),
+            ..claims()
+        };
+        assert!(issued_later.check("42", NOW).is_err());
+
+        let not_yet_valid = AssertionClaims {
+            nbf: Some(NOW + CLOCK_LEEWAY_SECS + 1),
+            ..claims()
+        };
+        assert!(not_yet_valid.check("42", NOW).is_err());
+
+        // Small clock differences are tolerated
+        let slightly_ahead = AssertionClaims {
+            iat: Some(NOW + CLOCK_LEEWAY_SECS),
+            nbf: Some(NOW + CLOCK_LEEWAY_SECS),
+            ..claims()
+        };
+        assert!(slightly_ahead.check("42", NOW).is_ok());
+    }
+
+    #[test]
+    fn jti_is_required() {
+        for jti in [None, Some(String::new())] {
+            let without_jti = AssertionClaims { jti, ..claims() };
+            assert!(without_jti.check("42", NOW).is_err());
+        }
+    }
+
+    fn ed25519_key_pair() -> (Vec<u8>, Ed25519KeyPair) {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new()).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        (pkcs8.as_ref().to_vec(), key_pair)
+    }
+
+    #[test]
+    fn public_keys_are_accepted() {
+        let (_, key_pair) = ed25519_key_pair();
+        // SubjectPublicKeyInfo of an Ed25519 key: a fixed prefix and the key
+        let mut spki = vec![
+            0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+        ];
+        spki.extend_from_slice(key_pair.public_key().as_ref());
+        let public_pem = pem::encode(&pem::Pem::new("PUBLIC KEY", spki));
+
+        let (algorithm, _) = decoding_key("EdDSA", &public_pem).unwrap();
+        assert_eq!(algorithm, Algorithm::EdDSA);
+        assert!(decoding_key("HS256", &public_pem).is_err());
+        assert!(decoding_key("none", &public_pem).is_err());
+    }
+
+    #[test]
+    fn private_keys_are_refused() {
+        let (pkcs8, _) = ed25519_key_pair();
+        let private_pem = pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8));
+
+        assert!(decoding_key("EdDSA", &private_pem).is_err());
+        assert!(decoding_key("EdDSA", "not a key").is_err());
+    }
+}
@@ -0,0 +1,361 @@
+use crate::auth::jwt::{AuthenticatedUser, create_jwt, load_account};
+use crate::database::DbPool;
+use crate::models::sessions::NewSession;
+use crate::repositories::sessions::{
+    RefreshOutcome, create_session, find_session_by_refresh_token, revoke_session,
+    rotate_refresh_token, touch_session,
+};
+use actix_web::cookie::{Cookie, SameSite, time::Duration};
+use actix_web::http::{StatusCode, header};
+use actix_web::{Error, HttpRequest, HttpResponse, web};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::Utc;
+use log::{error, info, warn};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::env;
+use uuid::Uuid;
+
+/// Cookie holding the short-lived access JWT.
+pub const AUTH_COOKIE: &str = "auth_token";
+/// Cookie holding the rotating refresh token; only sent to `/auth`.
+pub const REFRESH_COOKIE: &str = "refresh_token";
+
+/// Tokens handed to the client when a session starts or is refreshed.
+pub struct IssuedTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+/// Lifetime of access tokens, from `ACCESS_TOKEN_TTL_MINUTES` (default 15).
+pub fn access_token_ttl_secs() -> u64 {
+    env::var("ACCESS_TOKEN_TTL_MINUTES")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(15)
+        * 60
+}
+
+fn session_ttl() -> chrono::Duration {
+    chrono::Duration::days(
+        env::var("SESSION_TTL_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(30),
+    )
+}
+
+/// Returns the hex-encoded SHA-256 of a token; only hashes are stored.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Generates a random, URL-safe token with 256 bits of entropy.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Longest user agent stored with a session.
+const MAX_USER_AGENT_LEN: usize = 512;
+
+/// The device a login comes from, shown in the user's session list.
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl ClientInfo {
+    /// Reads the user agent and client address of a request. The address is
+    /// taken from `Forwarded`/`X-Forwarded-For` only when
+    /// `TRUST_PROXY_HEADERS=true`, since clients can set those headers freely.
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let user_agent = req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LEN).collect());
+
+        let trust_proxy = env::var("TRUST_PROXY_HEADERS").is_ok_and(|v| v == "true");
+        let ip_address = if trust_proxy {
+            req.connection_info()
+                .realip_remote_addr()
+                .map(str::to_string)
+        } else {
+            req.peer_addr().map(|addr| addr.ip().to_string())
+        };
+
+        ClientInfo {
+            user_agent,
+            ip_address,
+        }
+    }
+}
+
+/// Starts a new session for the user and issues its first token pair.
+pub fn start_session(
+    pool: &DbPool,
+    user_id: &str,
+    client: ClientInfo,
+) -> Result<IssuedTokens, Error> {
+    let refresh_token = generate_token();
+    let new_session = NewSession {
+        id: Uuid::new_v4(),
+        user_id: user_id.to_string(),
+        expires_at: (Utc::now() + session_ttl()).naive_utc(),
+        user_agent: client.user_agent,
+        ip_address: client.ip_address,
+    };
+
+    let session = create_session(pool, &new_session, &hash_token(&refresh_token)).map_err(|e| {
+        error!("Failed to create session: {}", e);
+        actix_web::error::ErrorInternalServerError("Failed to create session")
+    })?;
+
+    info!("Started session {} for user_id={}", session.id, user_id);
+
+    issue_access_token(user_id, session.id, refresh_token)
+}
+
+fn issue_access_token(
+    user_id: &str,
+    session_id: Uuid,
+    refresh_token: String,
+) -> Result<IssuedTokens, Error> {
+    let access_token = create_jwt(user_id, session_id, access_token_ttl_secs()).map_err(|e| {
+        error!("JWT creation failed: {:?}", e);
+        actix_web::error::ErrorInternalServerError("JWT creation failed")
+    })?;
+
+    Ok(IssuedTokens {
+        access_token,
+        refresh_token,
+    })
+}
+
+/// Builds the HTTP-only cookies carrying the access and refresh tokens.
+pub fn session_cookies(tokens: &IssuedTokens) -> [Cookie<'static>; 2] {
+    let access = Cookie::build(AUTH_COOKIE, tokens.access_token.clone())
+        .http_only(true)
+        .secure(false) // should be true in production with HTTPS
+        .path("/")
+        .same_site(SameSite::Lax)
+        .finish();
+
+    let refresh = Cookie::build(REFRESH_COOKIE, tokens.refresh_token.clone())
+        .http_only(true)
+        .secure(false) // should be true in production with HTTPS
+        .path("/auth")
+        .same_site(SameSite::Strict)
+        .max_age(Duration::seconds(session_ttl().num_seconds()))
+        .finish();
+
+    [access, refresh]
+}
+
+/// Cookies that remove the access and refresh tokens from the browser.
+pub fn session_removal_cookies() -> [Cookie<'static>; 2] {
+    let mut access = Cookie::build(AUTH_COOKIE, "").path("/").finish();
+    access.make_removal();
+    let mut refresh = Cookie::build(REFRESH_COOKIE, "").path("/auth").finish();
+    refresh.make_removal();
+    [access, refresh]
+}
+
+/// POST /auth/refresh
+/// Exchanges the refresh token cookie for a new access token and a new refresh token.
+/// Reusing a refresh token revokes the whole session, as does refreshing a
+/// session of a suspended or deleted account.
+pub async fn refresh(req: HttpRequest, pool: web::Data<DbPool>) -> Result<HttpResponse, Error> {
+    let Some(cookie) = req.cookie(REFRESH_COOKIE) else {
+        return Err(actix_web::error::ErrorUnauthorized("Missing refresh token"));
+    };
+
+    let new_refresh_token = generate_token();
+    let outcome = rotate_refresh_token(
+        &pool,
+        &hash_token(cookie.value()),
+        &hash_token(&new_refresh_token),
+        Utc::now().naive_utc(),
+    )
+    .map_err(|e| {
+        error!("Database error while refreshing session: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    let session = match outcome {
+        RefreshOutcome::Rotated(session) => session,
+        RefreshOutcome::Reused(session_id) => {
+            warn!(
+                "Refresh token reuse detected, revoked session {}",
+                session_id
+            );
+            return Ok(unauthorized_with_cleared_cookies(
+                "Refresh token reuse detected",
+            ));
+        }
+        RefreshOutcome::Invalid => {
+            return Ok(unauthorized_with_cleared_cookies("Invalid refresh token"));
+        }
+    };
+
+    if let Err(e) = load_account(&pool, &session.user_id) {
+        // The error's response can only be taken once
+        let mut response = e.error_response();
+        if response.status() == StatusCode::UNAUTHORIZED {
+            revoke_session(&pool, session.id).map_err(|e| {
+                error!("Failed to revoke session: {}", e);
+                actix_web::error::ErrorInternalServerError("Database error")
+            })?;
+            warn!(
+                "Revoked session {} of inactive user_id={}",
+                session.id, session.user_id
+            );
+
+            for cookie in session_removal_cookies() {
+                let _ = response.add_cookie(&cookie);
+            }
+        }
+        return Ok(response);
+    }
+
+    if let Err(e) = touch_session(&pool, session.id) {
+        warn!("Failed to record use of session {}: {}", session.id, e);
+    }
+
+    let tokens = issue_access_token(&session.user_id, session.id, new_refresh_token)?;
+    let [access_cookie, refresh_cookie] = session_cookies(&tokens);
+
+    info!("Refreshed session {}", session.id);
+
+    Ok(HttpResponse::Ok()
+        .cookie(access_cookie)
+        .cookie(refresh_cookie)
+        .json(serde_json::json!({
+            "access_token": tokens.access_token,
+            "expires_in": access_token_ttl_secs(),
+        })))
+}
+
+/// POST /auth/logout
+/// Revokes the current session and clears the auth cookies.
+/// Works with either a valid access token or the refresh token cookie.
+pub async fn logout(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    user: Option<AuthenticatedUser>,
+) -> Result<HttpResponse, Error> {
+    let session_id = match user.and_then(|user| user.session_id) {
+        Some(session_id) => Some(session_id),
+        None => match req.cookie(REFRESH_COOKIE) {
+            Some(cookie) => find_session_by_refresh_token(&pool, &hash_token(cookie.value()))
+                .map_err(|e| {
+                    error!("Database error while loading session: {}", e);
+                    actix_web::error::ErrorInternalServerError("Database error")
+                })?
+                .map(|session| session.id),
+            None => None,
+        },
+    };
+
+    if let Some(session_id) = session_id {
+        revoke_session(&pool, session_id).map_err(|e| {
+            error!("Failed to revoke session: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?;
+        info!("Logged out session {}", session_id);
+    }
+
+    let [access_cookie, refresh_cookie] = session_removal_cookies();
+
+    Ok(HttpResponse::Ok()
+        .cookie(access_cookie)
+        .cookie(refresh_cookie)
+        .json("Logged out"))
+}
+
+fn unauthorized_with_cleared_cookies(message: &str) -> HttpResponse {
+    let [access_cookie, refresh_cookie] = session_removal_cookies();
+
+    HttpResponse::Unauthorized()
+        .cookie(access_cookie)
+        .cookie(refresh_cookie)
+        .json(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn hash_token_is_hex_sha256() {
+        assert_eq!(
+            hash_token("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_ne!(hash_token("abc"), hash_token("abd"));
+    }
+
+    #[test]
+    fn generated_tokens_are_random_and_url_safe() {
+        let token = generate_token();
+        assert_eq!(token.len(), 43);
+        assert!(
+            token
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        );
+        assert_ne!(token, generate_token());
+    }
+
+    #[test]
+    fn refresh_cookie_is_only_sent_to_auth() {
+        let tokens = IssuedTokens {
+            access_token: "access".to_string(),
+            refresh_token: "refresh".to_string(),
+        };
+        let [access, refresh] = session_cookies(&tokens);
+
+        assert_eq!(access.name(), AUTH_COOKIE);
+        assert_eq!(access.value(), "access");
+        assert_eq!(access.path(), Some("/"));
+        assert_eq!(access.http_only(), Some(true));
+
+        assert_eq!(refresh.name(), REFRESH_COOKIE);
+        assert_eq!(refresh.value(), "refresh");
+        assert_eq!(refresh.path(), Some("/auth"));
+        assert_eq!(refresh.http_only(), Some(true));
+        assert_eq!(refresh.same_site(), Some(SameSite::Strict));
+    }
+
+    #[test]
+    fn removal_cookies_match_the_session_cookies() {
+        let [access, refresh] = session_removal_cookies();
+
+        assert_eq!((access.name(), access.path()), (AUTH_COOKIE, Some("/")));
+        assert_eq!(
+            (refresh.name(), refresh.path()),
+            (REFRESH_COOKIE, Some("/auth"))
+        );
+        assert_eq!(access.max_age(), Some(Duration::ZERO));
+        assert_eq!(refresh.max_age(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn client_info_truncates_long_user_agents() {
+        let req = TestRequest::default()
+            .insert_header((header::USER_AGENT, "a".repeat(MAX_USER_AGENT_LEN + 10)))
+            .peer_addr("192.0.2.1:443".parse().unwrap())
+            .to_http_request();
+        let client = ClientInfo::from_request(&req);
+
+        assert_eq!(
+            client.user_agent.map(|ua| ua.len()),
+            Some(MAX_USER_AGENT_LEN)
+        );
+        assert_eq!(client.ip_address.as_deref(), Some("192.0.2.1"));
+    }
+}
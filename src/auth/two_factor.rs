@@ -0,0 +1,524 @@
+use crate::auth::keys::jwt_keys;
+use crate::auth::session::{ClientInfo, hash_token, session_cookies, start_session};
+use crate::database::DbPool;
+use crate::models::two_factor::{NewTotpCredential, TotpCredential};
+use crate::repositories::two_factor::{
+    enable_totp_credential, find_totp_credential, record_totp_failure, record_totp_success,
+    replace_recovery_codes, save_pending_totp_credential, use_recovery_code,
+};
+use crate::repositories::users::find_user_by_id_str;
+use crate::requests::two_factor::SecondFactor;
+use actix_web::cookie::{Cookie, SameSite, time::Duration};
+use actix_web::{Error, HttpRequest, HttpResponse, web};
+use chrono::Utc;
+use log::{error, info, warn};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+/// Cookie holding the signed two-factor challenge between login and verification.
+pub const TWO_FACTOR_COOKIE: &str = "two_factor_challenge";
+
+/// How long the user has to enter their code after signing in with the provider.
+const CHALLENGE_TTL_SECS: u64 = 5 * 60;
+
+/// Issuer shown in authenticator apps.
+const TOTP_ISSUER: &str = "Drive";
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECS: u64 = 30;
+/// Codes from one step before or after the current one are accepted.
+const TOTP_SKEW: u8 = 1;
+
+/// Wrong codes allowed before verification is locked.
+const MAX_FAILED_ATTEMPTS: i32 = 5;
+const LOCKOUT_MINUTES: i64 = 15;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Recovery code alphabet, without characters that are easily confused.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ChallengeClaims {
+    sub: String, // User who passed the first factor
+    aud: String, // Always CHALLENGE_AUDIENCE, so a challenge is never taken for an access token
+    exp: usize,  // Expiration timestamp (unix seconds)
+}
+
+const CHALLENGE_AUDIENCE: &str = "two_factor";
+
+/// Generates a new random base32-encoded TOTP secret.
+fn generate_secret() -> String {
+    match Secret::generate_secret().to_encoded() {
+        Secret::Encoded(secret) => secret,
+        Secret::Raw(_) => unreachable!("to_encoded returns an encoded secret"),
+    }
+}
+
+fn build_totp(secret: &str, account_name: String) -> Result<TOTP, Error> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| {
+            error!("Stored TOTP secret is invalid: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Invalid two-factor secret")
+        })?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        TOTP_SKEW,
+        TOTP_STEP_SECS,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        account_name,
+    )
+    .map_err(|e| {
+        error!("Failed to build TOTP: {:?}", e);
+        actix_web::error::ErrorInternalServerError("Invalid two-factor secret")
+    })
+}
+
+/// Returns the time step `code` is valid for, if it is valid now and newer
+/// than the last accepted step.
+fn matching_step(credential: &TotpCredential, code: &str) -> Result<Option<i64>, Error> {
+    let totp = build_totp(&credential.secret, String::new())?;
+    let now = Utc::now().timestamp() as u64;
+
+    Ok(step_for_code(&totp, credential.last_used_step, code, now))
+}
+
+/// Returns the step within the allowed skew of `now` that `code` was
+/// generated for, skipping steps up to `last_used_step`. Codes are compared
+/// in constant time.
+fn step_for_code(totp: &TOTP, last_used_step: Option<i64>, code: &str, now: u64) -> Option<i64> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    let current_step = (now / TOTP_STEP_SECS) as i64;
+
+    (-(TOTP_SKEW as i64)..=TOTP_SKEW as i64)
+        .map(|offset| current_step + offset)
+        .filter(|step| last_used_step.is_none_or(|last| *step > last))
+        .find(|step| {
+            let expected = totp.generate(*step as u64 * TOTP_STEP_SECS);
+            bool::from(expected.as_bytes().ct_eq(code.as_bytes()))
+        })
+}
+
+/// Generates a fresh set of recovery codes, shown to the user once.
+fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let chars: String = (0..10)
+                .map(|_| {
+                    RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char
+                })
+                .collect();
+            format!("{}-{}", &chars[..5], &chars[5..])
+        })
+        .collect()
+}
+
+/// Hashes a recovery code the way the user may type it: case, spaces and dashes are ignored.
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hash_token(&normalized)
+}
+
+fn load_credential(pool: &DbPool, user_id: &str) -> Result<Option<TotpCredential>, Error> {
+    find_totp_credential(pool, user_id).map_err(|e| {
+        error!("Database error while loading two-factor settings: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    })
+}
+
+/// Returns true if the user has completed two-factor enrolment.
+pub fn has_two_factor(pool: &DbPool, user_id: &str) -> Result<bool, Error> {
+    Ok(load_credential(pool, user_id)?.is_some_and(|credential| credential.is_enabled()))
+}
+
+fn ensure_not_locked(credential: &TotpCredential) -> Result<(), Error> {
+    if credential.is_locked(Utc::now().naive_utc()) {
+        return Err(actix_web::error::ErrorTooManyRequests(
+            "Too many wrong codes, try again later",
+        ));
+    }
+    Ok(())
+}
+
+fn reject_code(pool: &DbPool, user_id: &str) -> Error {
+    let lock_until = (Utc::now() + chrono::Duration::minutes(LOCKOUT_MINUTES)).naive_utc();
+    if let Err(e) = record_totp_failure(pool, user_id, MAX_FAILED_ATTEMPTS, lock_until) {
+        error!("Failed to record wrong two-factor code: {}", e);
+    }
+    warn!("Wrong two-factor code for user {}", user_id);
+    actix_web::error::ErrorUnauthorized("Invalid authentication code")
+}
+
+/// Checks a second factor of a user with two-factor authentication enabled.
+/// Accepted codes cannot be used again, and recovery codes are used up.
+pub fn check_second_factor(
+    pool: &DbPool,
+    user_id: &str,
+    factor: &SecondFactor,
+) -> Result<(), Error> {
+    let credential = load_credential(pool, user_id)?
+        .filter(|credential| credential.is_enabled())
+        .ok_or_else(|| {
+            actix_web::error::ErrorConflict("Two-factor authentication is not enabled")
+        })?;
+    ensure_not_locked(&credential)?;
+
+    let db_error = |e: diesel::result::Error| {
+        error!("Database error while checking two-factor code: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    };
+
+    match (&factor.code, &factor.recovery_code) {
+        (Some(code), None) => {
+            let Some(step) = matching_step(&credential, code)? else {
+                return Err(reject_code(pool, user_id));
+            };
+            // Another request accepted this code, or a later one, first
+            if record_totp_success(pool, user_id, Some(step)).map_err(db_error)? == 0 {
+                return Err(reject_code(pool, user_id));
+            }
+        }
+        (None, Some(recovery_code)) => {
+            if !use_recovery_code(pool, user_id, &hash_recovery_code(recovery_code))
+                .map_err(db_error)?
+            {
+                return Err(reject_code(pool, user_id));
+            }
+            record_totp_success(pool, user_id, None).map_err(db_error)?;
+            info!("User {} used a recovery code", user_id);
+        }
+        _ => {
+            return Err(actix_web::error::ErrorBadRequest(
+                "Provide either 'code' or 'recovery_code'",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Starts (or restarts) enrolment: stores a new pending secret and returns it
+/// together with an `otpauth://` URI for authenticator apps.
+pub fn start_enrolment(pool: &DbPool, user_id: &str) -> Result<HttpResponse, Error> {
+    if has_two_factor(pool, user_id)? {
+        return Err(actix_web::error::ErrorConflict(
+            "Two-factor authentication is already enabled",
+        ));
+    }
+
+    let user = find_user_by_id_str(pool, user_id).map_err(|e| {
+        warn!("User not found: {}", e);
+        actix_web::error::ErrorNotFound(format!("User not found: {}", e))
+    })?;
+    let account_name = user
+        .email
+        .or(user.username)
+        .unwrap_or_else(|| format!("user-{}", user.id))
+        .replace(':', "");
+
+    let secret = generate_secret();
+    let otpauth_uri = build_totp(&secret, account_name)?.get_url();
+
+    save_pending_totp_credential(
+        pool,
+        &NewTotpCredential {
+            user_id: user_id.to_string(),
+            secret: secret.clone(),
+        },
+    )
+    .map_err(|e| {
+        error!("Failed to store two-factor secret: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB insert error: {}", e))
+    })?;
+
+    info!("User {} started two-factor enrolment", user_id);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "secret": secret,
+        "otpauth_uri": otpauth_uri,
+    })))
+}
+
+/// Completes enrolment with a code from the authenticator app and returns
+/// the new recovery codes.
+pub fn finish_enrolment(pool: &DbPool, user_id: &str, code: &str) -> Result<Vec<String>, Error> {
+    let credential = load_credential(pool, user_id)?
+        .ok_or_else(|| actix_web::error::ErrorConflict("Start two-factor enrolment first"))?;
+    if credential.is_enabled() {
+        return Err(actix_web::error::ErrorConflict(
+            "Two-factor authentication is already enabled",
+        ));
+    }
+    ensure_not_locked(&credential)?;
+
+    let Some(step) = matching_step(&credential, code)? else {
+        return Err(reject_code(pool, user_id));
+    };
+
+    let recovery_codes = generate_recovery_codes();
+    let hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|c| hash_recovery_code(c))
+        .collect();
+
+    enable_totp_credential(pool, user_id, step, &hashes).map_err(|e| {
+        error!("Failed to enable two-factor authentication: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+    })?;
+
+    info!("User {} enabled two-factor authentication", user_id);
+
+    Ok(recovery_codes)
+}
+
+/// Replaces the user's recovery codes and returns the new ones.
+pub fn regenerate_recovery_codes(pool: &DbPool, user_id: &str) -> Result<Vec<String>, Error> {
+    let recovery_codes = generate_recovery_codes();
+    let hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|c| hash_recovery_code(c))
+        .collect();
+
+    replace_recovery_codes(pool, user_id, &hashes).map_err(|e| {
+        error!("Failed to replace recovery codes: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+    })?;
+
+    info!("User {} regenerated recovery codes", user_id);
+
+    Ok(recovery_codes)
+}
+
+/// Holds back the session of a user who passed the provider login and
+/// redirects to `/auth-2fa`, where the code is entered. With `enrol` the
+/// user must first set up two-factor authentication.
+pub fn begin_two_factor_login(user_id: &str, enrol: bool) -> Result<HttpResponse, Error> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let expiration = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time went backwards")
+        .as_secs()
+        + CHALLENGE_TTL_SECS;
+
+    let token = jwt_keys()
+        .sign(&ChallengeClaims {
+            sub: user_id.to_string(),
+            aud: CHALLENGE_AUDIENCE.to_string(),
+            exp: expiration as usize,
+        })
+        .map_err(|e| {
+            error!("Failed to sign two-factor challenge: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to start login")
+        })?;
+
+    let cookie = Cookie::build(TWO_FACTOR_COOKIE, token)
+        .http_only(true)
+        .secure(false) // should be true in production with HTTPS
+        .path("/auth")
+        .same_site(SameSite::Lax)
+        .max_age(Duration::seconds(CHALLENGE_TTL_SECS as i64))
+        .finish();
+
+    let location = if enrol {
+        "/auth-2fa?setup=required"
+    } else {
+        "/auth-2fa"
+    };
+
+    info!("User {} must complete two-factor login", user_id);
+
+    Ok(HttpResponse::Found()
+        .append_header(("Location", location))
+        .cookie(cookie)
+        .finish())
+}
+
+/// Returns the user who passed the first factor, from the challenge cookie.
+fn challenged_user(req: &HttpRequest) -> Result<String, Error> {
+    req.cookie(TWO_FACTOR_COOKIE)
+        .and_then(|cookie| jwt_keys().verify::<ChallengeClaims>(cookie.value(), CHALLENGE_AUDIENCE))
+        .map(|claims| claims.sub)
+        .ok_or_else(|| {
+            actix_web::error::ErrorUnauthorized("Two-factor challenge is missing or expired")
+        })
+}
+
+fn challenge_removal_cookie() -> Cookie<'static> {
+    let mut cookie = Cookie::build(TWO_FACTOR_COOKIE, "").path("/auth").finish();
+    cookie.make_removal();
+    cookie
+}
+
+/// POST /auth/2fa/setup
+/// Starts two-factor enrolment during a login that requires it.
+pub async fn login_setup(req: HttpRequest, pool: web::Data<DbPool>) -> Result<HttpResponse, Error> {
+    let user_id = challenged_user(&req)?;
+    start_enrolment(&pool, &user_id)
+}
+
+/// POST /auth/2fa/verify
+/// Completes a login held back for two-factor authentication and starts the session.
+/// During enrolment the response also carries the new recovery codes.
+pub async fn login_verify(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    body: web::Json<SecondFactor>,
+) -> Result<HttpResponse, Error> {
+    let user_id = challenged_user(&req)?;
+
+    let recovery_codes = match load_credential(&pool, &user_id)? {
+        Some(credential) if credential.is_enabled() => {
+            check_second_factor(&pool, &user_id, &body)?;
+            None
+        }
+        Some(_) => {
+            let code = body.code.as_deref().ok_or_else(|| {
+                actix_web::error::ErrorBadRequest("Enter a code from your authenticator app")
+            })?;
+            Some(finish_enrolment(&pool, &user_id, code)?)
+        }
+        None => {
+            return Err(actix_web::error::ErrorConflict(
+                "Set up two-factor authentication first",
+            ));
+        }
+    };
+
+    let tokens = start_session(&pool, &user_id, ClientInfo::from_request(&req))?;
+    let [access_cookie, refresh_cookie] = session_cookies(&tokens);
+
+    info!("User {} passed two-factor authentication", user_id);
+
+    let mut response = HttpResponse::Ok();
+    response
+        .cookie(access_cookie)
+        .cookie(refresh_cookie)
+        .cookie(challenge_removal_cookie());
+
+    Ok(match recovery_codes {
+        Some(recovery_codes) => response.json(serde_json::json!({
+            "recovery_codes": recovery_codes,
+        })),
+        None => response.json("Logged in"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_totp() -> TOTP {
+        build_totp(&generate_secret(), String::new()).unwrap()
+    }
+
+    /// A time in the middle of step 1000.
+    const NOW: u64 = 1000 * TOTP_STEP_SECS + 10;
+
+    fn code_at_step(totp: &TOTP, step: i64) -> String {
+        totp.generate(step as u64 * TOTP_STEP_SECS)
+    }
+
+    #[test]
+    fn current_code_matches_its_step() {
+        let totp = test_totp();
+        let code = code_at_step(&totp, 1000);
+
+        assert_eq!(step_for_code(&totp, None, &code, NOW), Some(1000));
+    }
+
+    #[test]
+    fn codes_within_the_skew_are_accepted() {
+        let totp = test_totp();
+
+        for step in [999, 1001] {
+            let code = code_at_step(&totp, step);
+            assert_eq!(step_for_code(&totp, None, &code, NOW), Some(step));
+        }
+    }
+
+    #[test]
+    fn codes_outside_the_skew_are_rejected() {
+        let totp = test_totp();
+
+        for step in [997, 998, 1002, 1003] {
+            let code = code_at_step(&totp, step);
+            // A code may collide with a neighbouring step's by chance
+            if [999, 1000, 1001]
+                .iter()
+                .all(|near| code_at_step(&totp, *near) != code)
+            {
+                assert_eq!(step_for_code(&totp, None, &code, NOW), None);
+            }
+        }
+    }
+
+    #[test]
+    fn used_steps_cannot_be_replayed() {
+        let totp = test_totp();
+        let code = code_at_step(&totp, 1000);
+
+        assert_eq!(step_for_code(&totp, Some(1000), &code, NOW), None);
+        assert_eq!(step_for_code(&totp, Some(1001), &code, NOW), None);
+        assert_eq!(step_for_code(&totp, Some(999), &code, NOW), Some(1000));
+    }
+
+    #[test]
+    fn whitespace_in_codes_is_ignored() {
+        let totp = test_totp();
+        let code = code_at_step(&totp, 1000);
+        let spaced = format!(" {} {} ", &code[..3], &code[3..]);
+
+        assert_eq!(step_for_code(&totp, None, &spaced, NOW), Some(1000));
+    }
+
+    #[test]
+    fn wrong_and_malformed_codes_are_rejected() {
+        let totp = test_totp();
+        let code = code_at_step(&totp, 1000);
+
+        assert_eq!(step_for_code(&totp, None, "", NOW), None);
+        assert_eq!(step_for_code(&totp, None, &code[..5], NOW), None);
+        assert_eq!(step_for_code(&totp, None, &format!("{}0", code), NOW), None);
+    }
+
+    #[test]
+    fn recovery_codes_are_unique_and_formatted() {
+        let codes = generate_recovery_codes();
+
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        for code in &codes {
+            let (left, right) = code.split_once('-').unwrap();
+            assert_eq!((left.len(), right.len()), (5, 5));
+            assert!(
+                code.bytes()
+                    .filter(|b| *b != b'-')
+                    .all(|b| RECOVERY_CODE_ALPHABET.contains(&b))
+            );
+        }
+
+        let hashes: std::collections::HashSet<String> =
+            codes.iter().map(|c| hash_recovery_code(c)).collect();
+        assert_eq!(hashes.len(), codes.len());
+    }
+
+    #[test]
+    fn recovery_codes_match_however_they_are_typed() {
+        let hash = hash_recovery_code("abcde-fgh23");
+
+        assert_eq!(hash_recovery_code("ABCDE-FGH23"), hash);
+        assert_eq!(hash_recovery_code("abcdefgh23"), hash);
+        assert_eq!(hash_recovery_code(" abcde fgh23 "), hash);
+        assert_ne!(hash_recovery_code("abcde-fgh24"), hash);
+    }
+}
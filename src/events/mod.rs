@@ -0,0 +1,116 @@
+//! Live change notifications. A single poller reads new rows of the changes
+//! feed and fans them out to the open event streams, which pick out the
+//! changes of their user.
+
+pub mod worker;
+
+use crate::database::DbPool;
+use crate::models::file_changes::{ChangePosition, FileChange};
+use crate::repositories::file_changes::{change_watermark, list_file_changes};
+use log::warn;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Changes buffered for streams that have not caught up yet. A stream that
+/// falls further behind reads the changes it missed from the database.
+const BUFFER_SIZE: usize = 1024;
+/// Changes read from the database at a time when a stream catches up.
+const CATCH_UP_BATCH: i64 = 500;
+
+/// How often idle streams send a heartbeat.
+pub const HEARTBEAT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Fans changes out to the open event streams.
+#[derive(Clone)]
+pub struct EventHub {
+    sender: broadcast::Sender<Arc<FileChange>>,
+}
+
+impl EventHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(BUFFER_SIZE);
+        EventHub { sender }
+    }
+
+    pub fn publish(&self, change: FileChange) {
+        // Nobody listening is fine
+        let _ = self.sender.send(Arc::new(change));
+    }
+
+    /// Subscribes to the changes of a user after the change at
+    /// `last_event_id`, or to the changes from now on.
+    pub fn subscribe(
+        &self,
+        pool: &DbPool,
+        user_id: &str,
+        last_event_id: Option<ChangePosition>,
+    ) -> Result<Subscription, diesel::result::Error> {
+        // Subscribe before reading the position, so no change falls in between
+        let receiver = self.sender.subscribe();
+        let (from, catching_up) = match last_event_id {
+            Some(position) => (position.next(), true),
+            None => (change_watermark(pool)?, false),
+        };
+
+        Ok(Subscription {
+            pool: pool.clone(),
+            user_id: user_id.to_string(),
+            from,
+            receiver,
+            backlog: VecDeque::new(),
+            catching_up,
+        })
+    }
+}
+
+/// The changes of one user, in order and each once.
+pub struct Subscription {
+    pool: DbPool,
+    user_id: String,
+    /// The position of the next change to send.
+    from: ChangePosition,
+    receiver: broadcast::Receiver<Arc<FileChange>>,
+    backlog: VecDeque<FileChange>,
+    catching_up: bool,
+}
+
+impl Subscription {
+    /// Waits for the next change. The changes missed while resuming or while
+    /// the stream was too slow to keep up are read from the database first.
+    pub async fn next(&mut self) -> Result<FileChange, diesel::result::Error> {
+        loop {
+            if let Some(change) = self.backlog.pop_front() {
+                self.from = change.position().next();
+                return Ok(change);
+            }
+
+            if self.catching_up {
+                let until = change_watermark(&self.pool)?;
+                let missed =
+                    list_file_changes(&self.pool, &self.user_id, self.from, until, CATCH_UP_BATCH)?;
+                self.catching_up = missed.len() as i64 == CATCH_UP_BATCH;
+                self.backlog.extend(missed);
+                continue;
+            }
+
+            match self.receiver.recv().await {
+                Ok(change) if change.user_id == self.user_id && change.position() >= self.from => {
+                    self.from = change.position().next();
+                    return Ok(change.as_ref().clone());
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Event stream of user {} fell {} changes behind, catching up",
+                        self.user_id, skipped
+                    );
+                    self.catching_up = true;
+                }
+                // The hub lives as long as the server
+                Err(RecvError::Closed) => std::future::pending::<()>().await,
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use super::EventHub;
+use crate::database::DbPool;
+use crate::repositories::file_changes::{change_watermark, list_all_file_changes};
+use log::{error, info};
+
+/// How often the changes feed is polled for new changes.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// Maximum number of changes read per poll.
+const BATCH_SIZE: i64 = 500;
+
+/// Publishes new changes to the event hub until the process exits.
+pub async fn run_change_poller(pool: DbPool, hub: EventHub) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    let mut from = loop {
+        interval.tick().await;
+        match change_watermark(&pool) {
+            Ok(position) => break position,
+            Err(e) => error!("Failed to read the changes feed position: {}", e),
+        }
+    };
+
+    info!("Change poller started at position {}", from);
+
+    loop {
+        interval.tick().await;
+
+        let until = match change_watermark(&pool) {
+            Ok(position) => position,
+            Err(e) => {
+                error!("Failed to read the changes feed position: {}", e);
+                continue;
+            }
+        };
+
+        loop {
+            let changes = match list_all_file_changes(&pool, from, until, BATCH_SIZE) {
+                Ok(changes) => changes,
+                Err(e) => {
+                    error!("Failed to load new changes: {}", e);
+                    break;
+                }
+            };
+            let full = changes.len() as i64 == BATCH_SIZE;
+
+            for change in changes {
+                from = change.position().next();
+                hub.publish(change);
+            }
+            if !full {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use crate::auth::jwt::AuthenticatedUser;
+use crate::auth::scopes;
+use crate::database::DbPool;
+use crate::models::access_requests::{AccessRequest, NewAccessRequest, STATUS_PENDING};
+use crate::models::file_permissions::ROLE_READER;
+use crate::models::s3_files::S3File;
+use crate::notifications::templates::Notification;
+use crate::notifications::{Notifier, display_name};
+use crate::policy::SharingPolicy;
+use crate::repositories::access_requests::{
+    approve_access_request, deny_access_request, find_access_request_by_id,
+    find_pending_access_request, insert_access_request, list_access_requests_by_requester,
+    list_access_requests_for_owner,
+};
+use crate::repositories::file_permissions::has_file_access;
+use crate::repositories::s3_files::find_s3_file_by_id;
+use crate::repositories::users::find_user_by_id_str;
+use crate::requests::access::{AccessRequestFilter, CreateAccessRequest};
+use actix_web::{Error, HttpResponse, web};
+use diesel::result::DatabaseErrorKind;
+use log::{error, info, warn};
+
+/// Maximum length of the message attached to an access request.
+const MAX_MESSAGE_LEN: usize = 1000;
+
+/// POST /api/files/{id}/access-requests
+/// Records a request for access to a file the user cannot open.
+/// Returns the already pending request if there is one.
+pub async fn request_access(
+    pool: web::Data<DbPool>,
+    notifier: web::Data<Notifier>,
+    file_id: web::Path<i32>,
+    user: AuthenticatedUser,
+    body: web::Json<CreateAccessRequest>,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::SHARING)?;
+    let file_id = file_id.into_inner();
+    info!("User {} requests access to file {}", user.user_id, file_id);
+
+    let message = body
+        .into_inner()
+        .message
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty());
+
+    if message
+        .as_ref()
+        .is_some_and(|m| m.chars().count() > MAX_MESSAGE_LEN)
+    {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Message must be at most {} characters",
+            MAX_MESSAGE_LEN
+        )));
+    }
+
+    let file = find_s3_file_by_id(&pool, file_id).map_err(|e| {
+        warn!("File not found for access request: {}", e);
+        actix_web::error::ErrorNotFound(format!("File not found: {}", e))
+    })?;
+
+    let has_access = has_file_access(&pool, &file, &user.user_id).map_err(|e| {
+        error!("Database error while checking file access: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    })?;
+
+    if has_access {
+        return Err(actix_web::error::ErrorConflict(
+            "You already have access to this file",
+        ));
+    }
+
+    let find_pending = || {
+        find_pending_access_request(&pool, file_id, &user.user_id).map_err(|e| {
+            error!("Database error while loading access requests: {}", e);
+            actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+        })
+    };
+
+    if let Some(request) = find_pending()? {
+        info!(
+            "User {} already has pending access request {}",
+            user.user_id, request.id
+        );
+        return Ok(HttpResponse::Ok().json(request));
+    }
+
+    let new_request = NewAccessRequest {
+        file_id,
+        requester_id: user.user_id.clone(),
+        message,
+    };
+
+    let request = match insert_access_request(&pool, &new_request) {
+        Ok(request) => request,
+        // Another request for the same file was created at the same time
+        Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            let request = find_pending()?.ok_or_else(|| {
+                actix_web::error::ErrorConflict("An access request is already pending")
+            })?;
+            info!(
+                "User {} already has pending access request {}",
+                user.user_id, request.id
+            );
+            return Ok(HttpResponse::Ok().json(request));
+        }
+        Err(e) => {
+            error!("Failed to insert access request: {}", e);
+            return Err(actix_web::error::ErrorInternalServerError(format!(
+                "DB insert error: {}",
+                e
+            )));
+        }
+    };
+
+    info!("Created access request {} for file {}", request.id, file_id);
+
+    notifier.notify_user(
+        &pool,
+        &file.user_id,
+        Notification::AccessRequested {
+            file_id,
+            file_name: file.name.clone(),
+            requester: display_name(&pool, &user.user_id),
+            message: request.message.clone(),
+        },
+    );
+
+    Ok(HttpResponse::Created().json(request))
+}
+
+/// GET /api/access-requests
+/// Lists access requests on files owned by the user, optionally filtered by `status`.
+pub async fn list_access_requests(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    filter: web::Query<AccessRequestFilter>,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::SHARING)?;
+    info!("Listing access requests for owner {}", user.user_id);
+
+    let requests = list_access_requests_for_owner(&pool, &user.user_id, filter.status.as_deref())
+        .map_err(|e| {
+        error!("Database error while loading access requests: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(requests))
+}
+
+/// GET /api/access-requests/mine
+/// Lists access requests made by the user so they can poll for a decision.
+pub async fn list_my_access_requests(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::SHARING)?;
+    info!("Listing access requests made by user {}", user.user_id);
+
+    let requests = list_access_requests_by_requester(&pool, &user.user_id).map_err(|e| {
+        error!("Database error while loading access requests: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(requests))
+}
+
+/// GET /api/access-requests/{id}
+/// Returns a single access request to its requester or to the file owner.
+pub async fn get_access_request(
+    pool: web::Data<DbPool>,
+    request_id: web::Path<i32>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::SHARING)?;
+    let request = find_access_request(&pool, request_id.into_inner())?;
+
+    if request.requester_id != user.user_id {
+        ensure_file_owner(&pool, &request, &user)?;
+    }
+
+    Ok(HttpResponse::Ok().json(request))
+}
+
+/// POST /api/access-requests/{id}/approve
+/// Approves a pending request and grants the requester read access.
+pub async fn approve_request(
+    pool: web::Data<DbPool>,
+    notifier: web::Data<Notifier>,
+    request_id: web::Path<i32>,
+    user: AuthenticatedUser,
+    policy: SharingPolicy,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::SHARING)?;
+    let request = find_access_request(&pool, request_id.into_inner())?;
+    let file = ensure_file_owner(&pool, &request, &user)?;
+    ensure_pending(&request)?;
+
+    let load_user = |user_id: &str| {
+        find_user_by_id_str(&pool, user_id).map_err(|e| {
+            warn!("User {} not found: {}", user_id, e);
+            actix_web::error::ErrorNotFound(format!("User not found: {}", e))
+        })
+    };
+    let sharer = load_user(&user.user_id)?;
+    let recipient = load_user(&request.requester_id)?;
+
+    if let Err(reason) = policy.check_share(&sharer, &recipient) {
+        warn!(
+            "Sharing policy rejected access request {}: {}",
+            request.id, reason
+        );
+        return Err(actix_web::error::ErrorForbidden(reason));
+    }
+
+    let request = approve_access_request(&pool, request.id, ROLE_READER, &user.user_id)
+        .map_err(|e| resolve_error(e, "approve"))?;
+
+    info!(
+        "User {} approved access request {}",
+        user.user_id, request.id
+    );
+
+    notifier.notify_user(
+        &pool,
+        &request.requester_id,
+        Notification::FileShared {
+            file_id: file.file_id,
+            file_name: file.name,
+            role: ROLE_READER.to_string(),
+            shared_by: display_name(&pool, &user.user_id),
+        },
+    );
+
+    Ok(HttpResponse::Ok().json(request))
+}
+
+/// POST /api/access-requests/{id}/deny
+/// Denies a pending access request.
+pub async fn deny_request(
+    pool: web::Data<DbPool>,
+    notifier: web::Data<Notifier>,
+    request_id: web::Path<i32>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::SHARING)?;
+    let request = find_access_request(&pool, request_id.into_inner())?;
+    let file = ensure_file_owner(&pool, &request, &user)?;
+    ensure_pending(&request)?;
+
+    let request = deny_access_request(&pool, request.id).map_err(|e| resolve_error(e, "deny"))?;
+
+    info!("User {} denied access request {}", user.user_id, request.id);
+
+    notifier.notify_user(
+        &pool,
+        &request.requester_id,
+        Notification::AccessDenied {
+            file_name: file.name,
+        },
+    );
+
+    Ok(HttpResponse::Ok().json(request))
+}
+
+fn find_access_request(pool: &DbPool, request_id: i32) -> Result<AccessRequest, Error> {
+    find_access_request_by_id(pool, request_id).map_err(|e| {
+        warn!("Access request {} not found: {}", request_id, e);
+        actix_web::error::ErrorNotFound(format!("Access request not found: {}", e))
+    })
+}
+
+/// Checks that the user owns the file the request refers to and returns the file.
+fn ensure_file_owner(
+    pool: &DbPool,
+    request: &AccessRequest,
+    user: &AuthenticatedUser,
+) -> Result<S3File, Error> {
+    let file = find_s3_file_by_id(pool, request.file_id).map_err(|e| {
+        warn!("File not found for access request: {}", e);
+        actix_web::error::ErrorNotFound(format!("File not found: {}", e))
+    })?;
+
+    if file.user_id != user.user_id {
+        warn!(
+            "User {} tried to manage access request {} on someone else's file",
+            user.user_id, request.id
+        );
+        return Err(actix_web::error::ErrorForbidden("You do not own this file"));
+    }
+
+    Ok(file)
+}
+
+/// Maps the error of resolving a request. The update only matches pending
+/// requests, so `NotFound` means another request resolved it first.
+fn resolve_error(e: diesel::result::Error, action: &str) -> Error {
+    match e {
+        diesel::result::Error::NotFound => {
+            actix_web::error::ErrorConflict("Access request is no longer pending")
+        }
+        e => {
+            error!("Failed to {} access request: {}", action, e);
+            actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+        }
+    }
+}
+
+fn ensure_pending(request: &AccessRequest) -> Result<(), Error> {
+    if request.status != STATUS_PENDING {
+        return Err(actix_web::error::ErrorConflict(format!(
+            "Access request is already {}",
+            request.status
+        )));
+    }
+
+    Ok(())
+}
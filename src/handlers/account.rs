@@ -0,0 +1,239 @@
+use crate::auth::jwt::AuthenticatedUser;
+use crate::auth::session::session_removal_cookies;
+use crate::auth::two_factor::{check_second_factor, has_two_factor};
+use crate::database::DbPool;
+use crate::models::account_jobs::{AccountJob, KIND_DELETION, KIND_EXPORT, NewAccountJob};
+use crate::policy::SharingPolicy;
+use crate::repositories::account_jobs::{
+    find_account_job_by_id, find_active_account_job, insert_account_job, start_account_deletion,
+};
+use crate::repositories::sessions::revoke_user_sessions;
+use crate::repositories::users::find_user_by_id;
+use crate::requests::account::DeleteAccount;
+use crate::storage::S3Storage;
+use actix_web::http::header;
+use actix_web::{Error, HttpResponse, web};
+use diesel::result::DatabaseErrorKind;
+use log::{error, info, warn};
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+/// GET /api/me/export
+/// Starts an export of all the user's files and data, or returns the export
+/// already in progress. Poll the job at the returned `Location`.
+pub async fn export_account(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+
+    if let Some(job) = find_active_job(&pool, &user.user_id, KIND_EXPORT)? {
+        return Ok(accepted(&job));
+    }
+
+    let new_job = NewAccountJob {
+        id: Uuid::new_v4(),
+        user_id: user.user_id.clone(),
+        kind: KIND_EXPORT.to_string(),
+        transfer_to: None,
+    };
+
+    let job = match insert_account_job(&pool, &new_job) {
+        Ok(job) => job,
+        // Another request started an export at the same time
+        Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            find_active_job(&pool, &user.user_id, KIND_EXPORT)?
+                .ok_or_else(|| actix_web::error::ErrorConflict("Export already in progress"))?
+        }
+        Err(e) => {
+            error!("Failed to queue export: {}", e);
+            return Err(actix_web::error::ErrorInternalServerError(format!(
+                "DB insert error: {}",
+                e
+            )));
+        }
+    };
+
+    info!(
+        "User {} requested a data export (job {})",
+        user.user_id, job.id
+    );
+
+    Ok(accepted(&job))
+}
+
+/// GET /api/me/export/{id}
+/// Downloads the archive of a finished export.
+pub async fn download_export(
+    pool: web::Data<DbPool>,
+    storage_s3: web::Data<S3Storage>,
+    user: AuthenticatedUser,
+    job_id: web::Path<Uuid>,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+
+    let job = find_job(&pool, job_id.into_inner())?;
+    if job.kind != KIND_EXPORT || job.user_id != user.user_id {
+        warn!(
+            "User {} tried to download export {} of someone else",
+            user.user_id, job.id
+        );
+        return Err(actix_web::error::ErrorNotFound("Export not found"));
+    }
+
+    let key = match &job.archive_key {
+        Some(key) if job.is_downloadable(chrono::Utc::now().naive_utc()) => key,
+        _ if job.completed_at.is_some() => {
+            return Err(actix_web::error::ErrorGone(
+                "The export has expired, request a new one via GET /api/me/export",
+            ));
+        }
+        _ => {
+            return Err(actix_web::error::ErrorConflict(format!(
+                "The export is {}",
+                job.status
+            )));
+        }
+    };
+
+    let byte_stream = storage_s3.download_file(key).await?;
+    let stream = ReaderStream::new(byte_stream.into_async_read());
+
+    Ok(HttpResponse::Ok()
+        .append_header((
+            header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"export-{}.zip\"",
+                job.created_at.format("%Y%m%d-%H%M%S")
+            ),
+        ))
+        .append_header((header::CONTENT_TYPE, "application/zip"))
+        .streaming(stream))
+}
+
+/// DELETE /api/me
+/// Deletes the account. Files are transferred to `transfer_to` or deleted,
+/// and the user is anonymised by a background job. The transfer is subject
+/// to the sharing policy, as the files are effectively shared with the new
+/// owner. The account is locked
+/// immediately and all sessions are ended.
+pub async fn delete_account(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    policy: SharingPolicy,
+    body: web::Json<DeleteAccount>,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+
+    if has_two_factor(&pool, &user.user_id)? {
+        check_second_factor(&pool, &user.user_id, &body.second_factor)?;
+    }
+
+    let owner = user.id()?;
+
+    if let Some(new_owner) = body.transfer_to {
+        if new_owner == owner {
+            return Err(actix_web::error::ErrorBadRequest(
+                "Files cannot be transferred to yourself",
+            ));
+        }
+
+        let db_error = |e: diesel::result::Error| {
+            error!("Database error while loading transfer target: {}", e);
+            actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+        };
+
+        let target = match find_user_by_id(&pool, new_owner) {
+            Ok(target) if target.can_receive_transfer() => target,
+            Ok(_) | Err(diesel::result::Error::NotFound) => {
+                return Err(actix_web::error::ErrorBadRequest(format!(
+                    "User {} cannot receive files",
+                    new_owner
+                )));
+            }
+            Err(e) => return Err(db_error(e)),
+        };
+        let account = find_user_by_id(&pool, owner).map_err(db_error)?;
+
+        // Handing files over shares them, so the same rules apply
+        if let Err(reason) = policy.check_share(&account, &target) {
+            warn!(
+                "Sharing policy rejected transferring the files of user {} to user {}: {}",
+                owner, new_owner, reason
+            );
+            return Err(actix_web::error::ErrorForbidden(reason));
+        }
+    }
+
+    let new_job = NewAccountJob {
+        id: Uuid::new_v4(),
+        user_id: user.user_id.clone(),
+        kind: KIND_DELETION.to_string(),
+        transfer_to: body.transfer_to.map(|id| id.to_string()),
+    };
+
+    let job = start_account_deletion(&pool, owner, &new_job).map_err(|e| {
+        error!("Failed to queue account deletion: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB insert error: {}", e))
+    })?;
+
+    revoke_user_sessions(&pool, &user.user_id, None).map_err(|e| {
+        error!("Failed to revoke sessions of deleted account: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+    })?;
+
+    info!(
+        "User {} requested account deletion (job {}, transfer to {:?})",
+        user.user_id, job.id, body.transfer_to
+    );
+
+    let mut response = accepted(&job);
+    for cookie in session_removal_cookies() {
+        response.add_cookie(&cookie)?;
+    }
+    Ok(response)
+}
+
+/// GET /api/account-jobs/{id}
+/// Returns the status of an export or deletion job. The unguessable job ID
+/// is the only credential, so a deletion can be followed after the account
+/// is gone; the response contains no personal data.
+pub async fn get_account_job(
+    pool: web::Data<DbPool>,
+    job_id: web::Path<Uuid>,
+) -> Result<HttpResponse, Error> {
+    let job = find_job(&pool, job_id.into_inner())?;
+    Ok(HttpResponse::Ok().json(job_view(&job)))
+}
+
+fn find_job(pool: &DbPool, job_id: Uuid) -> Result<AccountJob, Error> {
+    find_account_job_by_id(pool, job_id).map_err(|e| match e {
+        diesel::result::Error::NotFound => actix_web::error::ErrorNotFound("Job not found"),
+        e => {
+            error!("Database error while loading account job: {}", e);
+            actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+        }
+    })
+}
+
+fn find_active_job(pool: &DbPool, owner: &str, kind: &str) -> Result<Option<AccountJob>, Error> {
+    find_active_account_job(pool, owner, kind).map_err(|e| {
+        error!("Database error while loading account jobs: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    })
+}
+
+/// The job as JSON, with a `download_url` once an export can be downloaded.
+fn job_view(job: &AccountJob) -> serde_json::Value {
+    let mut view = serde_json::json!(job);
+    if job.is_downloadable(chrono::Utc::now().naive_utc()) {
+        view["download_url"] = format!("/api/me/export/{}", job.id).into();
+    }
+    view
+}
+
+fn accepted(job: &AccountJob) -> HttpResponse {
+    HttpResponse::Accepted()
+        .append_header((header::LOCATION, format!("/api/account-jobs/{}", job.id)))
+        .json(job_view(job))
+}
@@ -0,0 +1,457 @@
+use crate::auth::jwt::AuthenticatedUser;
+use crate::database::DbPool;
+use crate::models::admin_audit_log::{
+    ACTION_CHANGE_ROLE, ACTION_DELETE_FILE, ACTION_REACTIVATE_USER, ACTION_SET_LINKS_DISABLED,
+    ACTION_SUSPEND_USER, ACTION_UPDATE_SHARING_POLICY, ACTION_UPDATE_TWO_FACTOR_POLICY,
+    NewAuditLogEntry, TARGET_FILE, TARGET_SETTING, TARGET_USER,
+};
+use crate::models::users::{USER_ROLES, User};
+use crate::policy::{SharingPolicy, TwoFactorPolicy};
+use crate::repositories::admin_audit_log::{
+    insert_audit_log_entry, list_audit_log, with_audit_log_entry,
+};
+use crate::repositories::instance_settings::update_setting;
+use crate::repositories::s3_files::{
+    delete_s3_file_by_id, find_s3_file_by_id, storage_usage_by_user, total_storage_usage,
+};
+use crate::repositories::sessions::{count_active_sessions, revoke_user_sessions_on};
+use crate::repositories::users::{
+    count_users, find_user_by_id, list_users, set_links_disabled, set_user_suspended,
+    update_user_role,
+};
+use crate::requests::admin::{AuditLogQuery, ChangeUserRole, SetLinksDisabled};
+use crate::storage::S3Storage;
+use actix_web::{Error, HttpResponse, web};
+use log::{error, info, warn};
+use std::collections::HashMap;
+
+/// Default and maximum number of audit log entries per page.
+const DEFAULT_AUDIT_LOG_LIMIT: i64 = 50;
+const MAX_AUDIT_LOG_LIMIT: i64 = 500;
+
+/// GET /api/admin/users
+/// Lists all users with the number of files and bytes they store.
+pub async fn list_all_users(
+    pool: web::Data<DbPool>,
+    admin: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+
+    let users = list_users(&pool).map_err(|e| {
+        error!("Database error while loading users: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    })?;
+
+    let usage: HashMap<String, (i64, i64)> = storage_usage_by_user(&pool)
+        .map_err(|e| {
+            error!("Database error while computing storage usage: {}", e);
+            actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+        })?
+        .into_iter()
+        .map(|u| (u.user_id, (u.file_count, u.total_bytes)))
+        .collect();
+
+    let users: Vec<serde_json::Value> = users
+        .into_iter()
+        .map(|user| {
+            let (file_count, total_bytes) =
+                usage.get(&user.id.to_string()).copied().unwrap_or_default();
+            serde_json::json!({
+                "user": user,
+                "file_count": file_count,
+                "storage_bytes": total_bytes,
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(users))
+}
+
+/// POST /api/admin/users/{id}/suspend
+/// Suspends a user: their sessions are revoked and every credential stops working.
+pub async fn suspend_user(
+    pool: web::Data<DbPool>,
+    admin: AuthenticatedUser,
+    user_id: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+    let target = find_target_user(&pool, user_id.into_inner())?;
+
+    if target.id.to_string() == admin.user_id {
+        return Err(actix_web::error::ErrorBadRequest(
+            "You cannot suspend yourself",
+        ));
+    }
+    if target.is_suspended() {
+        return Err(actix_web::error::ErrorConflict("User is already suspended"));
+    }
+
+    let (user, revoked) = with_audit_log_entry(&pool, |conn| {
+        let user = set_user_suspended(conn, target.id, Some(chrono::Utc::now().naive_utc()))?;
+        let revoked = revoke_user_sessions_on(conn, &user.id.to_string(), None)?;
+        let entry = audit_entry(
+            &admin,
+            ACTION_SUSPEND_USER,
+            TARGET_USER,
+            user.id.to_string(),
+            serde_json::json!({ "revoked_sessions": revoked }),
+        );
+        Ok(((user, revoked), entry))
+    })
+    .map_err(|e| {
+        error!("Failed to suspend user: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+    })?;
+
+    info!(
+        "Admin {} suspended user {} ({} sessions revoked)",
+        admin.user_id, user.id, revoked
+    );
+
+    Ok(HttpResponse::Ok().json(user))
+}
+
+/// POST /api/admin/users/{id}/reactivate
+/// Lifts a suspension; the user has to log in again.
+pub async fn reactivate_user(
+    pool: web::Data<DbPool>,
+    admin: AuthenticatedUser,
+    user_id: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+    let target = find_target_user(&pool, user_id.into_inner())?;
+
+    if !target.is_suspended() {
+        return Err(actix_web::error::ErrorConflict("User is not suspended"));
+    }
+
+    let user = with_audit_log_entry(&pool, |conn| {
+        let user = set_user_suspended(conn, target.id, None)?;
+        let entry = audit_entry(
+            &admin,
+            ACTION_REACTIVATE_USER,
+            TARGET_USER,
+            user.id.to_string(),
+            serde_json::json!({ "suspended_at": target.suspended_at }),
+        );
+        Ok((user, entry))
+    })
+    .map_err(|e| {
+        error!("Failed to reactivate user: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+    })?;
+
+    info!("Admin {} reactivated user {}", admin.user_id, user.id);
+
+    Ok(HttpResponse::Ok().json(user))
+}
+
+/// PUT /api/admin/users/{id}/role
+/// Promotes a user to administrator or demotes them.
+pub async fn change_user_role(
+    pool: web::Data<DbPool>,
+    admin: AuthenticatedUser,
+    user_id: web::Path<i32>,
+    body: web::Json<ChangeUserRole>,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+
+    if !USER_ROLES.contains(&body.role.as_str()) {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Invalid role '{}', expected one of: {}",
+            body.role,
+            USER_ROLES.join(", ")
+        )));
+    }
+
+    let target = find_target_user(&pool, user_id.into_inner())?;
+
+    if target.id.to_string() == admin.user_id {
+        return Err(actix_web::error::ErrorBadRequest(
+            "You cannot change your own role",
+        ));
+    }
+    if target.is_service_account() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "The role of a service account cannot be changed",
+        ));
+    }
+
+    let user = with_audit_log_entry(&pool, |conn| {
+        let user = update_user_role(conn, target.id, &body.role)?;
+        let entry = audit_entry(
+            &admin,
+            ACTION_CHANGE_ROLE,
+            TARGET_USER,
+            user.id.to_string(),
+            serde_json::json!({ "from": target.role, "to": user.role }),
+        );
+        Ok((user, entry))
+    })
+    .map_err(|e| {
+        error!("Failed to change user role: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+    })?;
+
+    info!(
+        "Admin {} changed role of user {} to {}",
+        admin.user_id, user.id, user.role
+    );
+
+    Ok(HttpResponse::Ok().json(user))
+}
+
+/// PUT /api/admin/users/{id}/links
+/// Forbids a user to create links (`links_disabled: true`), on top of the
+/// sharing policy, or allows it again.
+pub async fn set_user_links_disabled(
+    pool: web::Data<DbPool>,
+    admin: AuthenticatedUser,
+    user_id: web::Path<i32>,
+    body: web::Json<SetLinksDisabled>,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+    let target = find_target_user(&pool, user_id.into_inner())?;
+
+    let user = with_audit_log_entry(&pool, |conn| {
+        let user = set_links_disabled(conn, target.id, body.links_disabled)?;
+        let entry = audit_entry(
+            &admin,
+            ACTION_SET_LINKS_DISABLED,
+            TARGET_USER,
+            user.id.to_string(),
+            serde_json::json!({ "from": target.links_disabled, "to": user.links_disabled }),
+        );
+        Ok((user, entry))
+    })
+    .map_err(|e| {
+        error!("Failed to change link permission of user: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+    })?;
+
+    info!(
+        "Admin {} set links_disabled of user {} to {}",
+        admin.user_id, user.id, user.links_disabled
+    );
+
+    Ok(HttpResponse::Ok().json(user))
+}
+
+/// DELETE /api/admin/files/{id}
+/// Deletes any file from S3 and the database, regardless of its owner.
+pub async fn force_delete_file(
+    pool: web::Data<DbPool>,
+    storage_s3: web::Data<S3Storage>,
+    admin: AuthenticatedUser,
+    file_id: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+
+    let file = find_s3_file_by_id(&pool, file_id.into_inner()).map_err(|e| {
+        warn!("File not found for deletion: {}", e);
+        actix_web::error::ErrorNotFound(format!("File not found: {}", e))
+    })?;
+
+    // Recorded first: once the object is gone from S3 the deletion cannot
+    // be undone, so it must not happen unlogged
+    let entry = audit_entry(
+        &admin,
+        ACTION_DELETE_FILE,
+        TARGET_FILE,
+        file.file_id.to_string(),
+        serde_json::json!({
+            "name": file.name,
+            "owner": file.user_id,
+            "size": file.size,
+        }),
+    );
+    insert_audit_log_entry(&pool, &entry).map_err(|e| {
+        error!("Failed to write audit log entry: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB insert error: {}", e))
+    })?;
+
+    storage_s3.delete_file(&file.s3_key).await.map_err(|e| {
+        actix_web::error::ErrorInternalServerError(format!("Failed to delete file from S3: {}", e))
+    })?;
+
+    delete_s3_file_by_id(&pool, file.file_id).map_err(|e| {
+        actix_web::error::ErrorInternalServerError(format!("DB delete error: {}", e))
+    })?;
+
+    info!(
+        "Admin {} deleted file {} owned by user {}",
+        admin.user_id, file.file_id, file.user_id
+    );
+
+    Ok(HttpResponse::Ok().json("File deleted successfully"))
+}
+
+/// GET /api/admin/sharing-policy
+/// Returns the organisation sharing policy.
+pub async fn get_sharing_policy(
+    admin: AuthenticatedUser,
+    policy: SharingPolicy,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+
+    Ok(HttpResponse::Ok().json(policy))
+}
+
+/// PUT /api/admin/sharing-policy
+/// Replaces the organisation sharing policy. It applies from the next share
+/// or link creation on.
+pub async fn update_sharing_policy(
+    pool: web::Data<DbPool>,
+    admin: AuthenticatedUser,
+    current: SharingPolicy,
+    body: web::Json<SharingPolicy>,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+
+    let policy = body
+        .into_inner()
+        .validated()
+        .map_err(actix_web::error::ErrorBadRequest)?;
+
+    with_audit_log_entry(&pool, |conn| {
+        update_setting(conn, SharingPolicy::SETTING, &policy)?;
+        let entry = audit_entry(
+            &admin,
+            ACTION_UPDATE_SHARING_POLICY,
+            TARGET_SETTING,
+            SharingPolicy::SETTING.to_string(),
+            serde_json::json!({ "from": current, "to": policy }),
+        );
+        Ok(((), entry))
+    })
+    .map_err(|e| {
+        error!("Failed to update sharing policy: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+    })?;
+
+    info!("Admin {} updated the sharing policy", admin.user_id);
+
+    Ok(HttpResponse::Ok().json(policy))
+}
+
+/// GET /api/admin/two-factor-policy
+/// Returns whether every user must sign in with a second factor.
+pub async fn get_two_factor_policy(
+    admin: AuthenticatedUser,
+    policy: TwoFactorPolicy,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+
+    Ok(HttpResponse::Ok().json(policy))
+}
+
+/// PUT /api/admin/two-factor-policy
+/// Requires two-factor authentication for everyone, or makes it optional
+/// again. Users who have not enrolled are asked to at their next login.
+pub async fn update_two_factor_policy(
+    pool: web::Data<DbPool>,
+    admin: AuthenticatedUser,
+    current: TwoFactorPolicy,
+    body: web::Json<TwoFactorPolicy>,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+    let policy = body.into_inner();
+
+    with_audit_log_entry(&pool, |conn| {
+        update_setting(conn, TwoFactorPolicy::SETTING, &policy)?;
+        let entry = audit_entry(
+            &admin,
+            ACTION_UPDATE_TWO_FACTOR_POLICY,
+            TARGET_SETTING,
+            TwoFactorPolicy::SETTING.to_string(),
+            serde_json::json!({ "from": current, "to": policy }),
+        );
+        Ok(((), entry))
+    })
+    .map_err(|e| {
+        error!("Failed to update two-factor policy: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+    })?;
+
+    info!(
+        "Admin {} set required two-factor authentication to {}",
+        admin.user_id, policy.required
+    );
+
+    Ok(HttpResponse::Ok().json(policy))
+}
+
+/// GET /api/admin/stats
+/// Returns instance-wide user, storage and session counts.
+pub async fn get_stats(
+    pool: web::Data<DbPool>,
+    admin: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+
+    let db_error = |e: diesel::result::Error| {
+        error!("Database error while computing statistics: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    };
+
+    let (users, admins, suspended) = count_users(&pool).map_err(db_error)?;
+    let (files, storage_bytes) = total_storage_usage(&pool).map_err(db_error)?;
+    let active_sessions =
+        count_active_sessions(&pool, chrono::Utc::now().naive_utc()).map_err(db_error)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "users": users,
+        "admins": admins,
+        "suspended_users": suspended,
+        "files": files,
+        "storage_bytes": storage_bytes,
+        "active_sessions": active_sessions,
+    })))
+}
+
+/// GET /api/admin/audit-log
+/// Lists admin actions, newest first.
+pub async fn get_audit_log(
+    pool: web::Data<DbPool>,
+    admin: AuthenticatedUser,
+    query: web::Query<AuditLogQuery>,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_AUDIT_LOG_LIMIT)
+        .clamp(1, MAX_AUDIT_LOG_LIMIT);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let entries = list_audit_log(&pool, limit, offset).map_err(|e| {
+        error!("Database error while loading audit log: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+fn find_target_user(pool: &DbPool, user_id: i32) -> Result<User, Error> {
+    find_user_by_id(pool, user_id).map_err(|e| {
+        warn!("User {} not found: {}", user_id, e);
+        actix_web::error::ErrorNotFound(format!("User not found: {}", e))
+    })
+}
+
+/// Describes an admin action for the audit log.
+pub fn audit_entry(
+    admin: &AuthenticatedUser,
+    action: &str,
+    target_type: &str,
+    target_id: String,
+    details: serde_json::Value,
+) -> NewAuditLogEntry {
+    NewAuditLogEntry {
+        admin_id: admin.user_id.clone(),
+        action: action.to_string(),
+        target_type: target_type.to_string(),
+        target_id,
+        details: Some(details.to_string()),
+    }
+}
@@ -0,0 +1,113 @@
+use crate::auth::jwt::AuthenticatedUser;
+use crate::auth::scopes;
+use crate::database::DbPool;
+use crate::models::file_changes::{ChangePosition, FileChange};
+use crate::pagination::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::repositories::file_changes::{change_watermark, list_file_changes};
+use crate::repositories::s3_files::find_accessible_s3_files;
+use crate::requests::query::ChangesQuery;
+use actix_web::{Error, HttpResponse, web};
+use log::{error, info};
+use std::collections::HashMap;
+
+/// GET /api/changes/startPageToken
+/// Returns the page token to pass to `/api/changes` later to get the
+/// changes made from now on.
+pub async fn get_start_page_token(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_READ)?;
+
+    let token = change_watermark(&pool).map_err(|e| {
+        error!("Database error while loading the start page token: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "startPageToken": token.to_string() })))
+}
+
+/// GET /api/changes?pageToken=
+/// Lists the changes to files the user can see, or could see before the
+/// change, oldest first. While there are more changes the response has a
+/// `nextPageToken`; the last page has a `newStartPageToken` to poll with
+/// later. `file` is the current state of the file, or `null` when the user
+/// no longer has access to it.
+pub async fn list_changes(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    query: web::Query<ChangesQuery>,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_READ)?;
+
+    let from = ChangePosition::parse(&query.page_token)
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("Invalid pageToken"))?;
+    let page_size = query.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&page_size) {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "pageSize must be between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+
+    let db_error = |e: diesel::result::Error| {
+        error!("Database error while loading changes: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    };
+
+    // No change can still appear before the watermark, so a page ending
+    // there cannot miss one
+    let until = change_watermark(&pool).map_err(db_error)?;
+    let mut changes =
+        list_file_changes(&pool, &user.user_id, from, until, page_size + 1).map_err(db_error)?;
+
+    let has_more = changes.len() as i64 > page_size;
+    changes.truncate(page_size as usize);
+
+    info!(
+        "User {} fetched {} changes from {}",
+        user.user_id,
+        changes.len(),
+        from
+    );
+
+    let items = change_json(&pool, &user.user_id, &changes).map_err(db_error)?;
+
+    let mut response = serde_json::json!({ "changes": items });
+    match changes.last() {
+        Some(last) if has_more => {
+            response["nextPageToken"] = last.position().next().to_string().into()
+        }
+        _ => response["newStartPageToken"] = until.max(from).to_string().into(),
+    }
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Serializes changes as `viewer` sees them: with the current state of each
+/// file they still have access to.
+pub fn change_json(
+    pool: &DbPool,
+    viewer: &str,
+    changes: &[FileChange],
+) -> Result<Vec<serde_json::Value>, diesel::result::Error> {
+    let file_ids: Vec<i32> = changes.iter().map(|change| change.file_id).collect();
+    let files: HashMap<i32, serde_json::Value> = find_accessible_s3_files(pool, viewer, &file_ids)?
+        .into_iter()
+        .map(|file| (file.file_id, serde_json::json!(file)))
+        .collect();
+
+    Ok(changes
+        .iter()
+        .map(|change| {
+            serde_json::json!({
+                "id": change.id,
+                "file_id": change.file_id,
+                "change_type": change.change_type,
+                "removed": change.is_removal(),
+                "created_at": change.created_at,
+                "file": files.get(&change.file_id),
+            })
+        })
+        .collect())
+}
@@ -0,0 +1,250 @@
+use crate::auth::jwt::AuthenticatedUser;
+use crate::auth::scopes;
+use crate::database::DbPool;
+use crate::events::{EventHub, HEARTBEAT_INTERVAL, Subscription};
+use crate::handlers::changes::change_json;
+use crate::models::file_changes::{ChangePosition, FileChange};
+use crate::repositories::sessions::find_session_by_id;
+use crate::repositories::users::find_user_by_id_str;
+use crate::requests::query::EventsQuery;
+use actix_web::{Error, HttpRequest, HttpResponse, web};
+use log::{error, info, warn};
+use std::time::Instant;
+
+/// Tells `EventSource` clients how long to wait before reconnecting.
+const RETRY_MILLIS: u32 = 5000;
+
+/// GET /api/events
+/// Streams the changes to files the user can see as server-sent events, in
+/// the format of `/api/changes`. Each event carries the change's position in
+/// the feed, so a reconnecting client resumes after the last change it got
+/// through
+/// `Last-Event-ID` (or `?lastEventId=`). Comment lines are sent as
+/// heartbeats while nothing changes.
+pub async fn stream_events(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    hub: web::Data<EventHub>,
+    user: AuthenticatedUser,
+    query: web::Query<EventsQuery>,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_READ)?;
+
+    let subscription = subscribe(&req, &pool, &hub, &user, &query)?;
+    info!("User {} opened an event stream", user.user_id);
+
+    let state = EventStream {
+        pool: pool.get_ref().clone(),
+        user,
+        subscription,
+        heartbeat: tokio::time::interval_at(
+            tokio::time::Instant::now() + HEARTBEAT_INTERVAL,
+            HEARTBEAT_INTERVAL,
+        ),
+        started: false,
+    };
+
+    let body = futures_util::stream::unfold(state, |mut state| async move {
+        let chunk = state.next_chunk().await?;
+        Some((Ok::<_, Error>(web::Bytes::from(chunk)), state))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        // Keeps reverse proxies from holding events back
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(body))
+}
+
+/// GET /api/events/ws
+/// The WebSocket variant of `/api/events`: each change is sent as a JSON
+/// text message. The server pings on every heartbeat and closes connections
+/// that stop answering.
+pub async fn stream_events_ws(
+    req: HttpRequest,
+    body: web::Payload,
+    pool: web::Data<DbPool>,
+    hub: web::Data<EventHub>,
+    user: AuthenticatedUser,
+    query: web::Query<EventsQuery>,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_READ)?;
+
+    let mut subscription = subscribe(&req, &pool, &hub, &user, &query)?;
+    let (response, mut session, mut messages) = actix_ws::handle(&req, body)?;
+    info!("User {} opened an event socket", user.user_id);
+
+    let pool = pool.get_ref().clone();
+    actix_web::rt::spawn(async move {
+        use actix_ws::{CloseCode, CloseReason, Message};
+        use futures_util::StreamExt;
+
+        let mut heartbeat = tokio::time::interval_at(
+            tokio::time::Instant::now() + HEARTBEAT_INTERVAL,
+            HEARTBEAT_INTERVAL,
+        );
+        let mut last_pong = Instant::now();
+
+        let reason = loop {
+            tokio::select! {
+                message = messages.next() => match message {
+                    Some(Ok(Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            return;
+                        }
+                    }
+                    Some(Ok(Message::Pong(_))) => last_pong = Instant::now(),
+                    Some(Ok(Message::Close(reason))) => break reason,
+                    // The socket only goes one way
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => {
+                        warn!("Event socket of user {} failed: {}", user.user_id, e);
+                        break None;
+                    }
+                    None => break None,
+                },
+                change = subscription.next() => {
+                    let text = match change.and_then(|change| event_json(&pool, &user, change)) {
+                        Ok(json) => json.to_string(),
+                        Err(e) => {
+                            error!("Database error while streaming changes: {}", e);
+                            break Some(CloseReason::from(CloseCode::Error));
+                        }
+                    };
+                    // Waits while the client is not reading, so a slow client
+                    // falls behind instead of piling up messages
+                    if session.text(text).await.is_err() {
+                        return;
+                    }
+                }
+                _ = heartbeat.tick() => {
+                    if last_pong.elapsed() > 2 * HEARTBEAT_INTERVAL {
+                        info!("Event socket of user {} timed out", user.user_id);
+                        break None;
+                    }
+                    if !still_signed_in(&pool, &user) {
+                        break Some(CloseReason::from(CloseCode::Policy));
+                    }
+                    if session.ping(b"").await.is_err() {
+                        return;
+                    }
+                }
+            }
+        };
+
+        let _ = session.close(reason).await;
+    });
+
+    Ok(response)
+}
+
+/// State of a server-sent event stream.
+struct EventStream {
+    pool: DbPool,
+    user: AuthenticatedUser,
+    subscription: Subscription,
+    heartbeat: tokio::time::Interval,
+    started: bool,
+}
+
+impl EventStream {
+    /// Waits for the next chunk of the stream; `None` ends it.
+    async fn next_chunk(&mut self) -> Option<String> {
+        if !self.started {
+            self.started = true;
+            return Some(format!("retry: {}\n\n", RETRY_MILLIS));
+        }
+
+        tokio::select! {
+            change = self.subscription.next() => {
+                let change = change.and_then(|change| {
+                    let position = change.position();
+                    event_json(&self.pool, &self.user, change).map(|json| (position, json))
+                });
+                match change {
+                    Ok((position, json)) => {
+                        Some(format!("id: {}\nevent: change\ndata: {}\n\n", position, json))
+                    }
+                    Err(e) => {
+                        error!("Database error while streaming changes: {}", e);
+                        None
+                    }
+                }
+            }
+            _ = self.heartbeat.tick() => {
+                if !still_signed_in(&self.pool, &self.user) {
+                    return None;
+                }
+                Some(": heartbeat\n\n".to_string())
+            }
+        }
+    }
+}
+
+/// Subscribes to the user's changes after the position the client resumes
+/// from, if any.
+fn subscribe(
+    req: &HttpRequest,
+    pool: &DbPool,
+    hub: &EventHub,
+    user: &AuthenticatedUser,
+    query: &EventsQuery,
+) -> Result<Subscription, Error> {
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok())
+        .or(query.last_event_id.as_deref())
+        .map(|id| {
+            ChangePosition::parse(id)
+                .ok_or_else(|| actix_web::error::ErrorBadRequest("Invalid Last-Event-ID"))
+        })
+        .transpose()?;
+
+    hub.subscribe(pool, &user.user_id, last_event_id)
+        .map_err(|e| {
+            error!("Database error while opening an event stream: {}", e);
+            actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+        })
+}
+
+/// Serializes a change as the user sees it, with the event ID to resume
+/// after it.
+fn event_json(
+    pool: &DbPool,
+    user: &AuthenticatedUser,
+    change: FileChange,
+) -> Result<serde_json::Value, diesel::result::Error> {
+    let position = change.position();
+    let mut json = change_json(pool, &user.user_id, &[change])?
+        .pop()
+        .unwrap_or_default();
+    json["event_id"] = position.to_string().into();
+
+    Ok(json)
+}
+
+/// Returns false once the session or account behind a stream is no longer
+/// allowed in, so streams do not outlive a sign-out or suspension.
+fn still_signed_in(pool: &DbPool, user: &AuthenticatedUser) -> bool {
+    let now = chrono::Utc::now().naive_utc();
+
+    if let Some(session_id) = user.session_id {
+        match find_session_by_id(pool, session_id) {
+            Ok(Some(session)) if session.is_active(now) => {}
+            Ok(_) => return false,
+            // Keep the stream through a database hiccup
+            Err(e) => warn!("Failed to check session {}: {}", session_id, e),
+        }
+    }
+
+    match find_user_by_id_str(pool, &user.user_id) {
+        Ok(account) => !account.is_deleted() && !account.is_suspended(),
+        Err(diesel::result::Error::NotFound) => false,
+        Err(e) => {
+            warn!("Failed to check user {}: {}", user.user_id, e);
+            true
+        }
+    }
+}
@@ -0,0 +1,378 @@
+use crate::auth::jwt::AuthenticatedUser;
+use crate::auth::scopes;
+use crate::database::DbPool;
+use crate::models::file_requests::{FileRequest, NewFileRequest};
+use crate::models::s3_files::NewS3File;
+use crate::notifications::Notifier;
+use crate::notifications::templates::Notification;
+use crate::policy::SharingPolicy;
+use crate::repositories::file_requests::{
+    find_file_request_by_id, find_file_request_by_token, insert_file_request,
+    list_file_requests_by_owner, release_upload_slot, reserve_upload_slot, revoke_file_request,
+};
+use crate::repositories::s3_files::insert_s3_file;
+use crate::repositories::users::find_user_by_id_str;
+use crate::requests::file_requests::CreateFileRequest;
+use crate::storage::S3Storage;
+use actix_web::http::header;
+use actix_web::{Error, HttpRequest, HttpResponse, web};
+use chrono::{Duration, Utc};
+use log::{error, info, warn};
+use mime_guess::from_path;
+use std::env;
+use uuid::Uuid;
+
+/// Maximum length of the uploader's self-declared name.
+const MAX_UPLOADER_NAME_LEN: usize = 200;
+
+/// Largest file accepted through any file request, whatever the request
+/// allows (`FILE_REQUEST_MAX_FILE_SIZE`, default 100 MB). Uploads are
+/// anonymous and buffered in memory, so this is always enforced.
+fn instance_max_file_size() -> i64 {
+    env::var("FILE_REQUEST_MAX_FILE_SIZE")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(100 * 1024 * 1024)
+}
+
+/// POST /api/file-requests
+/// Creates a link that lets anonymous callers upload files to the user.
+pub async fn create_file_request(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    policy: SharingPolicy,
+    body: web::Json<CreateFileRequest>,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::SHARING)?;
+    let body = body.into_inner();
+    info!(
+        "User {} creates file request '{}'",
+        user.user_id, body.title
+    );
+
+    let title = body.title.trim().to_string();
+    if title.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest("Title must not be empty"));
+    }
+    if body.max_file_size.is_some_and(|size| size <= 0) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "max_file_size must be positive",
+        ));
+    }
+    if body
+        .max_file_size
+        .is_some_and(|size| size > instance_max_file_size())
+    {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "max_file_size must be at most {} bytes",
+            instance_max_file_size()
+        )));
+    }
+    if body.max_files.is_some_and(|count| count <= 0) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "max_files must be positive",
+        ));
+    }
+    if body.expires_in_hours.is_some_and(|hours| hours <= 0) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "expires_in_hours must be positive",
+        ));
+    }
+
+    // Without an explicit expiry the link lives as long as the policy allows.
+    let expires_at = match body.expires_in_hours.or(policy.max_link_lifetime_hours) {
+        Some(hours) => Some(
+            Duration::try_hours(hours)
+                .and_then(|lifetime| Utc::now().checked_add_signed(lifetime))
+                .ok_or_else(|| actix_web::error::ErrorBadRequest("expires_in_hours is too large"))?
+                .naive_utc(),
+        ),
+        None => None,
+    };
+
+    let creator = find_user_by_id_str(&pool, &user.user_id).map_err(|e| {
+        warn!("User not found: {}", e);
+        actix_web::error::ErrorNotFound(format!("User not found: {}", e))
+    })?;
+
+    if let Err(reason) = policy.check_link(&creator, expires_at) {
+        warn!("Sharing policy rejected file request: {}", reason);
+        return Err(actix_web::error::ErrorForbidden(reason));
+    }
+
+    let new_request = NewFileRequest {
+        token: Uuid::new_v4().simple().to_string(),
+        owner_id: user.user_id,
+        title,
+        description: body.description,
+        max_file_size: body.max_file_size,
+        allowed_mime_types: body
+            .allowed_mime_types
+            .unwrap_or_default()
+            .into_iter()
+            .map(|m| m.trim().to_lowercase())
+            .filter(|m| !m.is_empty())
+            .collect(),
+        max_files: body.max_files,
+        expires_at,
+    };
+
+    let file_request = insert_file_request(&pool, &new_request).map_err(|e| {
+        error!("Failed to insert file request: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB insert error: {}", e))
+    })?;
+
+    info!("Created file request {}", file_request.id);
+
+    Ok(HttpResponse::Created().json(file_request))
+}
+
+/// GET /api/file-requests
+/// Lists the file requests created by the user.
+pub async fn list_file_requests(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::SHARING)?;
+    let requests = list_file_requests_by_owner(&pool, &user.user_id).map_err(|e| {
+        error!("Database error while loading file requests: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(requests))
+}
+
+/// DELETE /api/file-requests/{id}
+/// Revokes a file request so its link stops accepting uploads.
+pub async fn revoke_request(
+    pool: web::Data<DbPool>,
+    request_id: web::Path<i32>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::SHARING)?;
+    let file_request = find_file_request_by_id(&pool, request_id.into_inner()).map_err(|e| {
+        warn!("File request not found: {}", e);
+        actix_web::error::ErrorNotFound(format!("File request not found: {}", e))
+    })?;
+
+    if file_request.owner_id != user.user_id {
+        warn!(
+            "User {} tried to revoke someone else's file request {}",
+            user.user_id, file_request.id
+        );
+        return Err(actix_web::error::ErrorForbidden(
+            "You do not own this file request",
+        ));
+    }
+
+    let file_request = revoke_file_request(&pool, file_request.id).map_err(|e| {
+        error!("Failed to revoke file request: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+    })?;
+
+    info!(
+        "User {} revoked file request {}",
+        user.user_id, file_request.id
+    );
+
+    Ok(HttpResponse::Ok().json(file_request))
+}
+
+/// GET /api/public/file-requests/{token}
+/// Describes an open file request to an anonymous uploader.
+pub async fn get_public_file_request(
+    pool: web::Data<DbPool>,
+    token: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let file_request = find_open_file_request(&pool, &token)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "title": file_request.title,
+        "description": file_request.description,
+        "max_file_size": file_request.upload_limit(instance_max_file_size()),
+        "allowed_mime_types": file_request.allowed_mime_types,
+        "remaining_uploads": file_request
+            .max_files
+            .map(|max| (max - file_request.upload_count).max(0)),
+        "expires_at": file_request.expires_at,
+    })))
+}
+
+/// POST /api/public/file-requests/{token}/upload
+/// Accepts an anonymous upload for a file request and stores it for the owner.
+/// The uploader identifies themselves with `X-Uploader-Name` and optionally
+/// `X-Uploader-Email`; the file name comes from `X-Filename`.
+pub async fn upload_to_file_request(
+    pool: web::Data<DbPool>,
+    storage_s3: web::Data<S3Storage>,
+    notifier: web::Data<Notifier>,
+    token: web::Path<String>,
+    req: HttpRequest,
+    payload: web::Payload,
+) -> Result<HttpResponse, Error> {
+    let file_request = find_open_file_request(&pool, &token)?;
+
+    let uploader_name = header_value(&req, "X-Uploader-Name")
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("X-Uploader-Name header is required"))?;
+    if uploader_name.chars().count() > MAX_UPLOADER_NAME_LEN {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "X-Uploader-Name must be at most {} characters",
+            MAX_UPLOADER_NAME_LEN
+        )));
+    }
+
+    let uploader_email = header_value(&req, "X-Uploader-Email");
+    if uploader_email
+        .as_ref()
+        .is_some_and(|email| email.parse::<lettre::Address>().is_err())
+    {
+        return Err(actix_web::error::ErrorBadRequest(
+            "X-Uploader-Email is not a valid email address",
+        ));
+    }
+
+    let original_name = header_value(&req, "X-Filename")
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("X-Filename header is required"))?;
+
+    // Same MIME detection as S3Storage::save_file: header first, then extension.
+    let mime_type = header_value(&req, header::CONTENT_TYPE.as_str())
+        .or_else(|| from_path(&original_name).first().map(|m| m.to_string()))
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+
+    if !file_request.accepts_mime_type(&mime_type) {
+        return Err(actix_web::error::ErrorUnsupportedMediaType(format!(
+            "Files of type '{}' are not accepted by this request",
+            mime_type
+        )));
+    }
+
+    let max_size = file_request.upload_limit(instance_max_file_size());
+    let declared_size =
+        header_value(&req, header::CONTENT_LENGTH.as_str()).and_then(|len| len.parse::<i64>().ok());
+    if declared_size.is_some_and(|size| size > max_size) {
+        return Err(actix_web::error::ErrorPayloadTooLarge(format!(
+            "File exceeds the maximum size of {} bytes",
+            max_size
+        )));
+    }
+
+    let slot = reserve_upload_slot(&pool, file_request.id).map_err(|e| {
+        error!("Failed to reserve upload slot: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+    })?;
+    if slot.is_none() {
+        return Err(actix_web::error::ErrorConflict(
+            "This file request has reached its upload limit",
+        ));
+    }
+
+    info!(
+        "Anonymous upload '{}' by '{}' for file request {}",
+        original_name, uploader_name, file_request.id
+    );
+
+    let (original_name, s3_key, size, mime_type_from_save) =
+        match storage_s3.save_file(&req, payload, Some(max_size)).await {
+            Ok(saved) => saved,
+            Err(e) => {
+                give_back_slot(&pool, &file_request);
+                return Err(e);
+            }
+        };
+
+    let now = Utc::now().naive_utc();
+    let new_s3_file = NewS3File {
+        name: original_name.clone(),
+        mime_type: mime_type_from_save.unwrap_or(mime_type),
+        size,
+        created_at: now,
+        s3_key,
+        etag: None,
+        user_id: file_request.owner_id.clone(),
+        uploader_name: Some(uploader_name.clone()),
+        uploader_email: uploader_email.clone(),
+        file_request_id: Some(file_request.id),
+        modified_at: now,
+    };
+
+    let s3_file = match insert_s3_file(&pool, &new_s3_file) {
+        Ok(s3_file) => s3_file,
+        Err(e) => {
+            error!("Failed to insert S3 file metadata: {}", e);
+            give_back_slot(&pool, &file_request);
+            if let Err(e) = storage_s3.delete_file(&new_s3_file.s3_key).await {
+                error!("Failed to clean up orphaned S3 object: {}", e);
+            }
+            return Err(actix_web::error::ErrorInternalServerError(format!(
+                "DB insert error: {}",
+                e
+            )));
+        }
+    };
+
+    notifier.notify_user(
+        &pool,
+        &file_request.owner_id,
+        Notification::FileRequestUpload {
+            file_id: s3_file.file_id,
+            request_title: file_request.title,
+            file_name: s3_file.name,
+            uploader_name,
+            uploader_email,
+        },
+    );
+
+    Ok(HttpResponse::Ok().json("File uploaded successfully"))
+}
+
+/// Loads a file request by token and checks that it still accepts uploads.
+/// Requests of suspended or deleted owners are closed.
+fn find_open_file_request(pool: &DbPool, token: &str) -> Result<FileRequest, Error> {
+    let file_request = find_file_request_by_token(pool, token).map_err(|e| {
+        warn!("File request not found: {}", e);
+        actix_web::error::ErrorNotFound("File request not found")
+    })?;
+
+    if !file_request.is_open(Utc::now().naive_utc()) {
+        return Err(actix_web::error::ErrorGone(
+            "This file request is closed or has expired",
+        ));
+    }
+
+    let owner = find_user_by_id_str(pool, &file_request.owner_id).map_err(|e| {
+        error!(
+            "Failed to load owner of file request {}: {}",
+            file_request.id, e
+        );
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+    if owner.is_suspended() || owner.is_deleted() {
+        warn!(
+            "Refusing file request {} of inactive user {}",
+            file_request.id, owner.id
+        );
+        return Err(actix_web::error::ErrorGone(
+            "This file request is closed or has expired",
+        ));
+    }
+
+    Ok(file_request)
+}
+
+fn give_back_slot(pool: &DbPool, file_request: &FileRequest) {
+    if let Err(e) = release_upload_slot(pool, file_request.id) {
+        error!(
+            "Failed to release upload slot on file request {}: {}",
+            file_request.id, e
+        );
+    }
+}
+
+fn header_value(req: &HttpRequest, name: &str) -> Option<String> {
+    req.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
@@ -1,28 +1,79 @@
 use crate::auth::jwt::AuthenticatedUser;
-use crate::models::s3_files::NewS3File;
-use crate::repositories::s3_files::load_all_s3_files;
+use crate::auth::scopes;
+use crate::database::DbPool;
+use crate::models::s3_files::{NewS3File, S3File};
+use crate::pagination::{Page, Sort, SortKey};
+use crate::repositories::file_contents::{
+    count_search_results, search_file_contents, suggest_file_names,
+};
+use crate::repositories::file_permissions::has_file_access;
 use crate::repositories::s3_files::{
-    delete_s3_file_by_id, find_s3_file_by_id, find_s3_files_by_key, insert_s3_file,
+    count_accessible_s3_files, delete_s3_file_by_id, find_s3_file_by_id, insert_s3_file,
+    list_s3_files_page,
 };
+use crate::requests::query::{ListQuery, SearchQuery, SuggestQuery};
+use crate::search::query::{ParsedQuery, parse};
+use crate::search::render_highlight;
 use crate::storage::S3Storage;
-use crate::{database::DbPool, requests::query::SearchQuery};
+use actix_web::error::InternalError;
 use actix_web::http::header;
 use actix_web::{Error, HttpResponse, web};
 use log::{debug, error, info, warn};
 use mime_guess::from_path;
 use tokio_util::io::ReaderStream;
 
+/// Names returned by `suggest_files` when no `limit` is given.
+const DEFAULT_SUGGESTIONS: i64 = 10;
+/// Upper bound for the `limit` of `suggest_files`.
+const MAX_SUGGESTIONS: i64 = 50;
+
 /// GET /api/files
-/// Returns a list of all files stored in the database.
-pub async fn list_files(pool: web::Data<DbPool>) -> Result<HttpResponse, Error> {
-    info!("Fetching all files from the database");
+/// Returns one page of the files the user owns or has been granted access
+/// to, newest first by default. See `ListQuery` for paging, sorting and
+/// field selection.
+pub async fn list_files(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    listing: web::Query<ListQuery>,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_READ)?;
+
+    let page = Page::from_query(&listing, Sort::NEWEST_FIRST)
+        .map_err(actix_web::error::ErrorBadRequest)?;
+    if page.sort.key == SortKey::Relevance {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Only searches can be sorted by relevance",
+        ));
+    }
+    info!("Fetching a page of files for user {}", user.user_id);
 
-    let s3_files = load_all_s3_files(&pool).map_err(|e| {
+    let db_error = |e: diesel::result::Error| {
         error!("Database error while loading files: {}", e);
         actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
-    })?;
+    };
+
+    let mut files = list_s3_files_page(
+        &pool,
+        &user.user_id,
+        page.sort,
+        page.after.as_ref(),
+        page.size + 1,
+    )
+    .map_err(db_error)?;
+    let next_page_token = page.next_token(&mut files, |file| (file, None));
 
-    Ok(HttpResponse::Ok().json(s3_files))
+    let mut response = serde_json::json!({
+        "files": files.iter().map(|file| page.project(file)).collect::<Vec<_>>(),
+    });
+    if let Some(token) = next_page_token {
+        response["nextPageToken"] = token.into();
+    }
+    if page.include_total {
+        let total = count_accessible_s3_files(&pool, &user.user_id).map_err(db_error)?;
+        response["totalCount"] = total.into();
+    }
+
+    Ok(HttpResponse::Ok().json(response))
 }
 
 /// GET api/file/{id}/meta
@@ -30,7 +81,9 @@ pub async fn list_files(pool: web::Data<DbPool>) -> Result<HttpResponse, Error>
 pub async fn get_metadata(
     pool: web::Data<DbPool>,
     file_id: web::Path<i32>,
+    user: AuthenticatedUser,
 ) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_READ)?;
     info!("Fetching metadata for file_id: {}", file_id);
 
     let file_id = file_id.into_inner();
@@ -41,29 +94,161 @@ pub async fn get_metadata(
         actix_web::error::ErrorNotFound(format!("S3 file not found: {}", e))
     })?;
 
+    ensure_file_access(&pool, &s3_file, &user)?;
+
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "name": s3_file.name,
         "mime_type": s3_file.mime_type,
         "size": s3_file.size,
         "created_at": s3_file.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+        "modified_at": s3_file.modified_at.format("%Y-%m-%d %H:%M:%S").to_string(),
     })))
 }
 
 /// GET /api/files/search
-/// Searches files by name with pagination
+/// Searches the files the user can access. `q` mixes free text, matched
+/// against names and extracted contents, with operators such as `type:pdf`
+/// or `size>10MB`. Matched terms in the content snippet are wrapped in
+/// `<mark>`; the rest of the snippet is HTML-escaped.
 pub async fn search_files(
     pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
     query: web::Query<SearchQuery>,
+    listing: web::Query<ListQuery>,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_READ)?;
+
+    let parsed = parse_search_query(&query.q)?;
+    info!("User {} is searching files for '{}'", user.user_id, query.q);
+
+    let results = search_results(&pool, &user.user_id, &parsed, &listing)?;
+    Ok(HttpResponse::Ok().json(results))
+}
+
+/// Parses a search box query. An invalid query gets `400` naming the
+/// offending token and its position.
+pub fn parse_search_query(q: &str) -> Result<ParsedQuery, Error> {
+    let parsed = parse(q).map_err(|e| {
+        warn!("Rejecting search query '{}': {}", q, e);
+        let response = HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e.message,
+            "token": e.token,
+            "position": e.position,
+        }));
+        InternalError::from_response(e.to_string(), response)
+    })?;
+    if parsed.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Search query must not be empty",
+        ));
+    }
+
+    Ok(parsed)
+}
+
+/// The sort order of a search when `orderBy` is not given.
+pub fn default_search_sort(parsed: &ParsedQuery) -> Sort {
+    match parsed.text {
+        Some(_) => Sort::RELEVANCE,
+        None => Sort::NEWEST_FIRST,
+    }
+}
+
+/// Runs a search as `viewer` and builds one page of the response.
+pub fn search_results(
+    pool: &DbPool,
+    viewer: &str,
+    parsed: &ParsedQuery,
+    listing: &ListQuery,
+) -> Result<serde_json::Value, Error> {
+    let page = Page::from_query(listing, default_search_sort(parsed))
+        .map_err(actix_web::error::ErrorBadRequest)?;
+    if page.sort.key == SortKey::Relevance && parsed.text.is_none() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Only searches with free text can be sorted by relevance",
+        ));
+    }
+
+    let db_error = |e: diesel::result::Error| {
+        error!("Database error while searching files: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    };
+
+    let mut hits = search_file_contents(
+        pool,
+        viewer,
+        parsed,
+        page.sort,
+        page.after.as_ref(),
+        page.size + 1,
+    )
+    .map_err(db_error)?;
+    let next_page_token = page.next_token(&mut hits, |hit| (&hit.file, hit.rank));
+
+    let results: Vec<serde_json::Value> = hits
+        .iter()
+        .map(|hit| {
+            serde_json::json!({
+                "file": page.project(&hit.file),
+                "rank": hit.rank,
+                "snippet": hit.headline.as_deref().map(render_highlight),
+            })
+        })
+        .collect();
+
+    let mut response = serde_json::json!({ "results": results });
+    if let Some(token) = next_page_token {
+        response["nextPageToken"] = token.into();
+    }
+    if page.include_total {
+        response["totalCount"] = count_search_results(pool, viewer, parsed)
+            .map_err(db_error)?
+            .into();
+    }
+
+    Ok(response)
+}
+
+/// GET /api/files/suggest?q=rep
+/// Autocompletes a file name typed into the search box: files the user can
+/// access whose name contains `q`, ignoring case and accents, with names
+/// starting with it first.
+pub async fn suggest_files(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    query: web::Query<SuggestQuery>,
 ) -> Result<HttpResponse, Error> {
-    info!("Searching files by name {}:", query.q);
+    user.require_scope(scopes::FILES_READ)?;
+
+    let prefix = query.q.trim();
+    if prefix.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest("q must not be empty"));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_SUGGESTIONS);
+    if !(1..=MAX_SUGGESTIONS).contains(&limit) {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "limit must be between 1 and {}",
+            MAX_SUGGESTIONS
+        )));
+    }
 
-    // Get files from S3
-    let s3_files = find_s3_files_by_key(&pool, &query.q).map_err(|e| {
-        warn!("S3 files not found for search: {}", e);
-        actix_web::error::ErrorNotFound(format!("S3 files not found: {}", e))
+    let files = suggest_file_names(&pool, &user.user_id, prefix, limit).map_err(|e| {
+        error!("Database error while suggesting file names: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
     })?;
 
-    Ok(HttpResponse::Ok().json(s3_files))
+    let suggestions: Vec<serde_json::Value> = files
+        .into_iter()
+        .map(|file| {
+            serde_json::json!({
+                "file_id": file.file_id,
+                "name": file.name,
+                "mime_type": file.mime_type,
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(suggestions))
 }
 
 /// POST /api/files
@@ -75,6 +260,7 @@ pub async fn upload_file(
     req: actix_web::HttpRequest,
     payload: web::Payload,
 ) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_WRITE)?;
     info!("User: {} is uploading a file", user.user_id);
 
     // Get the original filename from the request headers
@@ -88,17 +274,22 @@ pub async fn upload_file(
 
     // Save the file to S3
     let (original_name, s3_key, size, mime_type_from_save) =
-        storage_s3.save_file(&req, payload).await?;
+        storage_s3.save_file(&req, payload, None).await?;
 
     // Create a new S3 file record with metadata
+    let now = chrono::Utc::now().naive_utc();
     let new_s3_file = NewS3File {
         name: original_name.clone(),
-        mime_type: mime_type_from_save.unwrap_or_else(|| mime_type),
+        mime_type: mime_type_from_save.unwrap_or(mime_type),
         size,
-        created_at: chrono::Utc::now().naive_utc(),
+        created_at: now,
         s3_key,
         etag: None, // S3 etag will be set after upload
         user_id: user.user_id,
+        uploader_name: None,
+        uploader_email: None,
+        file_request_id: None,
+        modified_at: now,
     };
 
     // Insert the file metadata into the database
@@ -121,6 +312,7 @@ pub async fn delete_file(
     file_id: web::Path<i32>,
     user: AuthenticatedUser,
 ) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_DELETE)?;
     info!(
         "User {} attempts to delete file ID {}",
         user.user_id, file_id
@@ -161,7 +353,9 @@ pub async fn download_file(
     pool: web::Data<DbPool>,
     storage_s3: web::Data<S3Storage>,
     file_id: web::Path<i32>,
+    user: AuthenticatedUser,
 ) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_READ)?;
     info!("Downloading file with ID: {}", file_id);
 
     let file = find_s3_file_by_id(&pool, file_id.into_inner()).map_err(|e| {
@@ -169,6 +363,8 @@ pub async fn download_file(
         actix_web::error::ErrorNotFound(format!("File not found: {}", e))
     })?;
 
+    ensure_file_access(&pool, &file, &user)?;
+
     debug!("Downloading file from S3 with key: {}", file.s3_key);
     let byte_stream = storage_s3.download_file(&file.s3_key).await?;
 
@@ -183,3 +379,25 @@ pub async fn download_file(
         .append_header((header::CONTENT_TYPE, file.mime_type))
         .streaming(stream))
 }
+
+/// Checks that the user owns the file or has been granted access to it.
+/// The Forbidden response points at the access-request endpoint.
+fn ensure_file_access(pool: &DbPool, file: &S3File, user: &AuthenticatedUser) -> Result<(), Error> {
+    let allowed = has_file_access(pool, file, &user.user_id).map_err(|e| {
+        error!("Database error while checking file access: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    })?;
+
+    if !allowed {
+        warn!(
+            "User {} has no access to file (ID: {})",
+            user.user_id, file.file_id
+        );
+        return Err(actix_web::error::ErrorForbidden(format!(
+            "You do not have access to this file. Request access via POST /api/files/{}/access-requests",
+            file.file_id
+        )));
+    }
+
+    Ok(())
+}
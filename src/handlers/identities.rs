@@ -0,0 +1,57 @@
+use crate::auth::jwt::AuthenticatedUser;
+use crate::database::DbPool;
+use crate::repositories::user_identities::{UnlinkOutcome, list_user_identities, unlink_identity};
+use actix_web::{Error, HttpResponse, web};
+use log::{error, info, warn};
+
+/// GET /api/me/identities
+/// Lists the login providers linked to the user's account.
+pub async fn list_identities(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+    let identities = list_user_identities(&pool, user.id()?).map_err(|e| {
+        error!("Database error while loading identities: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(identities))
+}
+
+/// DELETE /api/me/identities/{id}
+/// Unlinks a login provider from the user's account.
+/// The last identity cannot be removed, so the account stays reachable.
+pub async fn unlink(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    identity_id: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+    let identity_id = identity_id.into_inner();
+
+    let outcome = unlink_identity(&pool, user.id()?, identity_id).map_err(|e| {
+        error!("Failed to unlink identity: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB delete error: {}", e))
+    })?;
+
+    match outcome {
+        UnlinkOutcome::Unlinked(identity) => {
+            info!(
+                "User {} unlinked {} identity {}",
+                user.user_id, identity.provider, identity.id
+            );
+            Ok(HttpResponse::Ok().json("Identity unlinked"))
+        }
+        UnlinkOutcome::NotFound => Err(actix_web::error::ErrorNotFound("Identity not found")),
+        UnlinkOutcome::LastIdentity => {
+            warn!(
+                "User {} tried to unlink their last identity {}",
+                user.user_id, identity_id
+            );
+            Err(actix_web::error::ErrorConflict(
+                "Cannot unlink the only login provider of the account; link another one first",
+            ))
+        }
+    }
+}
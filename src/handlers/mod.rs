@@ -1,2 +1,14 @@
+pub mod access_requests;
+pub mod account;
+pub mod admin;
+pub mod changes;
+pub mod events;
+pub mod file_requests;
 pub mod files;
+pub mod identities;
+pub mod saved_searches;
+pub mod service_accounts;
+pub mod sessions;
+pub mod tokens;
+pub mod two_factor;
 pub mod users;
@@ -0,0 +1,406 @@
+use crate::auth::jwt::AuthenticatedUser;
+use crate::auth::scopes;
+use crate::database::DbPool;
+use crate::handlers::files::{parse_search_query, search_results};
+use crate::models::saved_searches::{NewSavedSearch, NewSavedSearchShare, SavedSearch};
+use crate::notifications::templates::Notification;
+use crate::notifications::{Notifier, display_name};
+use crate::pagination::{Sort, SortKey};
+use crate::policy::SharingPolicy;
+use crate::repositories::saved_searches::{
+    delete_saved_search, delete_saved_search_share, find_saved_search_by_id, insert_saved_search,
+    insert_saved_search_share, is_saved_search_shared_with, list_saved_search_shares,
+    list_saved_searches_for_user, update_saved_search,
+};
+use crate::repositories::users::{find_user_by_email, find_user_by_id_str};
+use crate::requests::query::ListQuery;
+use crate::requests::saved_searches::{CreateSavedSearch, ShareSavedSearch, UpdateSavedSearch};
+use actix_web::{Error, HttpResponse, web};
+use diesel::result::DatabaseErrorKind;
+use log::{error, info, warn};
+
+/// Maximum length of a saved search's name.
+const MAX_NAME_LEN: usize = 200;
+
+/// POST /api/saved-searches
+/// Saves a search query, and optionally its sort order, under a name.
+pub async fn create_saved_search(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    body: web::Json<CreateSavedSearch>,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_WRITE)?;
+    let body = body.into_inner();
+
+    let (name, query, order_by) = validate(&body.name, &body.q, body.order_by.as_deref())?;
+    info!("User {} saves search '{}'", user.user_id, name);
+
+    let new_search = NewSavedSearch {
+        owner_id: user.user_id.clone(),
+        name,
+        query,
+        order_by,
+    };
+
+    let search =
+        insert_saved_search(&pool, &new_search).map_err(|e| write_error(e, &new_search.name))?;
+    info!("Created saved search {}", search.id);
+
+    Ok(HttpResponse::Created().json(search))
+}
+
+/// GET /api/saved-searches
+/// Lists the user's saved searches and those shared with them, by name.
+pub async fn list_saved_searches(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_READ)?;
+    let searches = list_saved_searches_for_user(&pool, &user.user_id).map_err(|e| {
+        error!("Database error while loading saved searches: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(searches))
+}
+
+/// GET /api/saved-searches/{id}
+/// Returns a saved search the user owns or that was shared with them.
+pub async fn get_saved_search(
+    pool: web::Data<DbPool>,
+    search_id: web::Path<i32>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_READ)?;
+    let search = find_visible_search(&pool, search_id.into_inner(), &user)?;
+
+    Ok(HttpResponse::Ok().json(search))
+}
+
+/// PATCH /api/saved-searches/{id}
+/// Renames a saved search or changes its query or sort order.
+pub async fn update_search(
+    pool: web::Data<DbPool>,
+    search_id: web::Path<i32>,
+    user: AuthenticatedUser,
+    body: web::Json<UpdateSavedSearch>,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_WRITE)?;
+    let search = find_owned_search(&pool, search_id.into_inner(), &user)?;
+    let body = body.into_inner();
+
+    let order_by = match body.order_by {
+        Some(order_by) => Some(order_by),
+        None => search.order_by.clone(),
+    };
+    let (name, query, order_by) = validate(
+        body.name.as_deref().unwrap_or(&search.name),
+        body.q.as_deref().unwrap_or(&search.query),
+        order_by.as_deref(),
+    )?;
+
+    let search = update_saved_search(&pool, search.id, &name, &query, order_by.as_deref())
+        .map_err(|e| write_error(e, &name))?;
+    info!("User {} updated saved search {}", user.user_id, search.id);
+
+    Ok(HttpResponse::Ok().json(search))
+}
+
+/// DELETE /api/saved-searches/{id}
+/// Deletes a saved search, also for the users it was shared with.
+pub async fn delete_search(
+    pool: web::Data<DbPool>,
+    search_id: web::Path<i32>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_WRITE)?;
+    let search = find_owned_search(&pool, search_id.into_inner(), &user)?;
+
+    delete_saved_search(&pool, search.id).map_err(|e| {
+        error!("Failed to delete saved search: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB delete error: {}", e))
+    })?;
+    info!("User {} deleted saved search {}", user.user_id, search.id);
+
+    Ok(HttpResponse::Ok().json("Saved search deleted"))
+}
+
+/// GET /api/saved-searches/{id}/files
+/// Opens a saved search like a folder: runs its query now, as the user
+/// opening it, so the results only include files they can access and
+/// `owner:me` means them. Paging and projection work as for searches; the
+/// saved sort order applies unless `orderBy` is given.
+pub async fn list_saved_search_files(
+    pool: web::Data<DbPool>,
+    search_id: web::Path<i32>,
+    user: AuthenticatedUser,
+    listing: web::Query<ListQuery>,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::FILES_READ)?;
+    let search = find_visible_search(&pool, search_id.into_inner(), &user)?;
+
+    let parsed = parse_search_query(&search.query)?;
+    let mut listing = listing.into_inner();
+    if listing.order_by.is_none() {
+        listing.order_by = search.order_by.clone();
+    }
+
+    info!(
+        "User {} opens saved search {} ('{}')",
+        user.user_id, search.id, search.query
+    );
+
+    let results = search_results(&pool, &user.user_id, &parsed, &listing)?;
+    Ok(HttpResponse::Ok().json(results))
+}
+
+/// POST /api/saved-searches/{id}/shares
+/// Shares a saved search with the user registered under the verified
+/// `email`. They see the results they have access to, not the files of the
+/// owner.
+pub async fn share_search(
+    pool: web::Data<DbPool>,
+    notifier: web::Data<Notifier>,
+    search_id: web::Path<i32>,
+    user: AuthenticatedUser,
+    policy: SharingPolicy,
+    body: web::Json<ShareSavedSearch>,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::SHARING)?;
+    let search = find_owned_search(&pool, search_id.into_inner(), &user)?;
+
+    // Unknown addresses and users the search may not be shared with get the
+    // same answer, so the endpoint does not reveal who has an account
+    let unavailable =
+        || actix_web::error::ErrorNotFound("No user with this email you can share with");
+
+    let email = body.email.trim();
+    let recipient = find_user_by_email(&pool, email)
+        .map_err(|e| {
+            error!("Database error while loading user: {}", e);
+            actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+        })?
+        .ok_or_else(unavailable)?;
+    let recipient_id = recipient.id.to_string();
+    if recipient_id == user.user_id {
+        return Err(actix_web::error::ErrorBadRequest(
+            "You cannot share a search with yourself",
+        ));
+    }
+    if recipient.is_suspended() || recipient.is_service_account() {
+        warn!(
+            "Refusing to share saved search {} with unavailable user {}",
+            search.id, recipient_id
+        );
+        return Err(unavailable());
+    }
+
+    let sharer = find_user_by_id_str(&pool, &user.user_id).map_err(|e| {
+        warn!("User not found: {}", e);
+        actix_web::error::ErrorNotFound(format!("User not found: {}", e))
+    })?;
+    if let Err(reason) = policy.check_share(&sharer, &recipient) {
+        warn!(
+            "Sharing policy rejected sharing saved search {}: {}",
+            search.id, reason
+        );
+        return Err(unavailable());
+    }
+
+    let new_share = NewSavedSearchShare {
+        saved_search_id: search.id,
+        user_id: recipient_id,
+        shared_by: user.user_id.clone(),
+    };
+    let share = insert_saved_search_share(&pool, &new_share).map_err(|e| {
+        error!("Failed to share saved search: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB insert error: {}", e))
+    })?;
+
+    let Some(share) = share else {
+        return Err(actix_web::error::ErrorConflict(
+            "The search is already shared with this user",
+        ));
+    };
+
+    info!(
+        "User {} shared saved search {} with user {}",
+        user.user_id, search.id, share.user_id
+    );
+
+    notifier.notify_user(
+        &pool,
+        &share.user_id,
+        Notification::SavedSearchShared {
+            saved_search_id: search.id,
+            name: search.name,
+            shared_by: display_name(&pool, &user.user_id),
+        },
+    );
+
+    Ok(HttpResponse::Created().json(share))
+}
+
+/// GET /api/saved-searches/{id}/shares
+/// Lists the users a saved search is shared with.
+pub async fn list_shares(
+    pool: web::Data<DbPool>,
+    search_id: web::Path<i32>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_scope(scopes::SHARING)?;
+    let search = find_owned_search(&pool, search_id.into_inner(), &user)?;
+
+    let shares = list_saved_search_shares(&pool, search.id).map_err(|e| {
+        error!("Database error while loading saved search shares: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(shares))
+}
+
+/// DELETE /api/saved-searches/{id}/shares/{user_id}
+/// Stops sharing a saved search with a user. The owner can remove anyone;
+/// other users can remove a search shared with them from their own list.
+pub async fn unshare_search(
+    pool: web::Data<DbPool>,
+    path: web::Path<(i32, String)>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    let (search_id, recipient_id) = path.into_inner();
+    let search = find_visible_search(&pool, search_id, &user)?;
+
+    if search.owner_id == user.user_id {
+        user.require_scope(scopes::SHARING)?;
+    } else if recipient_id == user.user_id {
+        user.require_scope(scopes::FILES_WRITE)?;
+    } else {
+        warn!(
+            "User {} tried to unshare saved search {} they do not own",
+            user.user_id, search.id
+        );
+        return Err(actix_web::error::ErrorForbidden(
+            "You do not own this saved search",
+        ));
+    }
+
+    let removed = delete_saved_search_share(&pool, search.id, &recipient_id).map_err(|e| {
+        error!("Failed to unshare saved search: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB delete error: {}", e))
+    })?;
+    if removed == 0 {
+        return Err(actix_web::error::ErrorNotFound(
+            "The search is not shared with this user",
+        ));
+    }
+
+    info!(
+        "User {} stopped sharing saved search {} with user {}",
+        user.user_id, search.id, recipient_id
+    );
+
+    Ok(HttpResponse::Ok().json("Share removed"))
+}
+
+/// Checks a saved search's name, query and sort order, and returns them
+/// trimmed. An empty `order_by` means the default order.
+fn validate(
+    name: &str,
+    query: &str,
+    order_by: Option<&str>,
+) -> Result<(String, String, Option<String>), Error> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest("Name must not be empty"));
+    }
+    if name.chars().count() > MAX_NAME_LEN {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Name must be at most {} characters",
+            MAX_NAME_LEN
+        )));
+    }
+
+    let query = query.trim();
+    let parsed = parse_search_query(query)?;
+
+    let order_by = order_by.map(str::trim).filter(|o| !o.is_empty());
+    if let Some(order_by) = order_by {
+        let sort = Sort::parse(order_by).map_err(actix_web::error::ErrorBadRequest)?;
+        if sort.key == SortKey::Relevance && parsed.text.is_none() {
+            return Err(actix_web::error::ErrorBadRequest(
+                "Only searches with free text can be sorted by relevance",
+            ));
+        }
+    }
+
+    Ok((
+        name.to_string(),
+        query.to_string(),
+        order_by.map(str::to_string),
+    ))
+}
+
+/// Maps a failed insert or update, reporting a duplicate name as a conflict.
+fn write_error(e: diesel::result::Error, name: &str) -> Error {
+    match e {
+        diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            actix_web::error::ErrorConflict(format!(
+                "You already have a saved search named '{}'",
+                name
+            ))
+        }
+        e => {
+            error!("Failed to save search: {}", e);
+            actix_web::error::ErrorInternalServerError(format!("DB write error: {}", e))
+        }
+    }
+}
+
+/// Loads a saved search the user owns or that was shared with them. Other
+/// searches are reported as missing.
+fn find_visible_search(
+    pool: &DbPool,
+    search_id: i32,
+    user: &AuthenticatedUser,
+) -> Result<SavedSearch, Error> {
+    let not_found = || actix_web::error::ErrorNotFound("Saved search not found");
+    let db_error = |e: diesel::result::Error| {
+        error!("Database error while loading saved search: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    };
+
+    let search = match find_saved_search_by_id(pool, search_id) {
+        Ok(search) => search,
+        Err(diesel::result::Error::NotFound) => return Err(not_found()),
+        Err(e) => return Err(db_error(e)),
+    };
+
+    if search.owner_id != user.user_id
+        && !is_saved_search_shared_with(pool, search.id, &user.user_id).map_err(db_error)?
+    {
+        return Err(not_found());
+    }
+
+    Ok(search)
+}
+
+/// Loads a saved search the user owns.
+fn find_owned_search(
+    pool: &DbPool,
+    search_id: i32,
+    user: &AuthenticatedUser,
+) -> Result<SavedSearch, Error> {
+    let search = find_visible_search(pool, search_id, user)?;
+
+    if search.owner_id != user.user_id {
+        warn!(
+            "User {} tried to change saved search {} they do not own",
+            user.user_id, search.id
+        );
+        return Err(actix_web::error::ErrorForbidden(
+            "You do not own this saved search",
+        ));
+    }
+
+    Ok(search)
+}
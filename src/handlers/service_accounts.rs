@@ -0,0 +1,228 @@
+use crate::auth::jwt::AuthenticatedUser;
+use crate::auth::scopes::{ADMIN, ALL_SCOPES};
+use crate::auth::service_accounts::decoding_key;
+use crate::database::DbPool;
+use crate::handlers::admin::audit_entry;
+use crate::models::admin_audit_log::{
+    ACTION_ADD_SERVICE_ACCOUNT_KEY, ACTION_CREATE_SERVICE_ACCOUNT,
+    ACTION_REVOKE_SERVICE_ACCOUNT_KEY, TARGET_SERVICE_ACCOUNT,
+};
+use crate::models::service_accounts::{
+    KEY_ALGORITHMS, NewServiceAccountKey, ServiceAccount, ServiceAccountKey,
+};
+use crate::repositories::admin_audit_log::with_audit_log_entry;
+use crate::repositories::service_accounts::{
+    find_service_account_by_id, find_service_account_key, insert_service_account,
+    insert_service_account_key, list_service_account_keys, list_service_accounts,
+    revoke_service_account_key,
+};
+use crate::requests::service_accounts::{AddServiceAccountKey, CreateServiceAccount};
+use actix_web::{Error, HttpResponse, web};
+use log::{error, info, warn};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// POST /api/admin/service-accounts
+/// Creates a service account owned by the calling administrator. It can be
+/// granted any scope except `admin`.
+pub async fn create_service_account(
+    pool: web::Data<DbPool>,
+    admin: AuthenticatedUser,
+    body: web::Json<CreateServiceAccount>,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+    admin.require_session()?;
+    let body = body.into_inner();
+
+    let name = body.name.trim().to_string();
+    if name.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest("Name must not be empty"));
+    }
+
+    if body.scopes.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "At least one scope is required",
+        ));
+    }
+    if let Some(unknown) = body
+        .scopes
+        .iter()
+        .find(|scope| !ALL_SCOPES.contains(&scope.as_str()) || scope.as_str() == ADMIN)
+    {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Scope '{}' cannot be granted to a service account",
+            unknown
+        )));
+    }
+
+    let mut scopes = body.scopes;
+    scopes.sort();
+    scopes.dedup();
+
+    let account = with_audit_log_entry(&pool, |conn| {
+        let account = insert_service_account(conn, &admin.user_id, &name, &scopes)?;
+        let entry = audit_entry(
+            &admin,
+            ACTION_CREATE_SERVICE_ACCOUNT,
+            TARGET_SERVICE_ACCOUNT,
+            account.id.to_string(),
+            serde_json::json!({ "name": account.name, "scopes": account.scopes }),
+        );
+        Ok((account, entry))
+    })
+    .map_err(|e| {
+        error!("Failed to insert service account: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB insert error: {}", e))
+    })?;
+
+    info!(
+        "Admin {} created service account {} (user {})",
+        admin.user_id, account.id, account.user_id
+    );
+
+    Ok(HttpResponse::Created().json(account))
+}
+
+/// GET /api/admin/service-accounts
+/// Lists all service accounts with their keys.
+pub async fn list_all_service_accounts(
+    pool: web::Data<DbPool>,
+    admin: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+
+    let db_error = |e: diesel::result::Error| {
+        error!("Database error while loading service accounts: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    };
+
+    let accounts = list_service_accounts(&pool).map_err(db_error)?;
+    let mut keys: HashMap<i32, Vec<ServiceAccountKey>> = HashMap::new();
+    for key in list_service_account_keys(&pool).map_err(db_error)? {
+        keys.entry(key.service_account_id).or_default().push(key);
+    }
+
+    let accounts: Vec<serde_json::Value> = accounts
+        .into_iter()
+        .map(|account| {
+            let account_keys = keys.remove(&account.id).unwrap_or_default();
+            serde_json::json!({
+                "service_account": account,
+                "keys": account_keys,
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(accounts))
+}
+
+/// POST /api/admin/service-accounts/{id}/keys
+/// Registers a public key. Assertions signed with the matching private key
+/// must carry the returned `key_id` as `kid`.
+pub async fn add_key(
+    pool: web::Data<DbPool>,
+    admin: AuthenticatedUser,
+    account_id: web::Path<i32>,
+    body: web::Json<AddServiceAccountKey>,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+    admin.require_session()?;
+    let account = find_account(&pool, account_id.into_inner())?;
+
+    if !KEY_ALGORITHMS.contains(&body.algorithm.as_str()) {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Unsupported algorithm '{}', expected one of: {}",
+            body.algorithm,
+            KEY_ALGORITHMS.join(", ")
+        )));
+    }
+    decoding_key(&body.algorithm, &body.public_key).map_err(actix_web::error::ErrorBadRequest)?;
+
+    let new_key = NewServiceAccountKey {
+        service_account_id: account.id,
+        key_id: Uuid::new_v4().to_string(),
+        algorithm: body.algorithm.clone(),
+        public_key: body.public_key.trim().to_string(),
+    };
+
+    let key = with_audit_log_entry(&pool, |conn| {
+        let key = insert_service_account_key(conn, &new_key)?;
+        let entry = audit_entry(
+            &admin,
+            ACTION_ADD_SERVICE_ACCOUNT_KEY,
+            TARGET_SERVICE_ACCOUNT,
+            account.id.to_string(),
+            serde_json::json!({ "key_id": key.key_id, "algorithm": key.algorithm }),
+        );
+        Ok((key, entry))
+    })
+    .map_err(|e| {
+        error!("Failed to insert service account key: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB insert error: {}", e))
+    })?;
+
+    info!(
+        "Admin {} added key {} to service account {}",
+        admin.user_id, key.key_id, account.id
+    );
+
+    Ok(HttpResponse::Created().json(key))
+}
+
+/// DELETE /api/admin/service-accounts/{id}/keys/{key_id}
+/// Revokes a key. Tokens already issued stay valid until they expire.
+pub async fn revoke_key(
+    pool: web::Data<DbPool>,
+    admin: AuthenticatedUser,
+    path: web::Path<(i32, String)>,
+) -> Result<HttpResponse, Error> {
+    admin.require_admin()?;
+    admin.require_session()?;
+    let (account_id, kid) = path.into_inner();
+    let account = find_account(&pool, account_id)?;
+
+    let key = match find_service_account_key(&pool, &kid) {
+        Ok(Some(key)) if key.service_account_id == account.id => key,
+        Ok(_) => return Err(actix_web::error::ErrorNotFound("Key not found")),
+        Err(e) => {
+            error!("Database error while loading service account key: {}", e);
+            return Err(actix_web::error::ErrorInternalServerError(format!(
+                "Database error: {}",
+                e
+            )));
+        }
+    };
+    if key.is_revoked() {
+        return Err(actix_web::error::ErrorConflict("Key is already revoked"));
+    }
+
+    let key = with_audit_log_entry(&pool, |conn| {
+        let key = revoke_service_account_key(conn, key.id)?;
+        let entry = audit_entry(
+            &admin,
+            ACTION_REVOKE_SERVICE_ACCOUNT_KEY,
+            TARGET_SERVICE_ACCOUNT,
+            account.id.to_string(),
+            serde_json::json!({ "key_id": key.key_id }),
+        );
+        Ok((key, entry))
+    })
+    .map_err(|e| {
+        error!("Failed to revoke service account key: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+    })?;
+
+    info!(
+        "Admin {} revoked key {} of service account {}",
+        admin.user_id, key.key_id, account.id
+    );
+
+    Ok(HttpResponse::Ok().json(key))
+}
+
+fn find_account(pool: &DbPool, account_id: i32) -> Result<ServiceAccount, Error> {
+    find_service_account_by_id(pool, account_id).map_err(|e| {
+        warn!("Service account {} not found: {}", account_id, e);
+        actix_web::error::ErrorNotFound(format!("Service account not found: {}", e))
+    })
+}
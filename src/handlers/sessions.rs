@@ -0,0 +1,110 @@
+use crate::auth::jwt::AuthenticatedUser;
+use crate::auth::session::session_removal_cookies;
+use crate::database::DbPool;
+use crate::repositories::sessions::{
+    find_session_by_id, list_active_sessions, revoke_session, revoke_user_sessions,
+};
+use actix_web::{Error, HttpResponse, web};
+use log::{error, info, warn};
+use uuid::Uuid;
+
+/// GET /api/me/sessions
+/// Lists the devices the user is signed in on, most recently used first.
+/// The session making the request is marked with `current`.
+pub async fn list_sessions(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+
+    let sessions = list_active_sessions(&pool, &user.user_id, chrono::Utc::now().naive_utc())
+        .map_err(|e| {
+            error!("Database error while loading sessions: {}", e);
+            actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+        })?;
+
+    let sessions: Vec<serde_json::Value> = sessions
+        .into_iter()
+        .map(|session| {
+            let current = Some(session.id) == user.session_id;
+            serde_json::json!({
+                "id": session.id,
+                "user_agent": session.user_agent,
+                "ip_address": session.ip_address,
+                "created_at": session.created_at,
+                "last_seen_at": session.last_seen_at,
+                "expires_at": session.expires_at,
+                "current": current,
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+/// DELETE /api/me/sessions/{id}
+/// Signs out one device. Its tokens are rejected from the next request on.
+/// Revoking the current session also clears the auth cookies.
+pub async fn revoke(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    session_id: web::Path<Uuid>,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+    let session_id = session_id.into_inner();
+
+    let session = find_session_by_id(&pool, session_id).map_err(|e| {
+        error!("Database error while loading session: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    })?;
+
+    match session {
+        Some(session)
+            if session.user_id == user.user_id
+                && session.is_active(chrono::Utc::now().naive_utc()) => {}
+        Some(_) => {
+            warn!(
+                "User {} tried to revoke session {} that is not theirs or not active",
+                user.user_id, session_id
+            );
+            return Err(actix_web::error::ErrorNotFound("Session not found"));
+        }
+        None => return Err(actix_web::error::ErrorNotFound("Session not found")),
+    }
+
+    revoke_session(&pool, session_id).map_err(|e| {
+        error!("Failed to revoke session: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+    })?;
+
+    info!("User {} revoked session {}", user.user_id, session_id);
+
+    let mut response = HttpResponse::Ok().json("Session revoked");
+    if user.session_id == Some(session_id) {
+        for cookie in session_removal_cookies() {
+            response.add_cookie(&cookie)?;
+        }
+    }
+    Ok(response)
+}
+
+/// DELETE /api/me/sessions
+/// Signs out everywhere else: revokes every session except the current one.
+pub async fn revoke_others(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+
+    let revoked = revoke_user_sessions(&pool, &user.user_id, user.session_id).map_err(|e| {
+        error!("Failed to revoke sessions: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+    })?;
+
+    info!(
+        "User {} signed out {} other sessions",
+        user.user_id, revoked
+    );
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "revoked": revoked })))
+}
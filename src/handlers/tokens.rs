@@ -0,0 +1,227 @@
+use crate::auth::jwt::{AuthenticatedUser, PAT_PREFIX};
+use crate::auth::scopes::{ADMIN, ALL_SCOPES};
+use crate::auth::session::{generate_token, hash_token};
+use crate::database::DbPool;
+use crate::models::personal_access_tokens::NewPersonalAccessToken;
+use crate::repositories::personal_access_tokens::{
+    find_personal_access_token_by_id, insert_personal_access_token, list_personal_access_tokens,
+    revoke_personal_access_token,
+};
+use crate::requests::tokens::CreatePersonalAccessToken;
+use actix_web::{Error, HttpResponse, web};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use log::{error, info, warn};
+
+/// Number of leading characters of a token kept to help users recognise it.
+const DISPLAY_PREFIX_LEN: usize = 12;
+
+/// POST /api/tokens
+/// Creates a personal access token. The secret is only returned in this response.
+pub async fn create_token(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    body: web::Json<CreatePersonalAccessToken>,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+    let body = body.into_inner();
+
+    let name = body.name.trim().to_string();
+    if name.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest("Name must not be empty"));
+    }
+
+    let scopes = check_scopes(body.scopes, &user)?;
+    let expires_at = token_expiry(body.expires_in_days, Utc::now())?;
+
+    let secret = format!("{}{}", PAT_PREFIX, generate_token());
+    let new_token = NewPersonalAccessToken {
+        user_id: user.user_id.clone(),
+        name,
+        token_hash: hash_token(&secret),
+        token_prefix: secret.chars().take(DISPLAY_PREFIX_LEN).collect(),
+        scopes,
+        expires_at,
+    };
+
+    let token = insert_personal_access_token(&pool, &new_token).map_err(|e| {
+        error!("Failed to insert personal access token: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB insert error: {}", e))
+    })?;
+
+    info!(
+        "User {} created personal access token {}",
+        user.user_id, token.id
+    );
+
+    Ok(HttpResponse::Created().json(serde_json::json!({
+        "token": secret,
+        "details": token,
+    })))
+}
+
+/// Validates the scopes requested for a new token and returns them sorted
+/// and without duplicates. Only administrators may request the admin scope.
+fn check_scopes(mut scopes: Vec<String>, user: &AuthenticatedUser) -> Result<Vec<String>, Error> {
+    if scopes.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "At least one scope is required",
+        ));
+    }
+    if let Some(unknown) = scopes
+        .iter()
+        .find(|scope| !ALL_SCOPES.contains(&scope.as_str()))
+    {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Unknown scope '{}', expected any of: {}",
+            unknown,
+            ALL_SCOPES.join(", ")
+        )));
+    }
+
+    if scopes.iter().any(|scope| scope == ADMIN) && !user.is_admin {
+        warn!(
+            "User {} requested the admin scope without being an administrator",
+            user.user_id
+        );
+        return Err(actix_web::error::ErrorForbidden(
+            "Only administrators can create tokens with the admin scope",
+        ));
+    }
+
+    scopes.sort();
+    scopes.dedup();
+    Ok(scopes)
+}
+
+/// Expiry of a token created at `now` that lives `expires_in_days`;
+/// `None` means it never expires.
+fn token_expiry(
+    expires_in_days: Option<i64>,
+    now: DateTime<Utc>,
+) -> Result<Option<NaiveDateTime>, Error> {
+    let Some(days) = expires_in_days else {
+        return Ok(None);
+    };
+    if days <= 0 {
+        return Err(actix_web::error::ErrorBadRequest(
+            "expires_in_days must be positive",
+        ));
+    }
+
+    Duration::try_days(days)
+        .and_then(|lifetime| now.checked_add_signed(lifetime))
+        .map(|expires_at| Some(expires_at.naive_utc()))
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("expires_in_days is too large"))
+}
+
+/// GET /api/tokens
+/// Lists the user's personal access tokens without their secrets.
+pub async fn list_tokens(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+
+    let tokens = list_personal_access_tokens(&pool, &user.user_id).map_err(|e| {
+        error!("Database error while loading access tokens: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+/// DELETE /api/tokens/{id}
+/// Revokes a personal access token.
+pub async fn revoke_token(
+    pool: web::Data<DbPool>,
+    token_id: web::Path<i32>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+
+    let token = find_personal_access_token_by_id(&pool, token_id.into_inner()).map_err(|e| {
+        warn!("Access token not found: {}", e);
+        actix_web::error::ErrorNotFound(format!("Access token not found: {}", e))
+    })?;
+
+    if token.user_id != user.user_id {
+        warn!(
+            "User {} tried to revoke someone else's access token {}",
+            user.user_id, token.id
+        );
+        return Err(actix_web::error::ErrorNotFound("Access token not found"));
+    }
+
+    let token = revoke_personal_access_token(&pool, token.id).map_err(|e| {
+        error!("Failed to revoke access token: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+    })?;
+
+    info!("User {} revoked access token {}", user.user_id, token.id);
+
+    Ok(HttpResponse::Ok().json(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::scopes::{FILES_READ, FILES_WRITE};
+
+    fn user(is_admin: bool) -> AuthenticatedUser {
+        AuthenticatedUser {
+            user_id: "42".to_string(),
+            session_id: Some(uuid::Uuid::new_v4()),
+            scopes: None,
+            is_admin,
+        }
+    }
+
+    fn scopes(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn scopes_are_sorted_and_deduplicated() {
+        let checked = check_scopes(
+            scopes(&[FILES_WRITE, FILES_READ, FILES_WRITE]),
+            &user(false),
+        );
+        assert_eq!(checked.unwrap(), scopes(&[FILES_READ, FILES_WRITE]));
+    }
+
+    #[test]
+    fn empty_and_unknown_scopes_are_rejected() {
+        assert!(check_scopes(Vec::new(), &user(false)).is_err());
+        assert!(check_scopes(scopes(&[FILES_READ, "files.everything"]), &user(true)).is_err());
+    }
+
+    #[test]
+    fn admin_scope_requires_an_administrator() {
+        assert!(check_scopes(scopes(&[ADMIN]), &user(false)).is_err());
+        assert_eq!(
+            check_scopes(scopes(&[ADMIN]), &user(true)).unwrap(),
+            scopes(&[ADMIN])
+        );
+    }
+
+    #[test]
+    fn token_expiry_adds_whole_days() {
+        let now = DateTime::from_timestamp(1_750_000_000, 0).unwrap();
+
+        assert_eq!(token_expiry(None, now).unwrap(), None);
+        assert_eq!(
+            token_expiry(Some(30), now).unwrap(),
+            Some((now + Duration::days(30)).naive_utc())
+        );
+    }
+
+    #[test]
+    fn token_expiry_rejects_invalid_lifetimes() {
+        let now = DateTime::from_timestamp(1_750_000_000, 0).unwrap();
+
+        assert!(token_expiry(Some(0), now).is_err());
+        assert!(token_expiry(Some(-1), now).is_err());
+        assert!(token_expiry(Some(i64::MAX), now).is_err());
+        assert!(token_expiry(Some(100_000_000), now).is_err());
+    }
+}
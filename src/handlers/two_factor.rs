@@ -0,0 +1,109 @@
+use crate::auth::jwt::AuthenticatedUser;
+use crate::auth::two_factor::{
+    check_second_factor, finish_enrolment, has_two_factor, regenerate_recovery_codes,
+    start_enrolment,
+};
+use crate::database::DbPool;
+use crate::policy::TwoFactorPolicy;
+use crate::repositories::two_factor::{count_unused_recovery_codes, delete_totp_credential};
+use crate::requests::two_factor::{EnableTwoFactor, SecondFactor};
+use actix_web::{Error, HttpResponse, web};
+use log::{error, info, warn};
+
+/// GET /api/me/2fa
+/// Returns whether two-factor authentication is enabled or required for the user.
+pub async fn get_two_factor_status(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    policy: TwoFactorPolicy,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+
+    let enabled = has_two_factor(&pool, &user.user_id)?;
+    let recovery_codes_remaining =
+        count_unused_recovery_codes(&pool, &user.user_id).map_err(|e| {
+            error!("Database error while counting recovery codes: {}", e);
+            actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+        })?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "enabled": enabled,
+        "required": policy.required,
+        "recovery_codes_remaining": recovery_codes_remaining,
+    })))
+}
+
+/// POST /api/me/2fa/setup
+/// Starts enrolment and returns the secret and `otpauth://` URI to add to an authenticator app.
+pub async fn setup_two_factor(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+    start_enrolment(&pool, &user.user_id)
+}
+
+/// POST /api/me/2fa/enable
+/// Confirms enrolment with a code from the authenticator app.
+/// The recovery codes are only returned in this response.
+pub async fn enable_two_factor(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    body: web::Json<EnableTwoFactor>,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+    let recovery_codes = finish_enrolment(&pool, &user.user_id, &body.code)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "recovery_codes": recovery_codes,
+    })))
+}
+
+/// POST /api/me/2fa/recovery-codes
+/// Replaces the recovery codes after checking a second factor.
+pub async fn regenerate_codes(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    body: web::Json<SecondFactor>,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+    check_second_factor(&pool, &user.user_id, &body)?;
+    let recovery_codes = regenerate_recovery_codes(&pool, &user.user_id)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "recovery_codes": recovery_codes,
+    })))
+}
+
+/// DELETE /api/me/2fa
+/// Turns two-factor authentication off after checking a second factor.
+/// Not allowed when the administrator requires two-factor authentication.
+pub async fn disable_two_factor(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    policy: TwoFactorPolicy,
+    body: web::Json<SecondFactor>,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+
+    if policy.required {
+        warn!(
+            "User {} tried to disable required two-factor authentication",
+            user.user_id
+        );
+        return Err(actix_web::error::ErrorForbidden(
+            "Two-factor authentication is required by the administrator",
+        ));
+    }
+
+    check_second_factor(&pool, &user.user_id, &body)?;
+
+    delete_totp_credential(&pool, &user.user_id).map_err(|e| {
+        error!("Failed to disable two-factor authentication: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("DB delete error: {}", e))
+    })?;
+
+    info!("User {} disabled two-factor authentication", user.user_id);
+
+    Ok(HttpResponse::Ok().json("Two-factor authentication disabled"))
+}
@@ -1,8 +1,95 @@
 use crate::auth::jwt::AuthenticatedUser;
-use actix_web::HttpResponse;
-use log::info;
+use crate::database::DbPool;
+use crate::models::users::User;
+use crate::policy::SharingPolicy;
+use crate::repositories::s3_files::storage_usage_for_user;
+use crate::repositories::users::{find_user_by_id, update_email_notifications};
+use crate::requests::users::UpdateProfile;
+use actix_web::{Error, HttpResponse, web};
+use log::{error, info, warn};
 
 pub async fn protected_route(user: AuthenticatedUser) -> HttpResponse {
     info!("Accessing protected route by user_id: {}", user.user_id);
     HttpResponse::Ok().body(format!("Hello, user_id: {}", user.user_id))
 }
+
+/// GET /api/me
+/// Returns the user's profile with their storage usage and preferences.
+pub async fn get_profile(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+
+    let user = find_user_by_id(&pool, user.id()?).map_err(|e| {
+        warn!("User not found: {}", e);
+        actix_web::error::ErrorNotFound(format!("User not found: {}", e))
+    })?;
+
+    profile_response(&pool, user)
+}
+
+/// PATCH /api/me
+/// Updates the user's preferences and returns the profile.
+pub async fn update_profile(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    body: web::Json<UpdateProfile>,
+) -> Result<HttpResponse, Error> {
+    user.require_session()?;
+    let user_id = user.id()?;
+
+    let updated = match body.email_notifications {
+        Some(enabled) => {
+            info!(
+                "User {} sets email notifications to {}",
+                user.user_id, enabled
+            );
+            update_email_notifications(&pool, user_id, enabled).map_err(|e| {
+                error!("Failed to update preferences: {}", e);
+                actix_web::error::ErrorInternalServerError(format!("DB update error: {}", e))
+            })?
+        }
+        None => find_user_by_id(&pool, user_id).map_err(|e| {
+            warn!("User not found: {}", e);
+            actix_web::error::ErrorNotFound(format!("User not found: {}", e))
+        })?,
+    };
+
+    profile_response(&pool, updated)
+}
+
+/// The profile as returned by `/api/me`.
+fn profile_response(pool: &DbPool, user: User) -> Result<HttpResponse, Error> {
+    let (file_count, storage_bytes) =
+        storage_usage_for_user(pool, &user.id.to_string()).map_err(|e| {
+            error!("Database error while computing storage usage: {}", e);
+            actix_web::error::ErrorInternalServerError(format!("Database error: {}", e))
+        })?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "preferences": {
+            "email_notifications": user.email_notifications,
+        },
+        "user": user,
+        "usage": {
+            "file_count": file_count,
+            "storage_bytes": storage_bytes,
+        },
+    })))
+}
+
+/// GET /api/me/sharing-policy
+/// Returns the organisation sharing policy as it applies to the user.
+pub async fn get_sharing_policy(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    policy: SharingPolicy,
+) -> Result<HttpResponse, Error> {
+    let user = find_user_by_id(&pool, user.id()?).map_err(|e| {
+        warn!("User not found: {}", e);
+        actix_web::error::ErrorNotFound(format!("User not found: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(policy.effective_for(&user)))
+}
@@ -1,4 +1,6 @@
-use crate::auth::google::GoogleOAuthClient;
+use crate::auth::providers::ProviderRegistry;
+use crate::notifications::Notifier;
+use crate::notifications::mailer::SmtpConfig;
 use crate::storage::S3Storage;
 use actix_web::{App, HttpServer, web};
 use aws_config::BehaviorVersion;
@@ -7,13 +9,19 @@ use aws_sdk_s3::Client;
 use aws_sdk_s3::config::Region;
 use std::env;
 
+mod account_jobs;
 mod auth;
 mod database;
+mod events;
 mod handlers;
 mod models;
+mod notifications;
+mod pagination;
+mod policy;
 mod repositories;
 mod requests;
 mod schema;
+mod search;
 mod storage;
 
 #[actix_web::main]
@@ -36,32 +44,309 @@ async fn main() -> std::io::Result<()> {
     let storage_s3 = S3Storage::new(client, baucket_name);
 
     let pool = database::create_pool();
-    let oauth_client = web::Data::new(GoogleOAuthClient::new());
+
+    // Load the JWT keys up front so a bad key configuration fails at startup
+    auth::keys::jwt_keys();
+    let login_providers = web::Data::new(ProviderRegistry::from_env().await);
+
+    policy::store_default_policies(&pool);
+
+    let smtp_config = SmtpConfig::from_env();
+    let notifier = web::Data::new(Notifier::new(smtp_config.is_some()));
+    match smtp_config {
+        Some(config) => {
+            actix_web::rt::spawn(notifications::worker::run_outbox_worker(
+                pool.clone(),
+                config,
+            ));
+        }
+        None => log::warn!("SMTP_HOST is not set, email notifications are disabled"),
+    }
+
+    actix_web::rt::spawn(account_jobs::worker::run_account_job_worker(
+        pool.clone(),
+        storage_s3.clone(),
+    ));
+
+    actix_web::rt::spawn(search::worker::run_index_worker(
+        pool.clone(),
+        storage_s3.clone(),
+    ));
+
+    let event_hub = web::Data::new(events::EventHub::new());
+    actix_web::rt::spawn(events::worker::run_change_poller(
+        pool.clone(),
+        event_hub.get_ref().clone(),
+    ));
 
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(pool.clone()))
             .app_data(web::Data::new(storage_s3.clone()))
-            .app_data(oauth_client.clone())
+            .app_data(login_providers.clone())
+            .app_data(notifier.clone())
+            .app_data(event_hub.clone())
             .service(
                 web::scope("/api/files")
                     .route("", web::get().to(handlers::files::list_files))
                     .route("", web::post().to(handlers::files::upload_file))
+                    .route("/search", web::get().to(handlers::files::search_files))
+                    .route("/suggest", web::get().to(handlers::files::suggest_files))
                     .route("/{id}", web::get().to(handlers::files::download_file))
                     .route("/{id}", web::delete().to(handlers::files::delete_file))
                     .route("/{id}/meta", web::get().to(handlers::files::get_metadata))
-                    .route("/search", web::get().to(handlers::files::search_files)),
+                    .route(
+                        "/{id}/access-requests",
+                        web::post().to(handlers::access_requests::request_access),
+                    ),
+            )
+            .service(
+                web::scope("/api/access-requests")
+                    .route(
+                        "",
+                        web::get().to(handlers::access_requests::list_access_requests),
+                    )
+                    .route(
+                        "/mine",
+                        web::get().to(handlers::access_requests::list_my_access_requests),
+                    )
+                    .route(
+                        "/{id}",
+                        web::get().to(handlers::access_requests::get_access_request),
+                    )
+                    .route(
+                        "/{id}/approve",
+                        web::post().to(handlers::access_requests::approve_request),
+                    )
+                    .route(
+                        "/{id}/deny",
+                        web::post().to(handlers::access_requests::deny_request),
+                    ),
+            )
+            .service(
+                web::scope("/api/file-requests")
+                    .route(
+                        "",
+                        web::post().to(handlers::file_requests::create_file_request),
+                    )
+                    .route(
+                        "",
+                        web::get().to(handlers::file_requests::list_file_requests),
+                    )
+                    .route(
+                        "/{id}",
+                        web::delete().to(handlers::file_requests::revoke_request),
+                    ),
+            )
+            .service(
+                web::scope("/api/changes")
+                    .route("", web::get().to(handlers::changes::list_changes))
+                    .route(
+                        "/startPageToken",
+                        web::get().to(handlers::changes::get_start_page_token),
+                    ),
+            )
+            .service(
+                web::scope("/api/events")
+                    .route("", web::get().to(handlers::events::stream_events))
+                    .route("/ws", web::get().to(handlers::events::stream_events_ws)),
+            )
+            .service(
+                web::scope("/api/saved-searches")
+                    .route(
+                        "",
+                        web::post().to(handlers::saved_searches::create_saved_search),
+                    )
+                    .route(
+                        "",
+                        web::get().to(handlers::saved_searches::list_saved_searches),
+                    )
+                    .route(
+                        "/{id}",
+                        web::get().to(handlers::saved_searches::get_saved_search),
+                    )
+                    .route(
+                        "/{id}",
+                        web::patch().to(handlers::saved_searches::update_search),
+                    )
+                    .route(
+                        "/{id}",
+                        web::delete().to(handlers::saved_searches::delete_search),
+                    )
+                    .route(
+                        "/{id}/files",
+                        web::get().to(handlers::saved_searches::list_saved_search_files),
+                    )
+                    .route(
+                        "/{id}/shares",
+                        web::post().to(handlers::saved_searches::share_search),
+                    )
+                    .route(
+                        "/{id}/shares",
+                        web::get().to(handlers::saved_searches::list_shares),
+                    )
+                    .route(
+                        "/{id}/shares/{user_id}",
+                        web::delete().to(handlers::saved_searches::unshare_search),
+                    ),
+            )
+            .service(
+                web::scope("/api/public/file-requests")
+                    .route(
+                        "/{token}",
+                        web::get().to(handlers::file_requests::get_public_file_request),
+                    )
+                    .route(
+                        "/{token}/upload",
+                        web::post().to(handlers::file_requests::upload_to_file_request),
+                    ),
+            )
+            .service(
+                web::scope("/api/tokens")
+                    .route("", web::post().to(handlers::tokens::create_token))
+                    .route("", web::get().to(handlers::tokens::list_tokens))
+                    .route("/{id}", web::delete().to(handlers::tokens::revoke_token)),
+            )
+            .service(
+                web::scope("/api/me")
+                    .route("", web::get().to(handlers::users::get_profile))
+                    .route("", web::patch().to(handlers::users::update_profile))
+                    .route("", web::delete().to(handlers::account::delete_account))
+                    .route("/export", web::get().to(handlers::account::export_account))
+                    .route(
+                        "/export/{id}",
+                        web::get().to(handlers::account::download_export),
+                    )
+                    .route(
+                        "/identities",
+                        web::get().to(handlers::identities::list_identities),
+                    )
+                    .route(
+                        "/identities/{id}",
+                        web::delete().to(handlers::identities::unlink),
+                    )
+                    .route(
+                        "/sessions",
+                        web::get().to(handlers::sessions::list_sessions),
+                    )
+                    .route(
+                        "/sessions",
+                        web::delete().to(handlers::sessions::revoke_others),
+                    )
+                    .route(
+                        "/sessions/{id}",
+                        web::delete().to(handlers::sessions::revoke),
+                    )
+                    .route(
+                        "/sharing-policy",
+                        web::get().to(handlers::users::get_sharing_policy),
+                    )
+                    .route(
+                        "/2fa",
+                        web::get().to(handlers::two_factor::get_two_factor_status),
+                    )
+                    .route(
+                        "/2fa",
+                        web::delete().to(handlers::two_factor::disable_two_factor),
+                    )
+                    .route(
+                        "/2fa/setup",
+                        web::post().to(handlers::two_factor::setup_two_factor),
+                    )
+                    .route(
+                        "/2fa/enable",
+                        web::post().to(handlers::two_factor::enable_two_factor),
+                    )
+                    .route(
+                        "/2fa/recovery-codes",
+                        web::post().to(handlers::two_factor::regenerate_codes),
+                    ),
+            )
+            .service(
+                web::scope("/api/admin")
+                    .route("/users", web::get().to(handlers::admin::list_all_users))
+                    .route(
+                        "/users/{id}/suspend",
+                        web::post().to(handlers::admin::suspend_user),
+                    )
+                    .route(
+                        "/users/{id}/reactivate",
+                        web::post().to(handlers::admin::reactivate_user),
+                    )
+                    .route(
+                        "/users/{id}/role",
+                        web::put().to(handlers::admin::change_user_role),
+                    )
+                    .route(
+                        "/users/{id}/links",
+                        web::put().to(handlers::admin::set_user_links_disabled),
+                    )
+                    .route(
+                        "/files/{id}",
+                        web::delete().to(handlers::admin::force_delete_file),
+                    )
+                    .route(
+                        "/service-accounts",
+                        web::post().to(handlers::service_accounts::create_service_account),
+                    )
+                    .route(
+                        "/service-accounts",
+                        web::get().to(handlers::service_accounts::list_all_service_accounts),
+                    )
+                    .route(
+                        "/service-accounts/{id}/keys",
+                        web::post().to(handlers::service_accounts::add_key),
+                    )
+                    .route(
+                        "/service-accounts/{id}/keys/{key_id}",
+                        web::delete().to(handlers::service_accounts::revoke_key),
+                    )
+                    .route(
+                        "/sharing-policy",
+                        web::get().to(handlers::admin::get_sharing_policy),
+                    )
+                    .route(
+                        "/sharing-policy",
+                        web::put().to(handlers::admin::update_sharing_policy),
+                    )
+                    .route(
+                        "/two-factor-policy",
+                        web::get().to(handlers::admin::get_two_factor_policy),
+                    )
+                    .route(
+                        "/two-factor-policy",
+                        web::put().to(handlers::admin::update_two_factor_policy),
+                    )
+                    .route("/stats", web::get().to(handlers::admin::get_stats))
+                    .route("/audit-log", web::get().to(handlers::admin::get_audit_log)),
             )
+            .route(
+                "/api/account-jobs/{id}",
+                web::get().to(handlers::account::get_account_job),
+            )
+            .route("/.well-known/jwks.json", web::get().to(auth::keys::jwks))
             .service(
                 web::scope("/auth")
-                    .route("/google", web::get().to(auth::google::google_auth))
+                    .route("/refresh", web::post().to(auth::session::refresh))
+                    .route("/logout", web::post().to(auth::session::logout))
+                    .route("/token", web::post().to(auth::service_accounts::token))
+                    .route("/2fa/setup", web::post().to(auth::two_factor::login_setup))
                     .route(
-                        "/google/callback",
-                        web::get().to(auth::google::google_callback),
+                        "/2fa/verify",
+                        web::post().to(auth::two_factor::login_verify),
                     )
                     .route(
                         "/protected",
                         web::post().to(handlers::users::protected_route),
+                    )
+                    .route("/{provider}", web::get().to(auth::providers::provider_auth))
+                    .route(
+                        "/{provider}/link",
+                        web::get().to(auth::providers::provider_link),
+                    )
+                    .route(
+                        "/{provider}/callback",
+                        web::get().to(auth::providers::provider_callback),
                     ),
             )
     })
@@ -0,0 +1,28 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_APPROVED: &str = "approved";
+pub const STATUS_DENIED: &str = "denied";
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::access_requests)]
+pub struct NewAccessRequest {
+    pub file_id: i32,
+    pub requester_id: String,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::access_requests)]
+pub struct AccessRequest {
+    pub id: i32,
+    pub file_id: i32,
+    pub requester_id: String,
+    pub message: Option<String>,
+    pub status: String,
+    pub role: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub resolved_at: Option<NaiveDateTime>,
+}
@@ -0,0 +1,57 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable, Selectable};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Builds a zip archive of the user's files and data.
+pub const KIND_EXPORT: &str = "export";
+/// Deletes or transfers the user's files and anonymises the account.
+pub const KIND_DELETION: &str = "deletion";
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_RUNNING: &str = "running";
+pub const STATUS_COMPLETED: &str = "completed";
+pub const STATUS_FAILED: &str = "failed";
+/// A completed export whose archive has been removed after the retention period.
+pub const STATUS_EXPIRED: &str = "expired";
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::account_jobs)]
+pub struct NewAccountJob {
+    pub id: Uuid,
+    pub user_id: String,
+    pub kind: String,
+    pub transfer_to: Option<String>,
+}
+
+/// A background export or deletion job. Owner and storage details are not
+/// serialized, as the status of a job can be read by anyone knowing its ID.
+#[derive(Debug, Queryable, Selectable, Serialize)]
+#[diesel(table_name = crate::schema::account_jobs)]
+pub struct AccountJob {
+    pub id: Uuid,
+    #[serde(skip_serializing)]
+    pub user_id: String,
+    pub kind: String,
+    pub status: String,
+    #[serde(skip_serializing)]
+    pub transfer_to: Option<String>,
+    #[serde(skip_serializing)]
+    pub archive_key: Option<String>,
+    pub archive_size: Option<i64>,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub completed_at: Option<NaiveDateTime>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+impl AccountJob {
+    /// Returns true if the archive of a completed export can still be downloaded at `now`.
+    pub fn is_downloadable(&self, now: NaiveDateTime) -> bool {
+        self.kind == KIND_EXPORT
+            && self.status == STATUS_COMPLETED
+            && self.archive_key.is_some()
+            && self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
@@ -0,0 +1,42 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable};
+use serde::Serialize;
+
+pub const ACTION_SUSPEND_USER: &str = "user.suspend";
+pub const ACTION_REACTIVATE_USER: &str = "user.reactivate";
+pub const ACTION_CHANGE_ROLE: &str = "user.change_role";
+pub const ACTION_SET_LINKS_DISABLED: &str = "user.set_links_disabled";
+pub const ACTION_DELETE_FILE: &str = "file.delete";
+pub const ACTION_CREATE_SERVICE_ACCOUNT: &str = "service_account.create";
+pub const ACTION_ADD_SERVICE_ACCOUNT_KEY: &str = "service_account.add_key";
+pub const ACTION_REVOKE_SERVICE_ACCOUNT_KEY: &str = "service_account.revoke_key";
+pub const ACTION_UPDATE_SHARING_POLICY: &str = "sharing_policy.update";
+pub const ACTION_UPDATE_TWO_FACTOR_POLICY: &str = "two_factor_policy.update";
+
+pub const TARGET_USER: &str = "user";
+pub const TARGET_FILE: &str = "file";
+pub const TARGET_SERVICE_ACCOUNT: &str = "service_account";
+/// An instance-wide setting; the target ID is the setting's name.
+pub const TARGET_SETTING: &str = "setting";
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::admin_audit_log)]
+pub struct NewAuditLogEntry {
+    pub admin_id: String,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: String,
+    /// JSON with action-specific details.
+    pub details: Option<String>,
+}
+
+#[derive(Debug, Queryable, Serialize)]
+pub struct AuditLogEntry {
+    pub id: i32,
+    pub admin_id: String,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: String,
+    pub details: Option<String>,
+    pub created_at: NaiveDateTime,
+}
@@ -0,0 +1,123 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The file was uploaded.
+pub const CHANGE_CREATED: &str = "created";
+/// The file's owner or permissions changed.
+pub const CHANGE_UPDATED: &str = "updated";
+/// The user was given access to the file.
+pub const CHANGE_SHARED: &str = "shared";
+/// The file was deleted.
+pub const CHANGE_DELETED: &str = "deleted";
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::file_changes)]
+pub struct NewFileChange {
+    pub user_id: String,
+    pub file_id: i32,
+    pub change_type: &'static str,
+}
+
+impl NewFileChange {
+    pub fn new(user_id: &str, file_id: i32, change_type: &'static str) -> Self {
+        NewFileChange {
+            user_id: user_id.to_string(),
+            file_id,
+            change_type,
+        }
+    }
+}
+
+/// A change to a file, as seen by one user.
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::file_changes)]
+pub struct FileChange {
+    pub id: i64,
+    /// The transaction that recorded the change.
+    #[serde(skip)]
+    pub tx_id: i64,
+    pub user_id: String,
+    pub file_id: i32,
+    pub change_type: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl FileChange {
+    /// Returns true if the user can no longer see the file.
+    pub fn is_removal(&self) -> bool {
+        self.change_type == CHANGE_DELETED
+    }
+
+    pub fn position(&self) -> ChangePosition {
+        ChangePosition {
+            tx_id: self.tx_id,
+            id: self.id,
+        }
+    }
+}
+
+/// A position in the changes feed. Changes are ordered by the transaction
+/// that recorded them and then by ID, so a transaction that commits late
+/// still lands after every change a reader has already seen. Positions are
+/// written as `<tx_id>-<id>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ChangePosition {
+    pub tx_id: i64,
+    pub id: i64,
+}
+
+impl ChangePosition {
+    /// The position right after this one.
+    pub fn next(self) -> Self {
+        ChangePosition {
+            tx_id: self.tx_id,
+            id: self.id + 1,
+        }
+    }
+
+    pub fn parse(token: &str) -> Option<Self> {
+        let (tx_id, id) = token.trim().split_once('-')?;
+        let (tx_id, id) = (tx_id.parse().ok()?, id.parse().ok()?);
+
+        (tx_id >= 0 && id >= 0).then_some(ChangePosition { tx_id, id })
+    }
+}
+
+impl fmt::Display for ChangePosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.tx_id, self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_round_trips() {
+        let position = ChangePosition {
+            tx_id: 7412,
+            id: 1042,
+        };
+        assert_eq!(position.to_string(), "7412-1042");
+        assert_eq!(ChangePosition::parse("7412-1042"), Some(position));
+    }
+
+    #[test]
+    fn invalid_positions_are_rejected() {
+        for token in ["", "abc", "1042", "-5", "1--5", "1-", "-", "1-2-3"] {
+            assert_eq!(ChangePosition::parse(token), None, "{}", token);
+        }
+    }
+
+    #[test]
+    fn positions_order_by_transaction_first() {
+        let early = ChangePosition { tx_id: 10, id: 900 };
+        let late = ChangePosition { tx_id: 11, id: 5 };
+        assert!(early < late);
+        assert!(early < early.next());
+        assert!(early.next() < late);
+    }
+}
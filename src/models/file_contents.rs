@@ -0,0 +1,20 @@
+use crate::models::s3_files::S3File;
+use diesel::{Queryable, Selectable};
+
+/// A file waiting for its contents to be indexed.
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = crate::schema::file_contents)]
+pub struct PendingFileContent {
+    pub file_id: i32,
+    pub attempts: i32,
+}
+
+/// A file matching a search. Searches with free text have a rank and a
+/// `ts_headline` snippet of the contents, which is raw and must go through
+/// `render_highlight`.
+#[derive(Debug)]
+pub struct FileSearchHit {
+    pub file: S3File,
+    pub rank: Option<f32>,
+    pub headline: Option<String>,
+}
@@ -0,0 +1,27 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+/// Read-only access: download and view metadata. Only the owner can change a
+/// file, so this is the only role that can be granted.
+pub const ROLE_READER: &str = "reader";
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::file_permissions)]
+pub struct NewFilePermission {
+    pub file_id: i32,
+    pub user_id: String,
+    pub role: String,
+    pub granted_by: String,
+}
+
+#[derive(Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::file_permissions)]
+pub struct FilePermission {
+    pub id: i32,
+    pub file_id: i32,
+    pub user_id: String,
+    pub role: String,
+    pub granted_by: String,
+    pub created_at: NaiveDateTime,
+}
@@ -0,0 +1,157 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::file_requests)]
+pub struct NewFileRequest {
+    pub token: String,
+    pub owner_id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub max_file_size: Option<i64>,
+    pub allowed_mime_types: Vec<String>,
+    pub max_files: Option<i32>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::file_requests)]
+pub struct FileRequest {
+    pub id: i32,
+    pub token: String,
+    pub owner_id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub max_file_size: Option<i64>,
+    pub allowed_mime_types: Vec<String>,
+    pub max_files: Option<i32>,
+    pub upload_count: i32,
+    pub expires_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl FileRequest {
+    /// Returns true if the request can still accept uploads at `now`,
+    /// ignoring the file count limit.
+    pub fn is_open(&self, now: NaiveDateTime) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+
+    /// The largest upload accepted: the request's own limit, but never more
+    /// than `instance_max`.
+    pub fn upload_limit(&self, instance_max: i64) -> i64 {
+        self.max_file_size
+            .map_or(instance_max, |max| max.min(instance_max))
+    }
+
+    /// Returns true if `mime_type` is allowed. Entries like `image/*` match a
+    /// whole top-level type; an empty list allows everything. Parameters such
+    /// as `; charset=utf-8` are ignored.
+    pub fn accepts_mime_type(&self, mime_type: &str) -> bool {
+        let mime_type = mime_type.split(';').next().unwrap_or("").trim();
+
+        self.allowed_mime_types.is_empty()
+            || self
+                .allowed_mime_types
+                .iter()
+                .any(|allowed| match allowed.strip_suffix("/*") {
+                    Some(prefix) => mime_type
+                        .split_once('/')
+                        .is_some_and(|(top, _)| top.eq_ignore_ascii_case(prefix)),
+                    None => allowed.eq_ignore_ascii_case(mime_type),
+                })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn file_request(allowed_mime_types: &[&str]) -> FileRequest {
+        FileRequest {
+            id: 1,
+            token: "token".to_string(),
+            owner_id: "7".to_string(),
+            title: "Invoices".to_string(),
+            description: None,
+            max_file_size: None,
+            allowed_mime_types: allowed_mime_types.iter().map(|t| t.to_string()).collect(),
+            max_files: None,
+            upload_count: 0,
+            expires_at: None,
+            revoked_at: None,
+            created_at: Utc::now().naive_utc(),
+        }
+    }
+
+    #[test]
+    fn empty_list_accepts_everything() {
+        let request = file_request(&[]);
+
+        assert!(request.accepts_mime_type("application/pdf"));
+        assert!(request.accepts_mime_type("application/octet-stream"));
+    }
+
+    #[test]
+    fn exact_types_match_case_insensitively() {
+        let request = file_request(&["application/pdf"]);
+
+        assert!(request.accepts_mime_type("application/pdf"));
+        assert!(request.accepts_mime_type("Application/PDF"));
+        assert!(!request.accepts_mime_type("application/pdfx"));
+        assert!(!request.accepts_mime_type("image/png"));
+    }
+
+    #[test]
+    fn wildcards_match_the_top_level_type() {
+        let request = file_request(&["image/*"]);
+
+        assert!(request.accepts_mime_type("image/png"));
+        assert!(request.accepts_mime_type("IMAGE/jpeg"));
+        assert!(!request.accepts_mime_type("imagex/png"));
+        assert!(!request.accepts_mime_type("text/plain"));
+        assert!(!request.accepts_mime_type("image"));
+    }
+
+    #[test]
+    fn parameters_are_ignored() {
+        let request = file_request(&["text/plain", "image/*"]);
+
+        assert!(request.accepts_mime_type("text/plain; charset=utf-8"));
+        assert!(request.accepts_mime_type("text/plain;charset=utf-8"));
+        assert!(request.accepts_mime_type("image/svg+xml; charset=utf-8"));
+        assert!(!request.accepts_mime_type("text/html; charset=utf-8"));
+    }
+
+    #[test]
+    fn open_until_revoked_or_expired() {
+        let now = Utc::now().naive_utc();
+        let mut request = file_request(&[]);
+        assert!(request.is_open(now));
+
+        request.expires_at = Some(now + Duration::hours(1));
+        assert!(request.is_open(now));
+
+        request.expires_at = Some(now);
+        assert!(!request.is_open(now));
+
+        request.expires_at = None;
+        request.revoked_at = Some(now - Duration::hours(1));
+        assert!(!request.is_open(now));
+    }
+
+    #[test]
+    fn upload_limit_never_exceeds_the_instance_maximum() {
+        let mut request = file_request(&[]);
+        assert_eq!(request.upload_limit(100), 100);
+
+        request.max_file_size = Some(40);
+        assert_eq!(request.upload_limit(100), 40);
+
+        request.max_file_size = Some(400);
+        assert_eq!(request.upload_limit(100), 100);
+    }
+}
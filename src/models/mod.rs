@@ -1,2 +1,16 @@
+pub mod access_requests;
+pub mod account_jobs;
+pub mod admin_audit_log;
+pub mod email_outbox;
+pub mod file_changes;
+pub mod file_contents;
+pub mod file_permissions;
+pub mod file_requests;
+pub mod personal_access_tokens;
 pub mod s3_files;
+pub mod saved_searches;
+pub mod service_accounts;
+pub mod sessions;
+pub mod two_factor;
+pub mod user_identities;
 pub mod users;
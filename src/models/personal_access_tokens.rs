@@ -0,0 +1,71 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable, Selectable};
+use serde::Serialize;
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::personal_access_tokens)]
+pub struct NewPersonalAccessToken {
+    pub user_id: String,
+    pub name: String,
+    pub token_hash: String,
+    pub token_prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+/// A personal access token as shown to its owner; the secret hash is never loaded.
+#[derive(Debug, Queryable, Selectable, Serialize)]
+#[diesel(table_name = crate::schema::personal_access_tokens)]
+pub struct PersonalAccessToken {
+    pub id: i32,
+    pub user_id: String,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+impl PersonalAccessToken {
+    /// Returns true if the token is neither revoked nor expired at `now`.
+    pub fn is_active(&self, now: NaiveDateTime) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration};
+
+    fn token(
+        expires_at: Option<NaiveDateTime>,
+        revoked_at: Option<NaiveDateTime>,
+    ) -> PersonalAccessToken {
+        PersonalAccessToken {
+            id: 1,
+            user_id: "42".to_string(),
+            name: "ci".to_string(),
+            token_prefix: "gdpat_abcdef".to_string(),
+            scopes: vec!["files.read".to_string()],
+            expires_at,
+            last_used_at: None,
+            created_at: NaiveDateTime::default(),
+            revoked_at,
+        }
+    }
+
+    #[test]
+    fn is_active_until_expiry_or_revocation() {
+        let now = DateTime::from_timestamp(1_750_000_000, 0)
+            .unwrap()
+            .naive_utc();
+
+        assert!(token(None, None).is_active(now));
+        assert!(token(Some(now + Duration::seconds(1)), None).is_active(now));
+        assert!(!token(Some(now), None).is_active(now));
+        assert!(!token(None, Some(now)).is_active(now));
+    }
+}
@@ -12,6 +12,10 @@ pub struct NewS3File {
     pub s3_key: String,
     pub etag: Option<String>,
     pub user_id: String,
+    pub uploader_name: Option<String>,
+    pub uploader_email: Option<String>,
+    pub file_request_id: Option<i32>,
+    pub modified_at: NaiveDateTime,
 }
 
 #[derive(Debug, Queryable, Selectable, Serialize, Deserialize)]
@@ -25,4 +29,17 @@ pub struct S3File {
     pub s3_key: String,
     pub etag: Option<String>,
     pub user_id: String,
+    pub uploader_name: Option<String>,
+    pub uploader_email: Option<String>,
+    pub file_request_id: Option<i32>,
+    /// Last change to the file's metadata; the upload time until then.
+    pub modified_at: NaiveDateTime,
+}
+
+/// Number of files and bytes stored by one owner.
+#[derive(Debug, Queryable, Serialize)]
+pub struct StorageUsage {
+    pub user_id: String,
+    pub file_count: i64,
+    pub total_bytes: i64,
 }
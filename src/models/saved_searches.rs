@@ -0,0 +1,43 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::saved_searches)]
+pub struct NewSavedSearch {
+    pub owner_id: String,
+    pub name: String,
+    pub query: String,
+    pub order_by: Option<String>,
+}
+
+/// A search query saved under a name. Opening it runs the query again, so
+/// its results are always current and limited to what the viewer can access.
+#[derive(Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::saved_searches)]
+pub struct SavedSearch {
+    pub id: i32,
+    pub owner_id: String,
+    pub name: String,
+    pub query: String,
+    pub order_by: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::saved_search_shares)]
+pub struct NewSavedSearchShare {
+    pub saved_search_id: i32,
+    pub user_id: String,
+    pub shared_by: String,
+}
+
+#[derive(Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::saved_search_shares)]
+pub struct SavedSearchShare {
+    pub saved_search_id: i32,
+    pub user_id: String,
+    pub shared_by: String,
+    pub created_at: NaiveDateTime,
+}
@@ -0,0 +1,69 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable, Selectable};
+use serde::Serialize;
+
+/// Signature algorithms a service account key can be registered for.
+pub const KEY_ALGORITHMS: [&str; 3] = ["RS256", "ES256", "EdDSA"];
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::service_accounts)]
+pub struct NewServiceAccount {
+    pub user_id: i32,
+    pub owner_id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+/// A non-human account that signs in with a JWT assertion. It has its own
+/// row in `users` (`user_id`), so the files it uploads are owned by it.
+#[derive(Debug, Queryable, Selectable, Serialize)]
+#[diesel(table_name = crate::schema::service_accounts)]
+pub struct ServiceAccount {
+    pub id: i32,
+    pub user_id: i32,
+    /// Administrator who created the account.
+    pub owner_id: String,
+    pub name: String,
+    /// Scopes its access tokens may be granted.
+    pub scopes: Vec<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::service_account_keys)]
+pub struct NewServiceAccountKey {
+    pub service_account_id: i32,
+    pub key_id: String,
+    pub algorithm: String,
+    pub public_key: String,
+}
+
+/// A public key that verifies the assertions of a service account.
+/// Assertions name it with the `kid` header.
+#[derive(Debug, Queryable, Selectable, Serialize)]
+#[diesel(table_name = crate::schema::service_account_keys)]
+pub struct ServiceAccountKey {
+    pub id: i32,
+    pub service_account_id: i32,
+    pub key_id: String,
+    pub algorithm: String,
+    /// PEM-encoded public key.
+    pub public_key: String,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+impl ServiceAccountKey {
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::service_account_assertions)]
+pub struct NewServiceAccountAssertion {
+    pub service_account_id: i32,
+    pub jti: String,
+    pub expires_at: NaiveDateTime,
+}
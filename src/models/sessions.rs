@@ -0,0 +1,82 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable, Selectable};
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::sessions)]
+pub struct NewSession {
+    pub id: Uuid,
+    pub user_id: String,
+    pub expires_at: NaiveDateTime,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+#[derive(Debug, Queryable, Selectable, Serialize)]
+#[diesel(table_name = crate::schema::sessions)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+    /// Device and address the session was started from.
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    /// Last time one of its tokens was used, updated at most once a minute.
+    pub last_seen_at: NaiveDateTime,
+}
+
+impl Session {
+    /// Returns true if the session is neither revoked nor expired at `now`.
+    pub fn is_active(&self, now: NaiveDateTime) -> bool {
+        self.revoked_at.is_none() && self.expires_at > now
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::refresh_tokens)]
+pub struct NewRefreshToken {
+    pub session_id: Uuid,
+    pub token_hash: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration};
+
+    fn session(expires_at: NaiveDateTime, revoked_at: Option<NaiveDateTime>) -> Session {
+        Session {
+            id: Uuid::new_v4(),
+            user_id: "42".to_string(),
+            created_at: expires_at - Duration::days(30),
+            expires_at,
+            revoked_at,
+            user_agent: None,
+            ip_address: None,
+            last_seen_at: expires_at - Duration::days(30),
+        }
+    }
+
+    #[test]
+    fn is_active_until_expiry() {
+        let now = DateTime::from_timestamp(1_750_000_000, 0)
+            .unwrap()
+            .naive_utc();
+
+        assert!(session(now + Duration::seconds(1), None).is_active(now));
+        assert!(!session(now, None).is_active(now));
+        assert!(!session(now - Duration::seconds(1), None).is_active(now));
+    }
+
+    #[test]
+    fn revoked_sessions_are_inactive() {
+        let now = DateTime::from_timestamp(1_750_000_000, 0)
+            .unwrap()
+            .naive_utc();
+
+        assert!(!session(now + Duration::days(1), Some(now)).is_active(now));
+    }
+}
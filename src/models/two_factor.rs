@@ -0,0 +1,40 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable, Selectable};
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::totp_credentials)]
+pub struct NewTotpCredential {
+    pub user_id: String,
+    pub secret: String,
+}
+
+/// A user's TOTP secret. Enrolment is pending until `enabled_at` is set.
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = crate::schema::totp_credentials)]
+pub struct TotpCredential {
+    /// Base32-encoded shared secret.
+    pub secret: String,
+    pub enabled_at: Option<NaiveDateTime>,
+    /// Last time step a code was accepted for; older codes are rejected as replays.
+    pub last_used_step: Option<i64>,
+    pub locked_until: Option<NaiveDateTime>,
+}
+
+impl TotpCredential {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled_at.is_some()
+    }
+
+    /// Returns true if too many wrong codes were entered and `now` is within the lockout.
+    pub fn is_locked(&self, now: NaiveDateTime) -> bool {
+        self.locked_until
+            .is_some_and(|locked_until| locked_until > now)
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::recovery_codes)]
+pub struct NewRecoveryCode {
+    pub user_id: String,
+    pub code_hash: String,
+}
@@ -1,24 +1,63 @@
 use chrono::NaiveDateTime;
-use diesel::{Insertable, Queryable};
+use diesel::{AsChangeset, Insertable, Queryable};
 use serde::Serialize;
 
+/// Regular user.
+pub const ROLE_USER: &str = "user";
+/// Instance administrator, allowed to use the admin API.
+pub const ROLE_ADMIN: &str = "admin";
+/// Every role that can be assigned to a user.
+pub const USER_ROLES: [&str; 2] = [ROLE_USER, ROLE_ADMIN];
+/// Service account; set when the account is created and never assigned.
+pub const ROLE_SERVICE: &str = "service";
+
 #[derive(Debug, Queryable, Serialize)]
 pub struct User {
     pub id: i32,
-    pub oauth_provider: String,
-    pub oauth_user_id: String,
     pub email: Option<String>,
     pub username: Option<String>,
     pub avatar_url: Option<String>,
     pub created_at: NaiveDateTime,
+    pub email_notifications: bool,
+    pub links_disabled: bool,
+    pub role: String,
+    pub suspended_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+    /// Set when a login provider vouched for `email`.
+    pub email_verified: bool,
+}
+
+impl User {
+    pub fn is_admin(&self) -> bool {
+        self.role == ROLE_ADMIN
+    }
+
+    pub fn is_service_account(&self) -> bool {
+        self.role == ROLE_SERVICE
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended_at.is_some()
+    }
+
+    /// Returns true once the user has asked for their account to be deleted.
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// Returns true if the user can be handed the files of a deleted account.
+    pub fn can_receive_transfer(&self) -> bool {
+        !self.is_deleted() && !self.is_suspended() && !self.is_service_account()
+    }
 }
 
-#[derive(Insertable)]
+/// Profile fields reported by a login provider. When updating a user,
+/// fields the provider did not report are left unchanged.
+#[derive(Insertable, AsChangeset)]
 #[diesel(table_name = crate::schema::users)]
 pub struct NewUser {
-    pub oauth_provider: String,
-    pub oauth_user_id: String,
     pub email: Option<String>,
+    pub email_verified: Option<bool>,
     pub username: Option<String>,
     pub avatar_url: Option<String>,
 }
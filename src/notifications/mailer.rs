@@ -0,0 +1,113 @@
+use crate::models::email_outbox::OutboxEmail;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, Message, Tokio1Executor};
+use std::env;
+
+/// How the connection to the SMTP relay is secured.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SmtpTls {
+    /// Plain connection, e.g. for a local SMTP sink such as MailHog.
+    None,
+    /// Upgrade a plain connection with STARTTLS.
+    StartTls,
+    /// Implicit TLS from the first byte.
+    Tls,
+}
+
+/// SMTP relay settings read from the environment.
+#[derive(Clone, Debug)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: Mailbox,
+}
+
+impl SmtpConfig {
+    /// Reads the relay settings. Returns `None` when `SMTP_HOST` is not set,
+    /// which disables email notifications.
+    pub fn from_env() -> Option<Self> {
+        let host = env::var("SMTP_HOST").ok().filter(|h| !h.is_empty())?;
+
+        let tls = match env::var("SMTP_TLS")
+            .unwrap_or_else(|_| "starttls".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "none" => SmtpTls::None,
+            "tls" => SmtpTls::Tls,
+            "starttls" => SmtpTls::StartTls,
+            other => panic!(
+                "SMTP_TLS must be one of none, starttls, tls (got '{}')",
+                other
+            ),
+        };
+
+        let default_port = match tls {
+            SmtpTls::None => 25,
+            SmtpTls::StartTls => 587,
+            SmtpTls::Tls => 465,
+        };
+
+        let port = env::var("SMTP_PORT")
+            .ok()
+            .map(|p| p.parse().expect("SMTP_PORT must be a valid port"))
+            .unwrap_or(default_port);
+
+        let from = env::var("SMTP_FROM")
+            .expect("SMTP_FROM must be set when SMTP_HOST is set")
+            .parse()
+            .expect("SMTP_FROM must be a valid mailbox");
+
+        Some(SmtpConfig {
+            host,
+            port,
+            tls,
+            username: env::var("SMTP_USERNAME").ok(),
+            password: env::var("SMTP_PASSWORD").ok(),
+            from,
+        })
+    }
+}
+
+/// Builds an SMTP transport for the configured relay.
+pub fn build_transport(
+    config: &SmtpConfig,
+) -> Result<AsyncSmtpTransport<Tokio1Executor>, lettre::transport::smtp::Error> {
+    let builder = match config.tls {
+        SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+        SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?,
+        SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+    }
+    .port(config.port);
+
+    let builder = match (&config.username, &config.password) {
+        (Some(username), Some(password)) => {
+            builder.credentials(Credentials::new(username.clone(), password.clone()))
+        }
+        _ => builder,
+    };
+
+    Ok(builder.build())
+}
+
+/// Builds a multipart (text + HTML) message from an outbox record.
+pub fn build_message(from: &Mailbox, email: &OutboxEmail) -> Result<Message, String> {
+    let to: Mailbox = email
+        .recipient
+        .parse()
+        .map_err(|e| format!("Invalid recipient '{}': {}", email.recipient, e))?;
+
+    Message::builder()
+        .from(from.clone())
+        .to(to)
+        .subject(&email.subject)
+        .multipart(MultiPart::alternative_plain_html(
+            email.body_text.clone(),
+            email.body_html.clone(),
+        ))
+        .map_err(|e| format!("Failed to build message: {}", e))
+}
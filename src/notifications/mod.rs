@@ -0,0 +1,84 @@
+pub mod mailer;
+pub mod templates;
+pub mod worker;
+
+use crate::database::DbPool;
+use crate::models::email_outbox::NewOutboxEmail;
+use crate::repositories::email_outbox::insert_outbox_email;
+use crate::repositories::users::find_user_by_id_str;
+use log::{debug, error, info};
+use std::env;
+use templates::{Notification, render};
+
+/// Queues notification emails in the persistent outbox.
+/// Delivery is done asynchronously by the outbox worker.
+#[derive(Clone)]
+pub struct Notifier {
+    enabled: bool,
+    base_url: String,
+}
+
+impl Notifier {
+    /// Creates a notifier. When `enabled` is false (no SMTP relay configured)
+    /// notifications are dropped instead of queued.
+    pub fn new(enabled: bool) -> Self {
+        Notifier {
+            enabled,
+            base_url: env::var("APP_BASE_URL")
+                .unwrap_or_else(|_| "http://localhost:8080".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+        }
+    }
+
+    /// Queues a notification for a user unless they opted out or have no email.
+    /// Failures are logged and never propagated to the caller.
+    pub fn notify_user(&self, pool: &DbPool, user_id: &str, notification: Notification) {
+        if !self.enabled {
+            debug!("Email notifications disabled, skipping notification");
+            return;
+        }
+
+        let user = match find_user_by_id_str(pool, user_id) {
+            Ok(user) => user,
+            Err(e) => {
+                error!("Failed to load user {} for notification: {}", user_id, e);
+                return;
+            }
+        };
+
+        if !user.email_notifications {
+            debug!("User {} opted out of email notifications", user.id);
+            return;
+        }
+
+        let Some(recipient) = user.email else {
+            debug!(
+                "User {} has no email address, skipping notification",
+                user.id
+            );
+            return;
+        };
+
+        let rendered = render(&notification, &self.base_url);
+        let email = NewOutboxEmail {
+            recipient,
+            subject: rendered.subject,
+            body_text: rendered.text,
+            body_html: rendered.html,
+        };
+
+        match insert_outbox_email(pool, &email) {
+            Ok(queued) => info!("Queued email {} for user {}", queued.id, user.id),
+            Err(e) => error!("Failed to queue email for user {}: {}", user.id, e),
+        }
+    }
+}
+
+/// Returns a human-readable name for a user, falling back to their id.
+pub fn display_name(pool: &DbPool, user_id: &str) -> String {
+    find_user_by_id_str(pool, user_id)
+        .ok()
+        .and_then(|user| user.username.or(user.email))
+        .unwrap_or_else(|| format!("User {}", user_id))
+}
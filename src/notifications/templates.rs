@@ -0,0 +1,340 @@
+/// Events that result in an email to a user.
+pub enum Notification {
+    /// Someone asked the recipient for access to one of their files.
+    AccessRequested {
+        file_id: i32,
+        file_name: String,
+        requester: String,
+        message: Option<String>,
+    },
+    /// The recipient was granted a role on a file.
+    FileShared {
+        file_id: i32,
+        file_name: String,
+        role: String,
+        shared_by: String,
+    },
+    /// The recipient was given a saved search.
+    SavedSearchShared {
+        saved_search_id: i32,
+        name: String,
+        shared_by: String,
+    },
+    /// The recipient's access request was denied.
+    AccessDenied { file_name: String },
+    /// Someone uploaded a file through one of the recipient's file request links.
+    FileRequestUpload {
+        file_id: i32,
+        request_title: String,
+        file_name: String,
+        uploader_name: String,
+        uploader_email: Option<String>,
+    },
+}
+
+/// Subject and bodies of a rendered notification.
+pub struct RenderedEmail {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+/// Renders a notification into plain-text and HTML bodies.
+/// `base_url` is used to build links back to the API.
+pub fn render(notification: &Notification, base_url: &str) -> RenderedEmail {
+    match notification {
+        Notification::AccessRequested {
+            file_id,
+            file_name,
+            requester,
+            message,
+        } => {
+            let link = format!("{}/api/access-requests?status=pending", base_url);
+            let subject = format!("{} requested access to \"{}\"", requester, file_name);
+
+            let mut text = format!(
+                "{} is asking for access to \"{}\" (file {}).\n",
+                requester, file_name, file_id
+            );
+            if let Some(message) = message {
+                text.push_str(&format!("\nMessage:\n{}\n", message));
+            }
+            text.push_str(&format!("\nReview pending requests: {}\n", link));
+
+            let message_html = message
+                .as_ref()
+                .map(|m| format!("<blockquote>{}</blockquote>", escape_html(m)))
+                .unwrap_or_default();
+            let body = format!(
+                "<p><strong>{}</strong> is asking for access to <strong>{}</strong>.</p>{}<p><a href=\"{}\">Review pending requests</a></p>",
+                escape_html(requester),
+                escape_html(file_name),
+                message_html,
+                escape_html(&link)
+            );
+
+            RenderedEmail {
+                html: layout(&subject, &body),
+                subject,
+                text,
+            }
+        }
+        Notification::FileShared {
+            file_id,
+            file_name,
+            role,
+            shared_by,
+        } => {
+            let link = format!("{}/api/files/{}", base_url, file_id);
+            let subject = format!("{} shared \"{}\" with you", shared_by, file_name);
+            let text = format!(
+                "{} gave you {} access to \"{}\".\n\nOpen it: {}\n",
+                shared_by, role, file_name, link
+            );
+            let body = format!(
+                "<p><strong>{}</strong> gave you <strong>{}</strong> access to <strong>{}</strong>.</p><p><a href=\"{}\">Open the file</a></p>",
+                escape_html(shared_by),
+                escape_html(role),
+                escape_html(file_name),
+                escape_html(&link)
+            );
+
+            RenderedEmail {
+                html: layout(&subject, &body),
+                subject,
+                text,
+            }
+        }
+        Notification::SavedSearchShared {
+            saved_search_id,
+            name,
+            shared_by,
+        } => {
+            let link = format!("{}/api/saved-searches/{}/files", base_url, saved_search_id);
+            let subject = format!("{} shared the search \"{}\" with you", shared_by, name);
+            let text = format!(
+                "{} shared the saved search \"{}\" with you. It lists the matching files you have access to.\n\nOpen it: {}\n",
+                shared_by, name, link
+            );
+            let body = format!(
+                "<p><strong>{}</strong> shared the saved search <strong>{}</strong> with you. It lists the matching files you have access to.</p><p><a href=\"{}\">Open the search</a></p>",
+                escape_html(shared_by),
+                escape_html(name),
+                escape_html(&link)
+            );
+
+            RenderedEmail {
+                html: layout(&subject, &body),
+                subject,
+                text,
+            }
+        }
+        Notification::AccessDenied { file_name } => {
+            let subject = format!("Your request for \"{}\" was declined", file_name);
+            let text = format!(
+                "The owner of \"{}\" declined your access request.\n",
+                file_name
+            );
+            let body = format!(
+                "<p>The owner of <strong>{}</strong> declined your access request.</p>",
+                escape_html(file_name)
+            );
+
+            RenderedEmail {
+                html: layout(&subject, &body),
+                subject,
+                text,
+            }
+        }
+        Notification::FileRequestUpload {
+            file_id,
+            request_title,
+            file_name,
+            uploader_name,
+            uploader_email,
+        } => {
+            let link = format!("{}/api/files/{}", base_url, file_id);
+            let uploader = match uploader_email {
+                Some(email) => format!("{} <{}>", uploader_name, email),
+                None => uploader_name.clone(),
+            };
+            let subject = format!("New upload for \"{}\"", request_title);
+            let text = format!(
+                "{} uploaded \"{}\" to your file request \"{}\".\n\nDownload it: {}\n",
+                uploader, file_name, request_title, link
+            );
+            let body = format!(
+                "<p><strong>{}</strong> uploaded <strong>{}</strong> to your file request <strong>{}</strong>.</p><p><a href=\"{}\">Download the file</a></p>",
+                escape_html(&uploader),
+                escape_html(file_name),
+                escape_html(request_title),
+                escape_html(&link)
+            );
+
+            RenderedEmail {
+                html: layout(&subject, &body),
+                subject,
+                text,
+            }
+        }
+    }
+}
+
+/// Wraps a rendered body into the common HTML document.
+fn layout(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{}</title></head><body>{}<hr><p><small>You can turn off these emails with PATCH /api/me.</small></p></body></html>",
+        escape_html(title),
+        body
+    )
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOSTILE: &str = "<script>alert(\"x\")</script> & 'y'";
+    const ESCAPED: &str = "&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt; &amp; &#39;y&#39;";
+
+    fn assert_escaped(email: &RenderedEmail) {
+        assert!(!email.html.contains("<script>"), "{}", email.html);
+        assert!(email.html.contains(ESCAPED), "{}", email.html);
+        // The plain-text body is not HTML and keeps the values as they are
+        assert!(email.text.contains(HOSTILE), "{}", email.text);
+    }
+
+    #[test]
+    fn escape_html_escapes_markup_characters() {
+        assert_eq!(escape_html(HOSTILE), ESCAPED);
+        assert_eq!(escape_html("plain text"), "plain text");
+        assert_eq!(escape_html("привет"), "привет");
+    }
+
+    #[test]
+    fn access_requested_is_escaped() {
+        let email = render(
+            &Notification::AccessRequested {
+                file_id: 1,
+                file_name: HOSTILE.to_string(),
+                requester: "alice".to_string(),
+                message: None,
+            },
+            "https://example.com",
+        );
+        assert_escaped(&email);
+
+        for (requester, message) in [
+            (HOSTILE.to_string(), None),
+            ("alice".to_string(), Some(HOSTILE.to_string())),
+        ] {
+            let email = render(
+                &Notification::AccessRequested {
+                    file_id: 1,
+                    file_name: "report.pdf".to_string(),
+                    requester,
+                    message,
+                },
+                "https://example.com",
+            );
+            assert_escaped(&email);
+        }
+    }
+
+    #[test]
+    fn file_shared_is_escaped() {
+        for (file_name, shared_by) in [(HOSTILE, "alice"), ("report.pdf", HOSTILE)] {
+            let email = render(
+                &Notification::FileShared {
+                    file_id: 1,
+                    file_name: file_name.to_string(),
+                    role: "reader".to_string(),
+                    shared_by: shared_by.to_string(),
+                },
+                "https://example.com",
+            );
+            assert_escaped(&email);
+        }
+    }
+
+    #[test]
+    fn saved_search_shared_is_escaped() {
+        for (name, shared_by) in [(HOSTILE, "alice"), ("Invoices", HOSTILE)] {
+            let email = render(
+                &Notification::SavedSearchShared {
+                    saved_search_id: 1,
+                    name: name.to_string(),
+                    shared_by: shared_by.to_string(),
+                },
+                "https://example.com",
+            );
+            assert_escaped(&email);
+        }
+    }
+
+    #[test]
+    fn access_denied_is_escaped() {
+        let email = render(
+            &Notification::AccessDenied {
+                file_name: HOSTILE.to_string(),
+            },
+            "https://example.com",
+        );
+        assert_escaped(&email);
+    }
+
+    #[test]
+    fn file_request_upload_is_escaped() {
+        for (request_title, file_name, uploader_name, uploader_email) in [
+            (HOSTILE, "a.pdf", "Bob", None),
+            ("Invoices", HOSTILE, "Bob", None),
+            ("Invoices", "a.pdf", HOSTILE, None),
+            ("Invoices", "a.pdf", "Bob", Some(HOSTILE)),
+        ] {
+            let email = render(
+                &Notification::FileRequestUpload {
+                    file_id: 1,
+                    request_title: request_title.to_string(),
+                    file_name: file_name.to_string(),
+                    uploader_name: uploader_name.to_string(),
+                    uploader_email: uploader_email.map(str::to_string),
+                },
+                "https://example.com",
+            );
+            assert_escaped(&email);
+        }
+    }
+
+    #[test]
+    fn links_are_escaped() {
+        let email = render(
+            &Notification::FileShared {
+                file_id: 7,
+                file_name: "report.pdf".to_string(),
+                role: "reader".to_string(),
+                shared_by: "alice".to_string(),
+            },
+            "https://example.com/\"><script>",
+        );
+        assert!(!email.html.contains("<script>"));
+        assert!(
+            email
+                .html
+                .contains("href=\"https://example.com/&quot;&gt;&lt;script&gt;/api/files/7\"")
+        );
+    }
+}
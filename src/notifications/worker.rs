@@ -0,0 +1,83 @@
+use crate::database::DbPool;
+use crate::notifications::mailer::{SmtpConfig, build_message, build_transport};
+use crate::repositories::email_outbox::{claim_due_emails, mark_email_failed, mark_email_sent};
+use chrono::{Duration, Utc};
+use lettre::AsyncTransport;
+use log::{error, info, warn};
+
+/// How often the outbox is polled for due emails.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+/// Maximum number of emails claimed per poll.
+const BATCH_SIZE: i64 = 20;
+/// How long a claimed email is hidden from other workers.
+const CLAIM_LEASE_SECS: i64 = 300;
+/// Delivery attempts before an email is marked as failed.
+const MAX_ATTEMPTS: i32 = 6;
+/// Delay before the first retry; doubled on every further attempt.
+const RETRY_BASE_SECS: i64 = 60;
+
+/// Delivers emails from the outbox until the process exits.
+/// Failed deliveries are retried with exponential backoff.
+pub async fn run_outbox_worker(pool: DbPool, config: SmtpConfig) {
+    let transport = match build_transport(&config) {
+        Ok(transport) => transport,
+        Err(e) => {
+            error!(
+                "Failed to set up SMTP transport, emails will not be sent: {}",
+                e
+            );
+            return;
+        }
+    };
+
+    info!(
+        "Email outbox worker started (relay {}:{}, tls {:?})",
+        config.host, config.port, config.tls
+    );
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let lease_until = (Utc::now() + Duration::seconds(CLAIM_LEASE_SECS)).naive_utc();
+        let emails = match claim_due_emails(&pool, BATCH_SIZE, lease_until) {
+            Ok(emails) => emails,
+            Err(e) => {
+                error!("Failed to load due emails from outbox: {}", e);
+                continue;
+            }
+        };
+
+        for email in emails {
+            let result = match build_message(&config.from, &email) {
+                Ok(message) => transport.send(message).await.map_err(|e| e.to_string()),
+                Err(e) => Err(e),
+            };
+
+            let update = match result {
+                Ok(_) => {
+                    info!("Sent email {} to {}", email.id, email.recipient);
+                    mark_email_sent(&pool, email.id)
+                }
+                Err(e) => {
+                    let attempts = email.attempts + 1;
+                    let retry_at = (attempts < MAX_ATTEMPTS).then(|| {
+                        let delay = RETRY_BASE_SECS * 2_i64.pow(email.attempts as u32);
+                        (Utc::now() + Duration::seconds(delay)).naive_utc()
+                    });
+
+                    warn!(
+                        "Failed to send email {} (attempt {}/{}): {}",
+                        email.id, attempts, MAX_ATTEMPTS, e
+                    );
+                    mark_email_failed(&pool, email.id, &e, retry_at)
+                }
+            };
+
+            if let Err(e) = update {
+                error!("Failed to update outbox email {}: {}", email.id, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,445 @@
+use crate::models::s3_files::S3File;
+use crate::requests::query::ListQuery;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+/// Items per page when no `pageSize` is given.
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+/// Upper bound for `pageSize`.
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
+/// File fields that can be picked with `fields=`.
+pub const FILE_FIELDS: &[&str] = &[
+    "file_id",
+    "name",
+    "mime_type",
+    "size",
+    "created_at",
+    "s3_key",
+    "etag",
+    "user_id",
+    "uploader_name",
+    "uploader_email",
+    "file_request_id",
+    "modified_at",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    Size,
+    Created,
+    Modified,
+    /// Search rank; only for searches with free text, best first.
+    Relevance,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl Sort {
+    pub const NEWEST_FIRST: Sort = Sort {
+        key: SortKey::Created,
+        descending: true,
+    };
+    pub const RELEVANCE: Sort = Sort {
+        key: SortKey::Relevance,
+        descending: true,
+    };
+
+    /// Parses `orderBy`: a key, optionally followed by `asc` or `desc`.
+    pub fn parse(value: &str) -> Result<Sort, String> {
+        let mut parts = value.split_whitespace();
+        let key = match parts.next().map(str::to_lowercase).as_deref() {
+            Some("name") => SortKey::Name,
+            Some("size") => SortKey::Size,
+            Some("created") => SortKey::Created,
+            Some("modified") => SortKey::Modified,
+            Some("relevance") => SortKey::Relevance,
+            _ => {
+                return Err(format!(
+                    "Invalid orderBy '{}', expected name, size, created, modified or relevance",
+                    value
+                ));
+            }
+        };
+        let descending = match parts.next().map(str::to_lowercase).as_deref() {
+            None => key == SortKey::Relevance,
+            Some("asc") => false,
+            Some("desc") => true,
+            Some(other) => {
+                return Err(format!(
+                    "Invalid sort direction '{}', expected asc or desc",
+                    other
+                ));
+            }
+        };
+        if parts.next().is_some() {
+            return Err(format!("Invalid orderBy '{}'", value));
+        }
+        if key == SortKey::Relevance && !descending {
+            return Err("Results can only be sorted by descending relevance".to_string());
+        }
+
+        Ok(Sort { key, descending })
+    }
+
+    fn canonical(&self) -> String {
+        let key = match self.key {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Created => "created",
+            SortKey::Modified => "modified",
+            SortKey::Relevance => "relevance",
+        };
+        let direction = if self.descending { "desc" } else { "asc" };
+        format!("{} {}", key, direction)
+    }
+}
+
+/// The sort value of the last item of a page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cursor {
+    Name(String),
+    Size(i64),
+    Created(NaiveDateTime),
+    Modified(NaiveDateTime),
+    Relevance(f32),
+}
+
+/// Opaque `pageToken`: where the previous page ended, and the order it was
+/// sorted in. The file ID breaks ties between equal sort values.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PageToken {
+    order_by: String,
+    pub after: Cursor,
+    pub file_id: i32,
+}
+
+impl PageToken {
+    /// The token for the page following `file`.
+    pub fn after_file(sort: Sort, file: &S3File, rank: Option<f32>) -> PageToken {
+        let after = match sort.key {
+            SortKey::Name => Cursor::Name(file.name.clone()),
+            SortKey::Size => Cursor::Size(file.size),
+            SortKey::Created => Cursor::Created(file.created_at),
+            SortKey::Modified => Cursor::Modified(file.modified_at),
+            SortKey::Relevance => Cursor::Relevance(rank.unwrap_or_default()),
+        };
+
+        PageToken {
+            order_by: sort.canonical(),
+            after,
+            file_id: file.file_id,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("page tokens always serialize");
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    /// Decodes a token, which must have been issued for the same sort order.
+    pub fn decode(token: &str, sort: Sort) -> Result<PageToken, String> {
+        let token: PageToken = URL_SAFE_NO_PAD
+            .decode(token)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| "Invalid pageToken".to_string())?;
+
+        let valid = token.order_by == sort.canonical()
+            && matches!(
+                (&token.after, sort.key),
+                (Cursor::Name(_), SortKey::Name)
+                    | (Cursor::Size(_), SortKey::Size)
+                    | (Cursor::Created(_), SortKey::Created)
+                    | (Cursor::Modified(_), SortKey::Modified)
+                    | (Cursor::Relevance(_), SortKey::Relevance)
+            );
+        if !valid {
+            return Err("pageToken was issued for a different orderBy".to_string());
+        }
+
+        Ok(token)
+    }
+}
+
+/// A validated page request.
+#[derive(Debug)]
+pub struct Page {
+    pub size: i64,
+    pub sort: Sort,
+    pub after: Option<PageToken>,
+    pub fields: Option<Vec<String>>,
+    pub include_total: bool,
+}
+
+impl Page {
+    pub fn from_query(query: &ListQuery, default_sort: Sort) -> Result<Page, String> {
+        let size = query.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&size) {
+            return Err(format!("pageSize must be between 1 and {}", MAX_PAGE_SIZE));
+        }
+
+        let sort = match query.order_by.as_deref() {
+            Some(order_by) => Sort::parse(order_by)?,
+            None => default_sort,
+        };
+        let after = query
+            .page_token
+            .as_deref()
+            .filter(|token| !token.is_empty())
+            .map(|token| PageToken::decode(token, sort))
+            .transpose()?;
+
+        let fields = match query.fields.as_deref() {
+            Some(fields) => {
+                let fields: Vec<String> = fields
+                    .split(',')
+                    .map(|f| f.trim().to_string())
+                    .filter(|f| !f.is_empty())
+                    .collect();
+                if let Some(unknown) = fields.iter().find(|f| !FILE_FIELDS.contains(&f.as_str())) {
+                    return Err(format!(
+                        "Unknown field '{}', expected any of: {}",
+                        unknown,
+                        FILE_FIELDS.join(", ")
+                    ));
+                }
+                Some(fields)
+            }
+            None => None,
+        };
+
+        Ok(Page {
+            size,
+            sort,
+            after,
+            fields,
+            include_total: query.include_total.unwrap_or(false),
+        })
+    }
+
+    /// Serializes a file with only the requested fields.
+    pub fn project(&self, file: &S3File) -> serde_json::Value {
+        let mut value = serde_json::to_value(file).expect("files always serialize");
+        if let (Some(fields), Some(object)) = (&self.fields, value.as_object_mut()) {
+            object.retain(|key, _| fields.contains(key));
+        }
+        value
+    }
+
+    /// Drops the extra item fetched past the end of the page and returns the
+    /// token for the next page, if there is one. Pages are loaded with
+    /// `size + 1` items so the last page needs no extra query.
+    pub fn next_token<T>(
+        &self,
+        items: &mut Vec<T>,
+        position: impl Fn(&T) -> (&S3File, Option<f32>),
+    ) -> Option<String> {
+        if items.len() as i64 <= self.size {
+            return None;
+        }
+
+        items.truncate(self.size as usize);
+        items.last().map(|item| {
+            let (file, rank) = position(item);
+            PageToken::after_file(self.sort, file, rank).encode()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn file(file_id: i32, name: &str) -> S3File {
+        let created_at = NaiveDate::from_ymd_opt(2025, 10, 8)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        S3File {
+            name: name.to_string(),
+            mime_type: "text/plain".to_string(),
+            size: 42,
+            created_at,
+            file_id,
+            s3_key: format!("uploads/{}", file_id),
+            etag: None,
+            user_id: "1".to_string(),
+            uploader_name: None,
+            uploader_email: None,
+            file_request_id: None,
+            modified_at: created_at + chrono::Duration::days(1),
+        }
+    }
+
+    fn query() -> ListQuery {
+        ListQuery {
+            page_size: None,
+            page_token: None,
+            order_by: None,
+            fields: None,
+            include_total: None,
+        }
+    }
+
+    fn sort(order_by: &str) -> Sort {
+        Sort::parse(order_by).unwrap()
+    }
+
+    #[test]
+    fn token_round_trips() {
+        let token = PageToken::after_file(sort("name"), &file(7, "b.txt"), None).encode();
+        let decoded = PageToken::decode(&token, sort("name asc")).unwrap();
+
+        assert_eq!(decoded.file_id, 7);
+        assert!(matches!(decoded.after, Cursor::Name(name) if name == "b.txt"));
+    }
+
+    #[test]
+    fn modified_order_uses_the_modification_time() {
+        let file = file(7, "b.txt");
+        let token = PageToken::after_file(sort("modified desc"), &file, None).encode();
+        let decoded = PageToken::decode(&token, sort("MODIFIED desc")).unwrap();
+
+        assert!(matches!(decoded.after, Cursor::Modified(at) if at == file.modified_at));
+        assert!(PageToken::decode(&token, sort("created desc")).is_err());
+    }
+
+    #[test]
+    fn token_is_rejected_under_a_different_order_by() {
+        let token = PageToken::after_file(sort("name"), &file(7, "b.txt"), None).encode();
+
+        assert!(PageToken::decode(&token, sort("name desc")).is_err());
+        assert!(PageToken::decode(&token, sort("size")).is_err());
+        assert!(PageToken::decode(&token, Sort::NEWEST_FIRST).is_err());
+    }
+
+    #[test]
+    fn token_with_a_mismatched_cursor_is_rejected() {
+        // A hand-made token claiming the name order but carrying a size
+        let token = PageToken {
+            order_by: sort("name").canonical(),
+            after: Cursor::Size(10),
+            file_id: 7,
+        }
+        .encode();
+
+        assert!(PageToken::decode(&token, sort("name")).is_err());
+    }
+
+    #[test]
+    fn garbage_token_is_rejected() {
+        assert!(PageToken::decode("not a token", Sort::NEWEST_FIRST).is_err());
+        assert!(PageToken::decode(&URL_SAFE_NO_PAD.encode("{}"), Sort::NEWEST_FIRST).is_err());
+    }
+
+    #[test]
+    fn page_size_bounds() {
+        let page = Page::from_query(&query(), Sort::NEWEST_FIRST).unwrap();
+        assert_eq!(page.size, DEFAULT_PAGE_SIZE);
+
+        for size in [1, MAX_PAGE_SIZE] {
+            let page_query = ListQuery {
+                page_size: Some(size),
+                ..query()
+            };
+            assert_eq!(
+                Page::from_query(&page_query, Sort::NEWEST_FIRST)
+                    .unwrap()
+                    .size,
+                size
+            );
+        }
+        for size in [0, -1, MAX_PAGE_SIZE + 1] {
+            let page_query = ListQuery {
+                page_size: Some(size),
+                ..query()
+            };
+            assert!(Page::from_query(&page_query, Sort::NEWEST_FIRST).is_err());
+        }
+    }
+
+    #[test]
+    fn page_token_must_match_the_requested_order() {
+        let token = PageToken::after_file(Sort::NEWEST_FIRST, &file(7, "b.txt"), None).encode();
+        let page_query = ListQuery {
+            page_token: Some(token),
+            order_by: Some("name".to_string()),
+            ..query()
+        };
+
+        assert!(Page::from_query(&page_query, Sort::NEWEST_FIRST).is_err());
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let page_query = ListQuery {
+            fields: Some("name,password".to_string()),
+            ..query()
+        };
+        let error = Page::from_query(&page_query, Sort::NEWEST_FIRST).unwrap_err();
+
+        assert!(error.contains("'password'"));
+    }
+
+    #[test]
+    fn projection_keeps_only_requested_fields() {
+        let page_query = ListQuery {
+            fields: Some(" file_id , name ,".to_string()),
+            ..query()
+        };
+        let page = Page::from_query(&page_query, Sort::NEWEST_FIRST).unwrap();
+
+        assert_eq!(
+            page.project(&file(7, "b.txt")),
+            serde_json::json!({ "file_id": 7, "name": "b.txt" })
+        );
+    }
+
+    #[test]
+    fn invalid_order_by() {
+        assert!(Sort::parse("owner").is_err());
+        assert!(Sort::parse("name sideways").is_err());
+        assert!(Sort::parse("name asc extra").is_err());
+        assert!(Sort::parse("relevance asc").is_err());
+        assert_eq!(
+            sort("Size DESC"),
+            Sort {
+                key: SortKey::Size,
+                descending: true,
+            }
+        );
+    }
+
+    #[test]
+    fn next_token_only_when_there_are_more_items() {
+        let page_query = ListQuery {
+            page_size: Some(2),
+            ..query()
+        };
+        let page = Page::from_query(&page_query, Sort::NEWEST_FIRST).unwrap();
+
+        let mut items = vec![file(3, "c"), file(2, "b")];
+        assert!(page.next_token(&mut items, |f| (f, None)).is_none());
+        assert_eq!(items.len(), 2);
+
+        let mut items = vec![file(3, "c"), file(2, "b"), file(1, "a")];
+        let token = page.next_token(&mut items, |f| (f, None)).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(
+            PageToken::decode(&token, Sort::NEWEST_FIRST)
+                .unwrap()
+                .file_id,
+            2
+        );
+    }
+}
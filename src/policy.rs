@@ -0,0 +1,500 @@
+use crate::database::DbPool;
+use crate::models::users::User;
+use crate::repositories::instance_settings::{find_setting, insert_default_setting};
+use actix_web::{Error, HttpRequest, web};
+use chrono::{Duration, NaiveDateTime, Utc};
+use futures_util::future::{Ready, ready};
+use log::error;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::env;
+
+/// Organisation-wide sharing rules, evaluated on every share and link
+/// creation. Stored in the database and changed by administrators; the
+/// environment only provides the initial values.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SharingPolicy {
+    /// Email domains files may be shared with. Empty means any domain.
+    pub allowed_domains: Vec<String>,
+    /// Whether anonymous (no account) links may be created at all.
+    pub allow_anonymous_links: bool,
+    /// Upper bound for the lifetime of a link, in hours.
+    pub max_link_lifetime_hours: Option<i64>,
+    /// Whether users outside the allowed domains may share files further.
+    pub allow_external_reshare: bool,
+}
+
+/// The sharing policy as it applies to a particular user.
+#[derive(Debug, Serialize)]
+pub struct EffectiveSharingPolicy {
+    pub allowed_domains: Vec<String>,
+    pub can_share: bool,
+    pub can_create_links: bool,
+    pub max_link_lifetime_hours: Option<i64>,
+}
+
+impl SharingPolicy {
+    /// Name the policy is stored under in `instance_settings`.
+    pub const SETTING: &'static str = "sharing_policy";
+
+    pub fn from_env() -> Self {
+        let allowed_domains = normalize_domains(
+            env::var("SHARING_ALLOWED_DOMAINS")
+                .unwrap_or_default()
+                .split(','),
+        );
+
+        let max_link_lifetime_hours = env::var("SHARING_MAX_LINK_LIFETIME_HOURS")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(|v| {
+                parse_link_lifetime(&v).unwrap_or_else(|| {
+                    panic!(
+                        "SHARING_MAX_LINK_LIFETIME_HOURS must be a positive number of hours, got '{}'",
+                        v
+                    )
+                })
+            });
+
+        SharingPolicy {
+            allowed_domains,
+            allow_anonymous_links: env_flag("SHARING_ALLOW_ANONYMOUS_LINKS", true),
+            max_link_lifetime_hours,
+            allow_external_reshare: env_flag("SHARING_ALLOW_EXTERNAL_RESHARE", true),
+        }
+    }
+
+    /// Loads the stored policy.
+    pub fn load(pool: &DbPool) -> Result<Self, diesel::result::Error> {
+        load_setting(pool, Self::SETTING)
+    }
+
+    /// Normalises a policy submitted by an administrator: domains are
+    /// lowercased and stripped of a leading `@`. Returns the reason when the
+    /// policy is invalid.
+    pub fn validated(self) -> Result<Self, String> {
+        if let Some(hours) = self.max_link_lifetime_hours
+            && !is_valid_link_lifetime(hours)
+        {
+            return Err(format!(
+                "max_link_lifetime_hours must be a positive number of hours, got {}",
+                hours
+            ));
+        }
+
+        Ok(SharingPolicy {
+            allowed_domains: normalize_domains(self.allowed_domains.iter().map(String::as_str)),
+            ..self
+        })
+    }
+
+    /// Returns true if the email belongs to one of the allowed domains,
+    /// or if no domain restriction is configured.
+    pub fn is_internal(&self, email: Option<&str>) -> bool {
+        if self.allowed_domains.is_empty() {
+            return true;
+        }
+
+        email
+            .and_then(|e| e.rsplit_once('@'))
+            .map(|(_, domain)| domain.to_lowercase())
+            .is_some_and(|domain| self.allowed_domains.contains(&domain))
+    }
+
+    /// Like [`is_internal`](Self::is_internal), but only trusts an email the
+    /// identity provider has verified. Legacy and unverified addresses never
+    /// count as internal while a domain restriction is configured.
+    pub fn is_internal_user(&self, user: &User) -> bool {
+        let email = user.email.as_deref().filter(|_| user.email_verified);
+        self.is_internal(email)
+    }
+
+    /// Checks that `sharer` may give `recipient` access to a file.
+    /// Returns the reason when the share is not allowed.
+    pub fn check_share(&self, sharer: &User, recipient: &User) -> Result<(), String> {
+        if !self.allow_external_reshare && !self.is_internal_user(sharer) {
+            return Err("Sharing policy does not allow external users to share files".to_string());
+        }
+
+        if !self.is_internal_user(recipient) {
+            return Err(format!(
+                "Sharing policy only allows sharing with addresses in: {}",
+                self.allowed_domains.join(", ")
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `creator` may create an anonymous link expiring at `expires_at`.
+    /// Returns the reason when the link is not allowed.
+    pub fn check_link(
+        &self,
+        creator: &User,
+        expires_at: Option<NaiveDateTime>,
+    ) -> Result<(), String> {
+        let effective = self.effective_for(creator);
+        if !effective.can_create_links {
+            return Err("Sharing policy does not allow you to create links".to_string());
+        }
+
+        if let Some(max_hours) = self.max_link_lifetime_hours {
+            // A cap too far in the future to represent allows any expiry date
+            let latest = Duration::try_hours(max_hours)
+                .and_then(|lifetime| Utc::now().naive_utc().checked_add_signed(lifetime));
+            if expires_at.is_none_or(|expires_at| latest.is_some_and(|latest| expires_at > latest))
+            {
+                return Err(format!(
+                    "Sharing policy requires links to expire within {} hours",
+                    max_hours
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the policy as it applies to `user`.
+    pub fn effective_for(&self, user: &User) -> EffectiveSharingPolicy {
+        let can_share = self.allow_external_reshare || self.is_internal_user(user);
+
+        EffectiveSharingPolicy {
+            allowed_domains: self.allowed_domains.clone(),
+            can_share,
+            can_create_links: can_share && self.allow_anonymous_links && !user.links_disabled,
+            max_link_lifetime_hours: self.max_link_lifetime_hours,
+        }
+    }
+}
+
+/// Loads the current sharing policy for a handler.
+impl actix_web::FromRequest for SharingPolicy {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        ready(load_for_request(req, Self::SETTING))
+    }
+}
+
+/// Stores the policies from the environment unless an administrator has
+/// already set them. Panics if they cannot be stored, as the server cannot
+/// enforce a policy it does not have.
+pub fn store_default_policies(pool: &DbPool) {
+    insert_default_setting(pool, SharingPolicy::SETTING, &SharingPolicy::from_env())
+        .unwrap_or_else(|e| panic!("Failed to store the default sharing policy: {}", e));
+    insert_default_setting(pool, TwoFactorPolicy::SETTING, &TwoFactorPolicy::from_env())
+        .unwrap_or_else(|e| panic!("Failed to store the default two-factor policy: {}", e));
+}
+
+fn load_setting<T: DeserializeOwned>(
+    pool: &DbPool,
+    setting_name: &str,
+) -> Result<T, diesel::result::Error> {
+    find_setting(pool, setting_name)?.ok_or(diesel::result::Error::NotFound)
+}
+
+fn load_for_request<T: DeserializeOwned>(
+    req: &HttpRequest,
+    setting_name: &str,
+) -> Result<T, Error> {
+    let Some(pool) = req.app_data::<web::Data<DbPool>>() else {
+        error!("Database pool is not configured");
+        return Err(actix_web::error::ErrorInternalServerError(
+            "Server misconfiguration",
+        ));
+    };
+
+    load_setting(pool, setting_name).map_err(|e| {
+        error!("Failed to load {}: {}", setting_name, e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })
+}
+
+/// Two-factor authentication rules. Stored in the database next to the
+/// sharing policy; `REQUIRE_TWO_FACTOR` only provides the initial value.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TwoFactorPolicy {
+    /// Whether every user must sign in with a second factor. Users who have
+    /// not enrolled yet are asked to do so during their next login.
+    pub required: bool,
+}
+
+impl TwoFactorPolicy {
+    /// Name the policy is stored under in `instance_settings`.
+    pub const SETTING: &'static str = "two_factor_policy";
+
+    pub fn from_env() -> Self {
+        TwoFactorPolicy {
+            required: env_flag("REQUIRE_TWO_FACTOR", false),
+        }
+    }
+}
+
+/// Loads the current two-factor policy for a handler.
+impl actix_web::FromRequest for TwoFactorPolicy {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        ready(load_for_request(req, Self::SETTING))
+    }
+}
+
+/// Reads a boolean setting. Panics on values that are not clearly true or
+/// false, so a typo does not silently change the policy.
+fn env_flag(name: &str, default: bool) -> bool {
+    match env::var(name) {
+        Ok(value) if value.trim().is_empty() => default,
+        Ok(value) => parse_flag(&value)
+            .unwrap_or_else(|| panic!("{} must be true or false, got '{}'", name, value)),
+        Err(_) => default,
+    }
+}
+
+/// Trims and lowercases email domains, dropping a leading `@` and empty
+/// entries.
+fn normalize_domains<'a>(domains: impl Iterator<Item = &'a str>) -> Vec<String> {
+    domains
+        .map(|d| d.trim().trim_start_matches('@').to_lowercase())
+        .filter(|d| !d.is_empty())
+        .collect()
+}
+
+/// Parses a maximum link lifetime: a positive number of hours that fits in a
+/// `Duration`.
+fn parse_link_lifetime(value: &str) -> Option<i64> {
+    value
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .filter(|hours| is_valid_link_lifetime(*hours))
+}
+
+fn is_valid_link_lifetime(hours: i64) -> bool {
+    hours > 0 && Duration::try_hours(hours).is_some()
+}
+
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowing(domains: &[&str]) -> SharingPolicy {
+        SharingPolicy {
+            allowed_domains: domains.iter().map(|d| d.to_string()).collect(),
+            allow_anonymous_links: true,
+            max_link_lifetime_hours: None,
+            allow_external_reshare: true,
+        }
+    }
+
+    fn user(email: Option<&str>) -> User {
+        User {
+            id: 1,
+            email: email.map(str::to_string),
+            username: None,
+            avatar_url: None,
+            created_at: Utc::now().naive_utc(),
+            email_notifications: true,
+            links_disabled: false,
+            role: crate::models::users::ROLE_USER.to_string(),
+            suspended_at: None,
+            deleted_at: None,
+            email_verified: true,
+        }
+    }
+
+    #[test]
+    fn no_allowlist_allows_everyone() {
+        let policy = allowing(&[]);
+        assert!(policy.is_internal(Some("alice@anywhere.org")));
+        assert!(policy.is_internal(None));
+    }
+
+    #[test]
+    fn allowlist_matches_the_domain_ignoring_case() {
+        let policy = allowing(&["example.com"]);
+        assert!(policy.is_internal(Some("alice@example.com")));
+        assert!(policy.is_internal(Some("Alice@EXAMPLE.com")));
+        assert!(!policy.is_internal(Some("alice@other.org")));
+        assert!(!policy.is_internal(Some("not an address")));
+        assert!(!policy.is_internal(None));
+    }
+
+    #[test]
+    fn allowlist_matches_whole_domains_only() {
+        let policy = allowing(&["example.com"]);
+        // Subdomains have to be listed themselves
+        assert!(!policy.is_internal(Some("bob@eu.example.com")));
+        assert!(!policy.is_internal(Some("eve@evilexample.com")));
+        assert!(!policy.is_internal(Some("eve@example.com.evil.org")));
+        // Only the part after the last `@` is the domain
+        assert!(!policy.is_internal(Some("alice@example.com@evil.org")));
+    }
+
+    #[test]
+    fn share_requires_an_internal_recipient() {
+        let policy = allowing(&["example.com"]);
+        let sharer = user(Some("alice@example.com"));
+
+        assert!(
+            policy
+                .check_share(&sharer, &user(Some("bob@example.com")))
+                .is_ok()
+        );
+        assert!(
+            policy
+                .check_share(&sharer, &user(Some("mallory@other.org")))
+                .is_err()
+        );
+        assert!(policy.check_share(&sharer, &user(None)).is_err());
+    }
+
+    #[test]
+    fn unverified_emails_are_not_internal() {
+        let policy = SharingPolicy {
+            allow_external_reshare: false,
+            ..allowing(&["example.com"])
+        };
+        let verified = user(Some("alice@example.com"));
+        let unverified = User {
+            email_verified: false,
+            ..user(Some("bob@example.com"))
+        };
+
+        assert!(!policy.is_internal_user(&unverified));
+        assert!(policy.check_share(&verified, &unverified).is_err());
+        assert!(policy.check_share(&unverified, &verified).is_err());
+        assert!(!policy.effective_for(&unverified).can_share);
+        // Without a domain restriction verification does not matter
+        assert!(allowing(&[]).is_internal_user(&unverified));
+    }
+
+    #[test]
+    fn external_users_cannot_reshare_when_forbidden() {
+        let policy = SharingPolicy {
+            allow_external_reshare: false,
+            ..allowing(&["example.com"])
+        };
+        let recipient = user(Some("bob@example.com"));
+
+        assert!(
+            policy
+                .check_share(&user(Some("guest@other.org")), &recipient)
+                .is_err()
+        );
+        assert!(
+            policy
+                .check_share(&user(Some("alice@example.com")), &recipient)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn link_lifetime_is_capped() {
+        let policy = SharingPolicy {
+            max_link_lifetime_hours: Some(24),
+            ..allowing(&[])
+        };
+        let creator = user(Some("alice@example.com"));
+        let now = Utc::now().naive_utc();
+
+        assert!(
+            policy
+                .check_link(&creator, Some(now + Duration::hours(23)))
+                .is_ok()
+        );
+        assert!(
+            policy
+                .check_link(&creator, Some(now + Duration::hours(25)))
+                .is_err()
+        );
+        // A link that never expires exceeds any cap
+        assert!(policy.check_link(&creator, None).is_err());
+        assert!(allowing(&[]).check_link(&creator, None).is_ok());
+    }
+
+    #[test]
+    fn huge_link_lifetime_caps_do_not_overflow() {
+        let creator = user(Some("alice@example.com"));
+        let far_future = Utc::now().naive_utc() + Duration::days(365 * 1000);
+
+        for max_hours in [i64::MAX / 3600, i64::MAX] {
+            let policy = SharingPolicy {
+                max_link_lifetime_hours: Some(max_hours),
+                ..allowing(&[])
+            };
+            assert!(policy.check_link(&creator, Some(far_future)).is_ok());
+            assert!(policy.check_link(&creator, None).is_err());
+        }
+    }
+
+    #[test]
+    fn link_lifetime_setting_must_be_positive_and_representable() {
+        assert_eq!(parse_link_lifetime("168"), Some(168));
+        assert_eq!(parse_link_lifetime(" 1 "), Some(1));
+        for value in ["0", "-5", "abc", "", "9223372036854775807"] {
+            assert_eq!(parse_link_lifetime(value), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn submitted_policies_are_normalised_and_checked() {
+        let policy = SharingPolicy {
+            max_link_lifetime_hours: Some(24),
+            ..allowing(&[" @OurCompany.com", "", "partner.org"])
+        };
+        assert_eq!(
+            policy.validated().unwrap().allowed_domains,
+            ["ourcompany.com", "partner.org"]
+        );
+
+        for hours in [0, -5, i64::MAX] {
+            let policy = SharingPolicy {
+                max_link_lifetime_hours: Some(hours),
+                ..allowing(&[])
+            };
+            assert!(policy.validated().is_err(), "{}", hours);
+        }
+    }
+
+    #[test]
+    fn links_can_be_disabled() {
+        let creator = User {
+            links_disabled: true,
+            ..user(Some("alice@example.com"))
+        };
+        assert!(allowing(&[]).check_link(&creator, None).is_err());
+
+        let no_links = SharingPolicy {
+            allow_anonymous_links: false,
+            ..allowing(&[])
+        };
+        assert!(
+            no_links
+                .check_link(&user(Some("alice@example.com")), None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn flags_must_be_recognised() {
+        for value in ["1", "true", "TRUE", " yes ", "on"] {
+            assert_eq!(parse_flag(value), Some(true), "{}", value);
+        }
+        for value in ["0", "false", "No", "off"] {
+            assert_eq!(parse_flag(value), Some(false), "{}", value);
+        }
+        for value in ["flase", "2", "enabled", ""] {
+            assert_eq!(parse_flag(value), None, "{}", value);
+        }
+    }
+}
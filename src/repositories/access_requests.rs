@@ -0,0 +1,167 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::access_requests::{
+    AccessRequest, NewAccessRequest, STATUS_APPROVED, STATUS_DENIED, STATUS_PENDING,
+};
+use crate::models::file_changes::{CHANGE_SHARED, CHANGE_UPDATED, NewFileChange};
+use crate::models::file_permissions::NewFilePermission;
+use crate::repositories::file_changes::{file_audience, record_file_changes};
+use crate::schema::access_requests::dsl::*;
+use crate::schema::{file_permissions, s3_files};
+use diesel::prelude::*;
+
+/// Inserts a new pending access request and returns the created record
+pub fn insert_access_request(
+    pool: &DbPool,
+    new: &NewAccessRequest,
+) -> Result<AccessRequest, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::insert_into(access_requests)
+        .values(new)
+        .get_result(&mut conn)
+}
+
+/// Finds an access request by its ID.
+pub fn find_access_request_by_id(
+    pool: &DbPool,
+    request_id: i32,
+) -> Result<AccessRequest, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    access_requests
+        .filter(id.eq(request_id))
+        .first::<AccessRequest>(&mut conn)
+}
+
+/// Finds the pending access request a user has open on a file, if any.
+pub fn find_pending_access_request(
+    pool: &DbPool,
+    file_id_val: i32,
+    requester: &str,
+) -> Result<Option<AccessRequest>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    access_requests
+        .filter(file_id.eq(file_id_val))
+        .filter(requester_id.eq(requester))
+        .filter(status.eq(STATUS_PENDING))
+        .first::<AccessRequest>(&mut conn)
+        .optional()
+}
+
+/// Lists access requests on all files owned by the given user, newest first.
+/// When `status_filter` is set, only requests with that status are returned.
+pub fn list_access_requests_for_owner(
+    pool: &DbPool,
+    owner_id: &str,
+    status_filter: Option<&str>,
+) -> Result<Vec<AccessRequest>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let mut query = access_requests
+        .inner_join(s3_files::table)
+        .filter(s3_files::user_id.eq(owner_id))
+        .select(AccessRequest::as_select())
+        .order(created_at.desc())
+        .into_boxed();
+
+    if let Some(status_val) = status_filter {
+        query = query.filter(status.eq(status_val));
+    }
+
+    query.load::<AccessRequest>(&mut conn)
+}
+
+/// Lists access requests made by the given user, newest first.
+pub fn list_access_requests_by_requester(
+    pool: &DbPool,
+    requester: &str,
+) -> Result<Vec<AccessRequest>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    access_requests
+        .filter(requester_id.eq(requester))
+        .order(created_at.desc())
+        .load::<AccessRequest>(&mut conn)
+}
+
+/// Approves a pending access request and grants the requester `role_val` on the file.
+/// Both changes are applied in a single transaction; an existing permission is
+/// replaced by the new role. The requester and the owner see the change in
+/// their changes feeds.
+pub fn approve_access_request(
+    pool: &DbPool,
+    request_id: i32,
+    role_val: &str,
+    granted_by: &str,
+) -> Result<AccessRequest, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        let request = diesel::update(
+            access_requests
+                .filter(id.eq(request_id))
+                .filter(status.eq(STATUS_PENDING)),
+        )
+        .set((
+            status.eq(STATUS_APPROVED),
+            role.eq(role_val),
+            resolved_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .get_result::<AccessRequest>(conn)?;
+
+        let (owner, grantees) = file_audience(conn, request.file_id)?;
+        let already_shared = grantees.contains(&request.requester_id);
+
+        let permission = NewFilePermission {
+            file_id: request.file_id,
+            user_id: request.requester_id.clone(),
+            role: role_val.to_string(),
+            granted_by: granted_by.to_string(),
+        };
+
+        diesel::insert_into(file_permissions::table)
+            .values(&permission)
+            .on_conflict((file_permissions::file_id, file_permissions::user_id))
+            .do_update()
+            .set((
+                file_permissions::role.eq(&permission.role),
+                file_permissions::granted_by.eq(&permission.granted_by),
+            ))
+            .execute(conn)?;
+
+        let requester_change = if already_shared {
+            CHANGE_UPDATED
+        } else {
+            CHANGE_SHARED
+        };
+        record_file_changes(
+            conn,
+            &[
+                NewFileChange::new(&request.requester_id, request.file_id, requester_change),
+                NewFileChange::new(&owner, request.file_id, CHANGE_UPDATED),
+            ],
+        )?;
+
+        Ok(request)
+    })
+}
+
+/// Denies a pending access request.
+pub fn deny_access_request(
+    pool: &DbPool,
+    request_id: i32,
+) -> Result<AccessRequest, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(
+        access_requests
+            .filter(id.eq(request_id))
+            .filter(status.eq(STATUS_PENDING)),
+    )
+    .set((
+        status.eq(STATUS_DENIED),
+        resolved_at.eq(chrono::Utc::now().naive_utc()),
+    ))
+    .get_result::<AccessRequest>(&mut conn)
+}
@@ -0,0 +1,254 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::account_jobs::{
+    AccountJob, KIND_EXPORT, NewAccountJob, STATUS_COMPLETED, STATUS_EXPIRED, STATUS_FAILED,
+    STATUS_PENDING, STATUS_RUNNING,
+};
+use crate::schema::account_jobs::dsl::*;
+use crate::schema::{file_requests, users};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// Inserts a new job and returns it. Fails with a unique violation if the
+/// user already has an active job of the same kind.
+pub fn insert_account_job(
+    pool: &DbPool,
+    new: &NewAccountJob,
+) -> Result<AccountJob, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::insert_into(account_jobs)
+        .values(new)
+        .returning(AccountJob::as_returning())
+        .get_result(&mut conn)
+}
+
+/// Marks the user as deleted, closes their file request links and queues the
+/// deletion job in one transaction, so the account is locked and receives no
+/// new files from the moment the deletion is accepted.
+pub fn start_account_deletion(
+    pool: &DbPool,
+    owner: i32,
+    new: &NewAccountJob,
+) -> Result<AccountJob, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let now = chrono::Utc::now().naive_utc();
+
+    conn.transaction(|conn| {
+        diesel::update(users::table.filter(users::id.eq(owner)))
+            .set(users::deleted_at.eq(now))
+            .execute(conn)?;
+
+        diesel::update(
+            file_requests::table
+                .filter(file_requests::owner_id.eq(&new.user_id))
+                .filter(file_requests::revoked_at.is_null()),
+        )
+        .set(file_requests::revoked_at.eq(now))
+        .execute(conn)?;
+
+        diesel::insert_into(account_jobs)
+            .values(new)
+            .returning(AccountJob::as_returning())
+            .get_result(conn)
+    })
+}
+
+/// Finds a job by its ID.
+pub fn find_account_job_by_id(
+    pool: &DbPool,
+    job_id: Uuid,
+) -> Result<AccountJob, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    account_jobs
+        .filter(id.eq(job_id))
+        .select(AccountJob::as_select())
+        .first::<AccountJob>(&mut conn)
+}
+
+/// Finds the pending or running job of the given kind for a user, if any.
+pub fn find_active_account_job(
+    pool: &DbPool,
+    owner: &str,
+    kind_val: &str,
+) -> Result<Option<AccountJob>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    account_jobs
+        .filter(user_id.eq(owner))
+        .filter(kind.eq(kind_val))
+        .filter(status.eq_any([STATUS_PENDING, STATUS_RUNNING]))
+        .select(AccountJob::as_select())
+        .first::<AccountJob>(&mut conn)
+        .optional()
+}
+
+/// Claims the next due job. Running jobs whose lease has expired are claimed
+/// again, so work left behind by a crashed worker is resumed. The claimed job
+/// has `next_attempt_at` pushed to `lease_until`.
+pub fn claim_due_account_job(
+    pool: &DbPool,
+    lease_until: NaiveDateTime,
+) -> Result<Option<AccountJob>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+    let now = chrono::Utc::now().naive_utc();
+
+    conn.transaction(|conn| {
+        let due = account_jobs
+            .filter(status.eq_any([STATUS_PENDING, STATUS_RUNNING]))
+            .filter(next_attempt_at.le(now))
+            .order(next_attempt_at.asc())
+            .for_update()
+            .skip_locked()
+            .select(AccountJob::as_select())
+            .first::<AccountJob>(conn)
+            .optional()?;
+
+        let Some(job) = due else {
+            return Ok(None);
+        };
+
+        diesel::update(account_jobs.filter(id.eq(job.id)))
+            .set((status.eq(STATUS_RUNNING), next_attempt_at.eq(lease_until)))
+            .returning(AccountJob::as_returning())
+            .get_result(conn)
+            .map(Some)
+    })
+}
+
+/// Marks an export as done and records where its archive is stored.
+pub fn complete_export_job(
+    pool: &DbPool,
+    job_id: Uuid,
+    key: &str,
+    size: i64,
+    expires: NaiveDateTime,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(account_jobs.filter(id.eq(job_id)))
+        .set((
+            status.eq(STATUS_COMPLETED),
+            archive_key.eq(key),
+            archive_size.eq(size),
+            attempts.eq(attempts + 1),
+            last_error.eq(None::<String>),
+            completed_at.eq(chrono::Utc::now().naive_utc()),
+            expires_at.eq(expires),
+        ))
+        .execute(&mut conn)
+}
+
+/// Marks a deletion job as done.
+pub fn complete_deletion_job(pool: &DbPool, job_id: Uuid) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(account_jobs.filter(id.eq(job_id)))
+        .set((
+            status.eq(STATUS_COMPLETED),
+            attempts.eq(attempts + 1),
+            last_error.eq(None::<String>),
+            completed_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(&mut conn)
+}
+
+/// Records a failed attempt. The job is retried at `retry_at`, or marked as
+/// permanently failed when `retry_at` is `None`.
+pub fn mark_account_job_failed(
+    pool: &DbPool,
+    job_id: Uuid,
+    error: &str,
+    retry_at: Option<NaiveDateTime>,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let target = account_jobs.filter(id.eq(job_id));
+
+    match retry_at {
+        Some(retry_at) => diesel::update(target)
+            .set((
+                status.eq(STATUS_PENDING),
+                attempts.eq(attempts + 1),
+                last_error.eq(error),
+                next_attempt_at.eq(retry_at),
+            ))
+            .execute(&mut conn),
+        None => diesel::update(target)
+            .set((
+                status.eq(STATUS_FAILED),
+                attempts.eq(attempts + 1),
+                last_error.eq(error),
+            ))
+            .execute(&mut conn),
+    }
+}
+
+/// Marks a deletion job as permanently failed and unlocks the account again,
+/// in one transaction, so a deletion that cannot finish does not leave the
+/// user locked out with their data still in place.
+pub fn fail_account_deletion(
+    pool: &DbPool,
+    job_id: Uuid,
+    owner: i32,
+    error: &str,
+) -> Result<(), diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        diesel::update(account_jobs.filter(id.eq(job_id)))
+            .set((
+                status.eq(STATUS_FAILED),
+                attempts.eq(attempts + 1),
+                last_error.eq(error),
+            ))
+            .execute(conn)?;
+
+        diesel::update(users::table.filter(users::id.eq(owner)))
+            .set(users::deleted_at.eq(None::<NaiveDateTime>))
+            .execute(conn)?;
+
+        Ok(())
+    })
+}
+
+/// Lists completed exports whose archive is past its expiry at `now`.
+pub fn list_expired_exports(
+    pool: &DbPool,
+    now: NaiveDateTime,
+) -> Result<Vec<AccountJob>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    account_jobs
+        .filter(kind.eq(KIND_EXPORT))
+        .filter(status.eq(STATUS_COMPLETED))
+        .filter(expires_at.le(now))
+        .select(AccountJob::as_select())
+        .load::<AccountJob>(&mut conn)
+}
+
+/// Lists the exports of a user that still have an archive in storage.
+pub fn list_export_archives_by_owner(
+    pool: &DbPool,
+    owner: &str,
+) -> Result<Vec<AccountJob>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    account_jobs
+        .filter(user_id.eq(owner))
+        .filter(kind.eq(KIND_EXPORT))
+        .filter(archive_key.is_not_null())
+        .select(AccountJob::as_select())
+        .load::<AccountJob>(&mut conn)
+}
+
+/// Forgets the archive of an export after it has been removed from storage.
+pub fn mark_export_expired(pool: &DbPool, job_id: Uuid) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(account_jobs.filter(id.eq(job_id)))
+        .set((status.eq(STATUS_EXPIRED), archive_key.eq(None::<String>)))
+        .execute(&mut conn)
+}
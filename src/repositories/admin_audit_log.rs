@@ -0,0 +1,49 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::admin_audit_log::{AuditLogEntry, NewAuditLogEntry};
+use crate::schema::admin_audit_log::dsl::*;
+use diesel::prelude::*;
+
+/// Runs an admin action and appends the audit log entry `run` returns
+/// in the same transaction, so an action that cannot be recorded is rolled
+/// back.
+pub fn with_audit_log_entry<T>(
+    pool: &DbPool,
+    run: impl FnOnce(&mut PgConnection) -> Result<(T, NewAuditLogEntry), diesel::result::Error>,
+) -> Result<T, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        let (result, entry) = run(conn)?;
+        diesel::insert_into(admin_audit_log)
+            .values(&entry)
+            .execute(conn)?;
+        Ok(result)
+    })
+}
+
+/// Appends an entry to the admin audit log.
+pub fn insert_audit_log_entry(
+    pool: &DbPool,
+    new: &NewAuditLogEntry,
+) -> Result<AuditLogEntry, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::insert_into(admin_audit_log)
+        .values(new)
+        .get_result(&mut conn)
+}
+
+/// Lists audit log entries, newest first.
+pub fn list_audit_log(
+    pool: &DbPool,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<AuditLogEntry>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    admin_audit_log
+        .order(id.desc())
+        .limit(limit)
+        .offset(offset)
+        .load::<AuditLogEntry>(&mut conn)
+}
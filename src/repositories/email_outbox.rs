@@ -0,0 +1,94 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::email_outbox::{
+    NewOutboxEmail, OutboxEmail, STATUS_FAILED, STATUS_PENDING, STATUS_SENT,
+};
+use crate::schema::email_outbox::dsl::*;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+/// Inserts a new email into the outbox and returns the created record
+pub fn insert_outbox_email(
+    pool: &DbPool,
+    new: &NewOutboxEmail,
+) -> Result<OutboxEmail, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::insert_into(email_outbox)
+        .values(new)
+        .get_result(&mut conn)
+}
+
+/// Claims up to `limit` pending emails that are due for delivery.
+/// Claimed emails have `next_attempt_at` pushed to `lease_until`, so other
+/// workers skip them while this one is sending.
+pub fn claim_due_emails(
+    pool: &DbPool,
+    limit: i64,
+    lease_until: NaiveDateTime,
+) -> Result<Vec<OutboxEmail>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+    let now = chrono::Utc::now().naive_utc();
+
+    conn.transaction(|conn| {
+        let due = email_outbox
+            .filter(status.eq(STATUS_PENDING))
+            .filter(next_attempt_at.le(now))
+            .order(next_attempt_at.asc())
+            .limit(limit)
+            .for_update()
+            .skip_locked()
+            .load::<OutboxEmail>(conn)?;
+
+        let ids: Vec<i32> = due.iter().map(|email| email.id).collect();
+
+        diesel::update(email_outbox.filter(id.eq_any(&ids)))
+            .set(next_attempt_at.eq(lease_until))
+            .execute(conn)?;
+
+        Ok(due)
+    })
+}
+
+/// Marks an outbox email as delivered.
+pub fn mark_email_sent(pool: &DbPool, email_id: i32) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(email_outbox.filter(id.eq(email_id)))
+        .set((
+            status.eq(STATUS_SENT),
+            attempts.eq(attempts + 1),
+            last_error.eq(None::<String>),
+            sent_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(&mut conn)
+}
+
+/// Records a failed delivery attempt. The email is retried at `retry_at`,
+/// or marked as permanently failed when `retry_at` is `None`.
+pub fn mark_email_failed(
+    pool: &DbPool,
+    email_id: i32,
+    error: &str,
+    retry_at: Option<NaiveDateTime>,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let target = email_outbox.filter(id.eq(email_id));
+
+    match retry_at {
+        Some(retry_at) => diesel::update(target)
+            .set((
+                attempts.eq(attempts + 1),
+                last_error.eq(error),
+                next_attempt_at.eq(retry_at),
+            ))
+            .execute(&mut conn),
+        None => diesel::update(target)
+            .set((
+                status.eq(STATUS_FAILED),
+                attempts.eq(attempts + 1),
+                last_error.eq(error),
+            ))
+            .execute(&mut conn),
+    }
+}
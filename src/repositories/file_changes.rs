@@ -0,0 +1,112 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::file_changes::{ChangePosition, FileChange, NewFileChange};
+use crate::schema::file_changes::dsl::*;
+use crate::schema::{file_permissions, s3_files};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+
+/// Records file changes as part of the caller's transaction. The rows are
+/// tagged with the transaction's ID, which is what orders them in the feed;
+/// see [`change_watermark`].
+pub fn record_file_changes(
+    conn: &mut PgConnection,
+    changes: &[NewFileChange],
+) -> Result<(), diesel::result::Error> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    diesel::insert_into(file_changes)
+        .values(changes)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// The owner of a file and the users it has been shared with, who all see
+/// changes to it.
+pub fn file_audience(
+    conn: &mut PgConnection,
+    file_id_val: i32,
+) -> Result<(String, Vec<String>), diesel::result::Error> {
+    let owner = s3_files::table
+        .filter(s3_files::file_id.eq(file_id_val))
+        .select(s3_files::user_id)
+        .first::<String>(conn)?;
+    let grantees = file_permissions::table
+        .filter(file_permissions::file_id.eq(file_id_val))
+        .select(file_permissions::user_id)
+        .load::<String>(conn)?;
+
+    Ok((owner, grantees))
+}
+
+/// The position up to which the feed is settled: every transaction before it
+/// has finished, so no change can still appear before it, and every change
+/// committed from now on comes after it. A long-running write transaction
+/// holds the watermark back, and with it the feed, until it finishes.
+pub fn change_watermark(pool: &DbPool) -> Result<ChangePosition, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let oldest_running = diesel::select(sql::<BigInt>(
+        "pg_snapshot_xmin(pg_current_snapshot())::text::bigint",
+    ))
+    .get_result::<i64>(&mut conn)?;
+
+    Ok(ChangePosition {
+        tx_id: oldest_running,
+        id: 0,
+    })
+}
+
+/// Filters changes to the positions from `from` up to, but not including,
+/// `until`.
+macro_rules! between {
+    ($from:expr, $until:expr) => {
+        tx_id
+            .gt($from.tx_id)
+            .or(tx_id.eq($from.tx_id).and(id.ge($from.id)))
+            .and(
+                tx_id
+                    .lt($until.tx_id)
+                    .or(tx_id.eq($until.tx_id).and(id.lt($until.id))),
+            )
+    };
+}
+
+/// Loads a user's changes from position `from` up to, but not including,
+/// `until`, oldest first.
+pub fn list_file_changes(
+    pool: &DbPool,
+    user: &str,
+    from: ChangePosition,
+    until: ChangePosition,
+    limit: i64,
+) -> Result<Vec<FileChange>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    file_changes
+        .filter(user_id.eq(user))
+        .filter(between!(from, until))
+        .order((tx_id.asc(), id.asc()))
+        .limit(limit)
+        .load::<FileChange>(&mut conn)
+}
+
+/// Loads the changes of all users from position `from` up to, but not
+/// including, `until`, oldest first.
+pub fn list_all_file_changes(
+    pool: &DbPool,
+    from: ChangePosition,
+    until: ChangePosition,
+    limit: i64,
+) -> Result<Vec<FileChange>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    file_changes
+        .filter(between!(from, until))
+        .order((tx_id.asc(), id.asc()))
+        .limit(limit)
+        .load::<FileChange>(&mut conn)
+}
@@ -0,0 +1,291 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::file_contents::{FileSearchHit, PendingFileContent};
+use crate::models::s3_files::S3File;
+use crate::pagination::{Cursor, PageToken, Sort, SortKey};
+use crate::repositories::s3_files::{FileQuery, accessible_files, sort_files};
+use crate::repositories::users::lower;
+use crate::schema::file_contents::dsl::*;
+use crate::schema::{s3_files, users};
+use crate::search::query::{Comparison, Filter, Owner, ParsedQuery};
+use crate::search::sql::{
+    TrigramExpressionMethods, TsvectorExpressionMethods, search_normalize, ts_headline, ts_rank,
+    word_similarity,
+};
+use crate::search::{
+    HEADLINE_OPTIONS, config, document_vector, fuzzy_terms, search_query, set_fuzzy_threshold,
+};
+use chrono::NaiveDateTime;
+use diesel::dsl::InnerJoinQuerySource;
+use diesel::expression::BoxableExpression;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Float4, Nullable, Text};
+
+/// Claims the next file waiting to be indexed. Its `next_attempt_at` is
+/// pushed to `lease_until`, so a file left behind by a crashed worker is
+/// picked up again once the lease is over.
+pub fn claim_due_file_content(
+    pool: &DbPool,
+    lease_until: NaiveDateTime,
+) -> Result<Option<PendingFileContent>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+    let now = chrono::Utc::now().naive_utc();
+
+    conn.transaction(|conn| {
+        let due = file_contents
+            .filter(indexed_at.is_null())
+            .filter(next_attempt_at.le(now))
+            .order(next_attempt_at.asc())
+            .for_update()
+            .skip_locked()
+            .select(PendingFileContent::as_select())
+            .first::<PendingFileContent>(conn)
+            .optional()?;
+
+        let Some(pending) = due else {
+            return Ok(None);
+        };
+
+        diesel::update(file_contents.filter(file_id.eq(pending.file_id)))
+            .set(next_attempt_at.eq(lease_until))
+            .execute(conn)?;
+
+        Ok(Some(pending))
+    })
+}
+
+/// Stores the extracted text of a file and rebuilds its search vector. A file
+/// whose text cannot be extracted is saved with `error` and no content, so
+/// it stays searchable by name.
+pub fn save_file_content(
+    pool: &DbPool,
+    file: &S3File,
+    text: Option<String>,
+    error: Option<&str>,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(file_contents.filter(file_id.eq(file.file_id)))
+        .set((
+            search_vector.eq(document_vector(
+                &file.name,
+                text.clone().unwrap_or_default(),
+            )),
+            content.eq(text),
+            extraction_error.eq(error),
+            attempts.eq(attempts + 1),
+            indexed_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(&mut conn)
+}
+
+/// Records a failed attempt to index a file; it is tried again at `retry_at`.
+pub fn mark_file_content_failed(
+    pool: &DbPool,
+    file_id_val: i32,
+    error: &str,
+    retry_at: NaiveDateTime,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(file_contents.filter(file_id.eq(file_id_val)))
+        .set((
+            attempts.eq(attempts + 1),
+            extraction_error.eq(error),
+            next_attempt_at.eq(retry_at),
+        ))
+        .execute(&mut conn)
+}
+
+/// The files a user owns or has been granted access to that match the
+/// operators and free text of a search. Free text matches the indexed words,
+/// or file names with similarly spelled words; the threshold for the latter
+/// must be set with `set_fuzzy_threshold` in the same transaction.
+fn matching_files(viewer: &str, query: &ParsedQuery) -> FileQuery {
+    let mut files = accessible_files(viewer);
+
+    for filter in &query.filters {
+        files = match filter {
+            Filter::Name(part) => files.filter(
+                search_normalize(s3_files::name)
+                    .like(search_normalize(format!("%{}%", escape_like(part)))),
+            ),
+            Filter::MimeType(patterns) => files.filter(mime_type_matches(patterns)),
+            Filter::Size(comparison, bytes) => match comparison {
+                Comparison::Less => files.filter(s3_files::size.lt(*bytes)),
+                Comparison::LessOrEqual => files.filter(s3_files::size.le(*bytes)),
+                Comparison::Greater => files.filter(s3_files::size.gt(*bytes)),
+                Comparison::GreaterOrEqual => files.filter(s3_files::size.ge(*bytes)),
+            },
+            Filter::Modified(comparison, at) => match comparison {
+                Comparison::Less => files.filter(s3_files::modified_at.lt(*at)),
+                Comparison::LessOrEqual => files.filter(s3_files::modified_at.le(*at)),
+                Comparison::Greater => files.filter(s3_files::modified_at.gt(*at)),
+                Comparison::GreaterOrEqual => files.filter(s3_files::modified_at.ge(*at)),
+            },
+            Filter::Owner(Owner::Me) => files.filter(s3_files::user_id.eq(viewer.to_string())),
+            Filter::Owner(Owner::Email(email)) => files.filter(
+                s3_files::user_id.eq_any(
+                    users::table
+                        .filter(lower(users::email).eq(lower(email.clone())))
+                        .filter(users::email_verified.eq(true))
+                        .select(users::id.cast::<Text>()),
+                ),
+            ),
+        };
+    }
+
+    if let Some(text) = &query.text {
+        files = files.filter(search_vector.matches(search_query(text.clone())).or(
+            search_normalize(fuzzy_terms(text)).word_similar_to(search_normalize(s3_files::name)),
+        ));
+    }
+
+    files
+}
+
+/// Loads one page of search results. Free text searches have a rank and a
+/// snippet, and can be sorted by relevance: the text search rank plus how
+/// similar the file name is to the text.
+pub fn search_file_contents(
+    pool: &DbPool,
+    viewer: &str,
+    query: &ParsedQuery,
+    sort: Sort,
+    after: Option<&PageToken>,
+    limit: i64,
+) -> Result<Vec<FileSearchHit>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let hits = conn.transaction(|conn| {
+        diesel::select(set_fuzzy_threshold()).execute(conn)?;
+        let files = matching_files(viewer, query);
+
+        match &query.text {
+            Some(text) => {
+                let rank = ts_rank(search_vector, search_query(text.clone()))
+                    + word_similarity(
+                        search_normalize(fuzzy_terms(text)),
+                        search_normalize(s3_files::name),
+                    );
+
+                let files = match (sort.key, after) {
+                    (SortKey::Relevance, Some(token)) => {
+                        let Cursor::Relevance(after_rank) = token.after else {
+                            unreachable!("page tokens are checked against the sort order");
+                        };
+                        files
+                            .filter(
+                                rank.clone().lt(after_rank).or(rank
+                                    .clone()
+                                    .eq(after_rank)
+                                    .and(s3_files::file_id.lt(token.file_id))),
+                            )
+                            .order_by((rank.clone().desc(), s3_files::file_id.desc()))
+                    }
+                    (SortKey::Relevance, None) => {
+                        files.order_by((rank.clone().desc(), s3_files::file_id.desc()))
+                    }
+                    _ => sort_files(files, sort, after),
+                };
+
+                files
+                    .select((
+                        S3File::as_select(),
+                        rank.nullable(),
+                        ts_headline(
+                            config(),
+                            content,
+                            search_query(text.clone()),
+                            HEADLINE_OPTIONS,
+                        ),
+                    ))
+                    .limit(limit)
+                    .load::<(S3File, Option<f32>, Option<String>)>(conn)
+            }
+            None => sort_files(files, sort, after)
+                .select((
+                    S3File::as_select(),
+                    None::<f32>.into_sql::<Nullable<Float4>>(),
+                    None::<String>.into_sql::<Nullable<Text>>(),
+                ))
+                .limit(limit)
+                .load::<(S3File, Option<f32>, Option<String>)>(conn),
+        }
+    })?;
+
+    Ok(hits
+        .into_iter()
+        .map(|(file, rank, headline)| FileSearchHit {
+            file,
+            rank,
+            headline,
+        })
+        .collect())
+}
+
+/// Counts all results of a search.
+pub fn count_search_results(
+    pool: &DbPool,
+    viewer: &str,
+    query: &ParsedQuery,
+) -> Result<i64, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        diesel::select(set_fuzzy_threshold()).execute(conn)?;
+        matching_files(viewer, query)
+            .count()
+            .get_result::<i64>(conn)
+    })
+}
+
+/// Completes a partially typed file name: files the user can access whose
+/// name contains `prefix`, names starting with it first.
+pub fn suggest_file_names(
+    pool: &DbPool,
+    viewer: &str,
+    prefix: &str,
+    limit: i64,
+) -> Result<Vec<S3File>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+    let prefix = escape_like(prefix);
+
+    accessible_files(viewer)
+        .filter(search_normalize(s3_files::name).like(search_normalize(format!("%{}%", prefix))))
+        .order_by((
+            search_normalize(s3_files::name)
+                .like(search_normalize(format!("{}%", prefix)))
+                .desc(),
+            s3_files::name.asc(),
+            s3_files::file_id.asc(),
+        ))
+        .select(S3File::as_select())
+        .limit(limit)
+        .load::<S3File>(&mut conn)
+}
+
+/// `mime_type LIKE p1 OR mime_type LIKE p2 ...`
+fn mime_type_matches(
+    patterns: &[String],
+) -> Box<
+    dyn BoxableExpression<InnerJoinQuerySource<s3_files::table, file_contents>, Pg, SqlType = Bool>,
+> {
+    patterns
+        .iter()
+        .map(
+            |pattern| -> Box<dyn BoxableExpression<_, Pg, SqlType = Bool>> {
+                Box::new(s3_files::mime_type.like(pattern.clone()))
+            },
+        )
+        .reduce(|any, next| Box::new(any.or(next)))
+        .unwrap_or_else(|| Box::new(false.into_sql::<Bool>()))
+}
+
+/// Escapes the `LIKE` wildcards in user input.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
@@ -0,0 +1,62 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::file_permissions::FilePermission;
+use crate::models::s3_files::S3File;
+use crate::schema::file_permissions::dsl::*;
+use crate::schema::s3_files;
+use diesel::prelude::*;
+
+/// Finds the permission a user has been granted on a file, if any.
+pub fn find_file_permission(
+    pool: &DbPool,
+    file_id_val: i32,
+    user_id_val: &str,
+) -> Result<Option<FilePermission>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    file_permissions
+        .filter(file_id.eq(file_id_val))
+        .filter(user_id.eq(user_id_val))
+        .first::<FilePermission>(&mut conn)
+        .optional()
+}
+
+/// Returns true if the user owns the file or has been granted any role on it.
+pub fn has_file_access(
+    pool: &DbPool,
+    file: &S3File,
+    user_id_val: &str,
+) -> Result<bool, diesel::result::Error> {
+    if file.user_id == user_id_val {
+        return Ok(true);
+    }
+
+    Ok(find_file_permission(pool, file.file_id, user_id_val)?.is_some())
+}
+
+/// Lists the permissions granted on files owned by the given user.
+pub fn list_permissions_for_owner(
+    pool: &DbPool,
+    owner: &str,
+) -> Result<Vec<FilePermission>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    file_permissions
+        .inner_join(s3_files::table)
+        .filter(s3_files::user_id.eq(owner))
+        .select(FilePermission::as_select())
+        .order(created_at.asc())
+        .load::<FilePermission>(&mut conn)
+}
+
+/// Lists the permissions a user has been granted on other people's files.
+pub fn list_permissions_for_user(
+    pool: &DbPool,
+    user_id_val: &str,
+) -> Result<Vec<FilePermission>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    file_permissions
+        .filter(user_id.eq(user_id_val))
+        .order(created_at.asc())
+        .load::<FilePermission>(&mut conn)
+}
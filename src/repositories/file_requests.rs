@@ -0,0 +1,94 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::file_requests::{FileRequest, NewFileRequest};
+use crate::schema::file_requests::dsl::*;
+use diesel::prelude::*;
+
+/// Inserts a new file request and returns the created record
+pub fn insert_file_request(
+    pool: &DbPool,
+    new: &NewFileRequest,
+) -> Result<FileRequest, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::insert_into(file_requests)
+        .values(new)
+        .get_result(&mut conn)
+}
+
+/// Finds a file request by its ID.
+pub fn find_file_request_by_id(
+    pool: &DbPool,
+    request_id: i32,
+) -> Result<FileRequest, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    file_requests
+        .filter(id.eq(request_id))
+        .first::<FileRequest>(&mut conn)
+}
+
+/// Finds a file request by its public token.
+pub fn find_file_request_by_token(
+    pool: &DbPool,
+    token_val: &str,
+) -> Result<FileRequest, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    file_requests
+        .filter(token.eq(token_val))
+        .first::<FileRequest>(&mut conn)
+}
+
+/// Lists file requests created by the given user, newest first.
+pub fn list_file_requests_by_owner(
+    pool: &DbPool,
+    owner: &str,
+) -> Result<Vec<FileRequest>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    file_requests
+        .filter(owner_id.eq(owner))
+        .order(created_at.desc())
+        .load::<FileRequest>(&mut conn)
+}
+
+/// Revokes a file request so it no longer accepts uploads.
+pub fn revoke_file_request(
+    pool: &DbPool,
+    request_id: i32,
+) -> Result<FileRequest, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(file_requests.filter(id.eq(request_id)))
+        .set(revoked_at.eq(chrono::Utc::now().naive_utc()))
+        .get_result(&mut conn)
+}
+
+/// Reserves one upload slot on a file request.
+/// Returns `None` when the request has reached its `max_files` limit.
+pub fn reserve_upload_slot(
+    pool: &DbPool,
+    request_id: i32,
+) -> Result<Option<FileRequest>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(
+        file_requests.filter(id.eq(request_id)).filter(
+            max_files
+                .is_null()
+                .or(upload_count.lt(max_files.assume_not_null())),
+        ),
+    )
+    .set(upload_count.eq(upload_count + 1))
+    .get_result(&mut conn)
+    .optional()
+}
+
+/// Gives back an upload slot reserved for an upload that did not complete.
+pub fn release_upload_slot(pool: &DbPool, request_id: i32) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(file_requests.filter(id.eq(request_id)))
+        .set(upload_count.eq(upload_count - 1))
+        .execute(&mut conn)
+}
@@ -0,0 +1,66 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::schema::instance_settings::dsl::*;
+use diesel::prelude::*;
+use diesel::result::Error;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Loads the setting stored under `setting_name`, if any.
+pub fn find_setting<T: DeserializeOwned>(
+    pool: &DbPool,
+    setting_name: &str,
+) -> Result<Option<T>, Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let stored = instance_settings
+        .filter(name.eq(setting_name))
+        .select(value)
+        .first::<String>(&mut conn)
+        .optional()?;
+
+    stored
+        .map(|stored| serde_json::from_str(&stored))
+        .transpose()
+        .map_err(|e| Error::DeserializationError(Box::new(e)))
+}
+
+/// Stores `default` under `setting_name` unless a value is already stored.
+pub fn insert_default_setting<T: Serialize>(
+    pool: &DbPool,
+    setting_name: &str,
+    default: &T,
+) -> Result<(), Error> {
+    let mut conn = get_db_conn(pool)?;
+    let default =
+        serde_json::to_string(default).map_err(|e| Error::SerializationError(Box::new(e)))?;
+
+    diesel::insert_into(instance_settings)
+        .values((name.eq(setting_name), value.eq(default)))
+        .on_conflict(name)
+        .do_nothing()
+        .execute(&mut conn)?;
+
+    Ok(())
+}
+
+/// Replaces the setting stored under `setting_name`.
+pub fn update_setting<T: Serialize>(
+    conn: &mut PgConnection,
+    setting_name: &str,
+    new_value: &T,
+) -> Result<(), Error> {
+    let new_value =
+        serde_json::to_string(new_value).map_err(|e| Error::SerializationError(Box::new(e)))?;
+
+    diesel::insert_into(instance_settings)
+        .values((name.eq(setting_name), value.eq(&new_value)))
+        .on_conflict(name)
+        .do_update()
+        .set((
+            value.eq(&new_value),
+            updated_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
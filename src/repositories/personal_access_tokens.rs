@@ -0,0 +1,83 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::personal_access_tokens::{NewPersonalAccessToken, PersonalAccessToken};
+use crate::schema::personal_access_tokens::dsl::*;
+use diesel::prelude::*;
+
+/// Inserts a new personal access token and returns the created record
+pub fn insert_personal_access_token(
+    pool: &DbPool,
+    new: &NewPersonalAccessToken,
+) -> Result<PersonalAccessToken, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::insert_into(personal_access_tokens)
+        .values(new)
+        .returning(PersonalAccessToken::as_returning())
+        .get_result(&mut conn)
+}
+
+/// Finds a personal access token by the hash of its secret.
+pub fn find_personal_access_token_by_hash(
+    pool: &DbPool,
+    hash: &str,
+) -> Result<Option<PersonalAccessToken>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    personal_access_tokens
+        .filter(token_hash.eq(hash))
+        .select(PersonalAccessToken::as_select())
+        .first::<PersonalAccessToken>(&mut conn)
+        .optional()
+}
+
+/// Finds a personal access token by its ID.
+pub fn find_personal_access_token_by_id(
+    pool: &DbPool,
+    token_id: i32,
+) -> Result<PersonalAccessToken, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    personal_access_tokens
+        .filter(id.eq(token_id))
+        .select(PersonalAccessToken::as_select())
+        .first::<PersonalAccessToken>(&mut conn)
+}
+
+/// Lists the personal access tokens of a user, newest first.
+pub fn list_personal_access_tokens(
+    pool: &DbPool,
+    owner: &str,
+) -> Result<Vec<PersonalAccessToken>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    personal_access_tokens
+        .filter(user_id.eq(owner))
+        .order(created_at.desc())
+        .select(PersonalAccessToken::as_select())
+        .load::<PersonalAccessToken>(&mut conn)
+}
+
+/// Records that a token has just been used.
+pub fn touch_personal_access_token(
+    pool: &DbPool,
+    token_id: i32,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(personal_access_tokens.filter(id.eq(token_id)))
+        .set(last_used_at.eq(chrono::Utc::now().naive_utc()))
+        .execute(&mut conn)
+}
+
+/// Revokes a personal access token.
+pub fn revoke_personal_access_token(
+    pool: &DbPool,
+    token_id: i32,
+) -> Result<PersonalAccessToken, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(personal_access_tokens.filter(id.eq(token_id)))
+        .set(revoked_at.eq(chrono::Utc::now().naive_utc()))
+        .returning(PersonalAccessToken::as_returning())
+        .get_result(&mut conn)
+}
@@ -1,22 +1,135 @@
 use crate::database::{DbPool, get_db_conn};
-use crate::models::s3_files::{NewS3File, S3File};
+use crate::models::file_changes::{CHANGE_CREATED, CHANGE_DELETED, NewFileChange};
+use crate::models::s3_files::{NewS3File, S3File, StorageUsage};
+use crate::pagination::{Cursor, PageToken, Sort, SortKey};
+use crate::repositories::file_changes::{file_audience, record_file_changes};
 use crate::schema::s3_files::dsl::*;
+use crate::schema::{file_contents, file_permissions};
+use crate::search::document_vector;
+use diesel::dsl::{InnerJoin, IntoBoxed};
+use diesel::pg::Pg;
 use diesel::prelude::*;
 
-/// Inserts a new S3 file record and returns the created record
+/// Files joined with their search index entry, which every file has.
+pub type FileQuery = IntoBoxed<'static, InnerJoin<s3_files, file_contents::table>, Pg>;
+
+/// Inserts a new S3 file record and returns the created record. The file is
+/// queued for content indexing and can be found by name right away, and it
+/// is added to the owner's changes feed.
 pub fn insert_s3_file(pool: &DbPool, new: &NewS3File) -> Result<S3File, diesel::result::Error> {
     let mut conn = get_db_conn(pool)?;
 
-    diesel::insert_into(s3_files)
-        .values(new)
-        .get_result(&mut conn)
+    conn.transaction(|conn| {
+        let file: S3File = diesel::insert_into(s3_files).values(new).get_result(conn)?;
+
+        diesel::insert_into(file_contents::table)
+            .values((
+                file_contents::file_id.eq(file.file_id),
+                file_contents::search_vector.eq(document_vector(&file.name, String::new())),
+            ))
+            .execute(conn)?;
+
+        record_file_changes(
+            conn,
+            &[NewFileChange::new(
+                &file.user_id,
+                file.file_id,
+                CHANGE_CREATED,
+            )],
+        )?;
+
+        Ok(file)
+    })
+}
+
+/// The files a user owns or has been granted access to.
+pub fn accessible_files(viewer: &str) -> FileQuery {
+    let shared_with_viewer = file_permissions::table
+        .filter(file_permissions::user_id.eq(viewer.to_string()))
+        .select(file_permissions::file_id);
+
+    s3_files
+        .inner_join(file_contents::table)
+        .filter(
+            user_id
+                .eq(viewer.to_string())
+                .or(file_id.eq_any(shared_with_viewer)),
+        )
+        .into_boxed()
 }
 
-/// Loads all S3 file records from the database
-pub fn load_all_s3_files(pool: &DbPool) -> Result<Vec<S3File>, diesel::result::Error> {
+/// Loads one page of the files a user can access, sorted by `sort`.
+pub fn list_s3_files_page(
+    pool: &DbPool,
+    viewer: &str,
+    sort: Sort,
+    after: Option<&PageToken>,
+    limit: i64,
+) -> Result<Vec<S3File>, diesel::result::Error> {
     let mut conn = get_db_conn(pool)?;
 
-    s3_files.load::<S3File>(&mut conn)
+    sort_files(accessible_files(viewer), sort, after)
+        .select(S3File::as_select())
+        .limit(limit)
+        .load::<S3File>(&mut conn)
+}
+
+/// Counts the files a user can access.
+pub fn count_accessible_s3_files(
+    pool: &DbPool,
+    viewer: &str,
+) -> Result<i64, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    accessible_files(viewer).count().get_result(&mut conn)
+}
+
+/// Skips past the `after` cursor: the next file in `$column` order, with
+/// the file ID breaking ties.
+macro_rules! after_cursor {
+    ($query:expr, $column:expr, $value:expr, $id:expr, $descending:expr) => {
+        if $descending {
+            $query.filter(
+                $column
+                    .lt($value.clone())
+                    .or($column.eq($value.clone()).and(file_id.lt($id))),
+            )
+        } else {
+            $query.filter(
+                $column
+                    .gt($value.clone())
+                    .or($column.eq($value.clone()).and(file_id.gt($id))),
+            )
+        }
+    };
+}
+
+/// Orders a file query by `sort` and starts it after the `after` cursor.
+/// Relevance is specific to searches, which sort by it themselves.
+pub fn sort_files(query: FileQuery, sort: Sort, after: Option<&PageToken>) -> FileQuery {
+    let query = match after.map(|token| (&token.after, token.file_id)) {
+        Some((Cursor::Name(value), id)) => after_cursor!(query, name, value, id, sort.descending),
+        Some((Cursor::Size(value), id)) => after_cursor!(query, size, value, id, sort.descending),
+        Some((Cursor::Created(value), id)) => {
+            after_cursor!(query, created_at, value, id, sort.descending)
+        }
+        Some((Cursor::Modified(value), id)) => {
+            after_cursor!(query, modified_at, value, id, sort.descending)
+        }
+        Some((Cursor::Relevance(_), _)) | None => query,
+    };
+
+    match (sort.key, sort.descending) {
+        (SortKey::Name, false) => query.order_by((name.asc(), file_id.asc())),
+        (SortKey::Name, true) => query.order_by((name.desc(), file_id.desc())),
+        (SortKey::Size, false) => query.order_by((size.asc(), file_id.asc())),
+        (SortKey::Size, true) => query.order_by((size.desc(), file_id.desc())),
+        (SortKey::Created, false) => query.order_by((created_at.asc(), file_id.asc())),
+        (SortKey::Created, true) => query.order_by((created_at.desc(), file_id.desc())),
+        (SortKey::Modified, false) => query.order_by((modified_at.asc(), file_id.asc())),
+        (SortKey::Modified, true) => query.order_by((modified_at.desc(), file_id.desc())),
+        (SortKey::Relevance, _) => query,
+    }
 }
 
 /// Finds an S3 file record by its ID.
@@ -31,24 +144,102 @@ pub fn find_s3_file_by_id(
         .first::<S3File>(&mut conn)
 }
 
-/// Deletes an S3 file record from the 's3_files' table by its ID.
+/// Lists the files owned by a user, oldest first.
+pub fn list_s3_files_by_owner(
+    pool: &DbPool,
+    owner: &str,
+) -> Result<Vec<S3File>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    s3_files
+        .filter(user_id.eq(owner))
+        .order(file_id.asc())
+        .load::<S3File>(&mut conn)
+}
+
+/// Loads those of the given files the user owns or has been granted access to.
+pub fn find_accessible_s3_files(
+    pool: &DbPool,
+    viewer: &str,
+    file_ids: &[i32],
+) -> Result<Vec<S3File>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let shared_with_viewer = file_permissions::table
+        .filter(file_permissions::user_id.eq(viewer))
+        .select(file_permissions::file_id);
+
+    s3_files
+        .filter(file_id.eq_any(file_ids))
+        .filter(user_id.eq(viewer).or(file_id.eq_any(shared_with_viewer)))
+        .load::<S3File>(&mut conn)
+}
+
+/// Deletes an S3 file record from the 's3_files' table by its ID, and
+/// reports the deletion to everyone who could see the file.
 pub fn delete_s3_file_by_id(
     pool: &DbPool,
     file_id_val: i32,
 ) -> Result<usize, diesel::result::Error> {
     let mut conn = get_db_conn(pool)?;
 
-    diesel::delete(s3_files.filter(file_id.eq(file_id_val))).execute(&mut conn)
+    conn.transaction(|conn| {
+        let (owner, grantees) = match file_audience(conn, file_id_val) {
+            Ok(audience) => audience,
+            Err(diesel::result::Error::NotFound) => return Ok(0),
+            Err(e) => return Err(e),
+        };
+
+        let deleted = diesel::delete(s3_files.filter(file_id.eq(file_id_val))).execute(conn)?;
+
+        let changes: Vec<NewFileChange> = std::iter::once(owner)
+            .chain(grantees)
+            .map(|user| NewFileChange::new(&user, file_id_val, CHANGE_DELETED))
+            .collect();
+        record_file_changes(conn, &changes)?;
+
+        Ok(deleted)
+    })
+}
+
+/// Returns the number of files and bytes stored per owner.
+pub fn storage_usage_by_user(pool: &DbPool) -> Result<Vec<StorageUsage>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    s3_files
+        .group_by(user_id)
+        .select((
+            user_id,
+            diesel::dsl::count_star(),
+            diesel::dsl::sql::<diesel::sql_types::BigInt>("COALESCE(SUM(size), 0)::BIGINT"),
+        ))
+        .load::<StorageUsage>(&mut conn)
 }
 
-/// Finds S3 files by partial match on s3_key (like a file path)
-pub fn find_s3_files_by_key(
+/// Returns the number of files and bytes stored by one owner.
+pub fn storage_usage_for_user(
     pool: &DbPool,
-    search_query: &str,
-) -> Result<Vec<S3File>, diesel::result::Error> {
+    owner: &str,
+) -> Result<(i64, i64), diesel::result::Error> {
     let mut conn = get_db_conn(pool)?;
 
     s3_files
-        .filter(s3_key.ilike(format!("%{}%", search_query)))
-        .load::<S3File>(&mut conn)
+        .filter(user_id.eq(owner))
+        .select((
+            diesel::dsl::count_star(),
+            diesel::dsl::sql::<diesel::sql_types::BigInt>("COALESCE(SUM(size), 0)::BIGINT"),
+        ))
+        .first::<(i64, i64)>(&mut conn)
+}
+
+/// Returns the number of files and bytes stored on the whole instance.
+pub fn total_storage_usage(pool: &DbPool) -> Result<(i64, i64), diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    s3_files
+        .select((
+            diesel::dsl::count_star(),
+            diesel::dsl::sql::<diesel::sql_types::BigInt>("COALESCE(SUM(size), 0)::BIGINT"),
+        ))
+        .first::<(i64, i64)>(&mut conn)
 }
@@ -0,0 +1,136 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::saved_searches::{
+    NewSavedSearch, NewSavedSearchShare, SavedSearch, SavedSearchShare,
+};
+use crate::schema::saved_search_shares;
+use crate::schema::saved_searches::dsl::*;
+use diesel::prelude::*;
+
+/// Inserts a new saved search and returns the created record.
+pub fn insert_saved_search(
+    pool: &DbPool,
+    new: &NewSavedSearch,
+) -> Result<SavedSearch, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::insert_into(saved_searches)
+        .values(new)
+        .get_result(&mut conn)
+}
+
+/// Finds a saved search by its ID.
+pub fn find_saved_search_by_id(
+    pool: &DbPool,
+    search_id: i32,
+) -> Result<SavedSearch, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    saved_searches
+        .filter(id.eq(search_id))
+        .first::<SavedSearch>(&mut conn)
+}
+
+/// Lists the searches a user saved or that were shared with them, by name.
+pub fn list_saved_searches_for_user(
+    pool: &DbPool,
+    user: &str,
+) -> Result<Vec<SavedSearch>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let shared_with_user = saved_search_shares::table
+        .filter(saved_search_shares::user_id.eq(user))
+        .select(saved_search_shares::saved_search_id);
+
+    saved_searches
+        .filter(owner_id.eq(user).or(id.eq_any(shared_with_user)))
+        .order((name.asc(), id.asc()))
+        .load::<SavedSearch>(&mut conn)
+}
+
+/// Replaces the name, query and order of a saved search and returns the
+/// updated record.
+pub fn update_saved_search(
+    pool: &DbPool,
+    search_id: i32,
+    name_val: &str,
+    query_val: &str,
+    order_by_val: Option<&str>,
+) -> Result<SavedSearch, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(saved_searches.filter(id.eq(search_id)))
+        .set((
+            name.eq(name_val),
+            query.eq(query_val),
+            order_by.eq(order_by_val),
+            updated_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .get_result(&mut conn)
+}
+
+/// Deletes a saved search; its shares are removed by cascade.
+pub fn delete_saved_search(pool: &DbPool, search_id: i32) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::delete(saved_searches.filter(id.eq(search_id))).execute(&mut conn)
+}
+
+/// Returns true if the saved search has been shared with the user.
+pub fn is_saved_search_shared_with(
+    pool: &DbPool,
+    search_id: i32,
+    user: &str,
+) -> Result<bool, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::select(diesel::dsl::exists(
+        saved_search_shares::table
+            .filter(saved_search_shares::saved_search_id.eq(search_id))
+            .filter(saved_search_shares::user_id.eq(user)),
+    ))
+    .get_result(&mut conn)
+}
+
+/// Shares a saved search with a user. Returns `None` if it already was.
+pub fn insert_saved_search_share(
+    pool: &DbPool,
+    new: &NewSavedSearchShare,
+) -> Result<Option<SavedSearchShare>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::insert_into(saved_search_shares::table)
+        .values(new)
+        .on_conflict_do_nothing()
+        .get_result(&mut conn)
+        .optional()
+}
+
+/// Lists the users a saved search has been shared with, oldest share first.
+pub fn list_saved_search_shares(
+    pool: &DbPool,
+    search_id: i32,
+) -> Result<Vec<SavedSearchShare>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    saved_search_shares::table
+        .filter(saved_search_shares::saved_search_id.eq(search_id))
+        .order(saved_search_shares::created_at.asc())
+        .load::<SavedSearchShare>(&mut conn)
+}
+
+/// Stops sharing a saved search with a user. Returns the number of removed
+/// shares, 0 or 1.
+pub fn delete_saved_search_share(
+    pool: &DbPool,
+    search_id: i32,
+    user: &str,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::delete(
+        saved_search_shares::table
+            .filter(saved_search_shares::saved_search_id.eq(search_id))
+            .filter(saved_search_shares::user_id.eq(user)),
+    )
+    .execute(&mut conn)
+}
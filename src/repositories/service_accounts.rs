@@ -0,0 +1,146 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::service_accounts::{
+    NewServiceAccount, NewServiceAccountAssertion, NewServiceAccountKey, ServiceAccount,
+    ServiceAccountKey,
+};
+use crate::models::users::{ROLE_SERVICE, User};
+use crate::schema::{service_account_assertions, service_account_keys, service_accounts, users};
+use diesel::prelude::*;
+
+/// Creates a service account together with the user it acts as.
+pub fn insert_service_account(
+    conn: &mut PgConnection,
+    owner: &str,
+    name: &str,
+    scopes: &[String],
+) -> Result<ServiceAccount, diesel::result::Error> {
+    conn.transaction(|conn| {
+        let user: User = diesel::insert_into(users::table)
+            .values((users::username.eq(name), users::role.eq(ROLE_SERVICE)))
+            .get_result(conn)?;
+
+        diesel::insert_into(service_accounts::table)
+            .values(&NewServiceAccount {
+                user_id: user.id,
+                owner_id: owner.to_string(),
+                name: name.to_string(),
+                scopes: scopes.to_vec(),
+            })
+            .returning(ServiceAccount::as_returning())
+            .get_result(conn)
+    })
+}
+
+/// Finds a service account by its ID.
+pub fn find_service_account_by_id(
+    pool: &DbPool,
+    account_id: i32,
+) -> Result<ServiceAccount, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    service_accounts::table
+        .filter(service_accounts::id.eq(account_id))
+        .select(ServiceAccount::as_select())
+        .first::<ServiceAccount>(&mut conn)
+}
+
+/// Lists all service accounts, oldest first.
+pub fn list_service_accounts(pool: &DbPool) -> Result<Vec<ServiceAccount>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    service_accounts::table
+        .order(service_accounts::created_at.asc())
+        .select(ServiceAccount::as_select())
+        .load::<ServiceAccount>(&mut conn)
+}
+
+/// Lists the service accounts created by `owner`.
+pub fn list_service_accounts_by_owner(
+    pool: &DbPool,
+    owner: &str,
+) -> Result<Vec<ServiceAccount>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    service_accounts::table
+        .filter(service_accounts::owner_id.eq(owner))
+        .select(ServiceAccount::as_select())
+        .load::<ServiceAccount>(&mut conn)
+}
+
+/// Registers a public key for a service account.
+pub fn insert_service_account_key(
+    conn: &mut PgConnection,
+    new: &NewServiceAccountKey,
+) -> Result<ServiceAccountKey, diesel::result::Error> {
+    diesel::insert_into(service_account_keys::table)
+        .values(new)
+        .returning(ServiceAccountKey::as_returning())
+        .get_result(conn)
+}
+
+/// Finds a key by the ID assertions name it with.
+pub fn find_service_account_key(
+    pool: &DbPool,
+    kid: &str,
+) -> Result<Option<ServiceAccountKey>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    service_account_keys::table
+        .filter(service_account_keys::key_id.eq(kid))
+        .select(ServiceAccountKey::as_select())
+        .first::<ServiceAccountKey>(&mut conn)
+        .optional()
+}
+
+/// Lists the keys of all service accounts, oldest first.
+pub fn list_service_account_keys(
+    pool: &DbPool,
+) -> Result<Vec<ServiceAccountKey>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    service_account_keys::table
+        .order(service_account_keys::created_at.asc())
+        .select(ServiceAccountKey::as_select())
+        .load::<ServiceAccountKey>(&mut conn)
+}
+
+/// Records that a key has just verified an assertion.
+pub fn touch_service_account_key(pool: &DbPool, key: i32) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(service_account_keys::table.filter(service_account_keys::id.eq(key)))
+        .set(service_account_keys::last_used_at.eq(chrono::Utc::now().naive_utc()))
+        .execute(&mut conn)
+}
+
+/// Revokes a key; assertions signed with it are rejected from now on.
+pub fn revoke_service_account_key(
+    conn: &mut PgConnection,
+    key: i32,
+) -> Result<ServiceAccountKey, diesel::result::Error> {
+    diesel::update(service_account_keys::table.filter(service_account_keys::id.eq(key)))
+        .set(service_account_keys::revoked_at.eq(chrono::Utc::now().naive_utc()))
+        .returning(ServiceAccountKey::as_returning())
+        .get_result(conn)
+}
+
+/// Records the ID of an accepted assertion. Returns false if the service
+/// account already used it. Expired IDs are dropped on the way.
+pub fn record_assertion(
+    pool: &DbPool,
+    new: &NewServiceAccountAssertion,
+) -> Result<bool, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::delete(
+        service_account_assertions::table
+            .filter(service_account_assertions::expires_at.lt(chrono::Utc::now().naive_utc())),
+    )
+    .execute(&mut conn)?;
+
+    diesel::insert_into(service_account_assertions::table)
+        .values(new)
+        .on_conflict_do_nothing()
+        .execute(&mut conn)
+        .map(|inserted| inserted == 1)
+}
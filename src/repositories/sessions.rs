@@ -0,0 +1,215 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::sessions::{NewRefreshToken, NewSession, Session};
+use crate::schema::{refresh_tokens, sessions};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// Result of presenting a refresh token.
+pub enum RefreshOutcome {
+    /// The token was valid; it is now used and `new_hash` is the session's current token.
+    Rotated(Session),
+    /// The token had already been used, so it was stolen or replayed.
+    /// The session has been revoked.
+    Reused(Uuid),
+    /// Unknown token, or the session is revoked or expired.
+    Invalid,
+}
+
+/// Creates a session together with its first refresh token.
+pub fn create_session(
+    pool: &DbPool,
+    new: &NewSession,
+    refresh_hash: &str,
+) -> Result<Session, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        let session = diesel::insert_into(sessions::table)
+            .values(new)
+            .get_result::<Session>(conn)?;
+
+        diesel::insert_into(refresh_tokens::table)
+            .values(&NewRefreshToken {
+                session_id: session.id,
+                token_hash: refresh_hash.to_string(),
+            })
+            .execute(conn)?;
+
+        Ok(session)
+    })
+}
+
+/// Finds a session by its ID.
+pub fn find_session_by_id(
+    pool: &DbPool,
+    session_id: Uuid,
+) -> Result<Option<Session>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    sessions::table
+        .filter(sessions::id.eq(session_id))
+        .first::<Session>(&mut conn)
+        .optional()
+}
+
+/// Finds the session a refresh token (used or not) belongs to.
+pub fn find_session_by_refresh_token(
+    pool: &DbPool,
+    token_hash: &str,
+) -> Result<Option<Session>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    refresh_tokens::table
+        .inner_join(sessions::table)
+        .filter(refresh_tokens::token_hash.eq(token_hash))
+        .select(Session::as_select())
+        .first::<Session>(&mut conn)
+        .optional()
+}
+
+/// Exchanges a refresh token for a new one.
+/// Presenting an already used token revokes the whole session (reuse detection).
+pub fn rotate_refresh_token(
+    pool: &DbPool,
+    presented_hash: &str,
+    new_hash: &str,
+    now: NaiveDateTime,
+) -> Result<RefreshOutcome, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        let token = refresh_tokens::table
+            .filter(refresh_tokens::token_hash.eq(presented_hash))
+            .select((
+                refresh_tokens::id,
+                refresh_tokens::session_id,
+                refresh_tokens::used_at,
+            ))
+            .for_update()
+            .first::<(i32, Uuid, Option<NaiveDateTime>)>(conn)
+            .optional()?;
+
+        let Some((token_id, session_id, used_at)) = token else {
+            return Ok(RefreshOutcome::Invalid);
+        };
+
+        if used_at.is_some() {
+            diesel::update(
+                sessions::table
+                    .filter(sessions::id.eq(session_id))
+                    .filter(sessions::revoked_at.is_null()),
+            )
+            .set(sessions::revoked_at.eq(now))
+            .execute(conn)?;
+
+            return Ok(RefreshOutcome::Reused(session_id));
+        }
+
+        let session = sessions::table
+            .filter(sessions::id.eq(session_id))
+            .first::<Session>(conn)?;
+
+        if !session.is_active(now) {
+            return Ok(RefreshOutcome::Invalid);
+        }
+
+        diesel::update(refresh_tokens::table.filter(refresh_tokens::id.eq(token_id)))
+            .set(refresh_tokens::used_at.eq(now))
+            .execute(conn)?;
+
+        diesel::insert_into(refresh_tokens::table)
+            .values(&NewRefreshToken {
+                session_id,
+                token_hash: new_hash.to_string(),
+            })
+            .execute(conn)?;
+
+        Ok(RefreshOutcome::Rotated(session))
+    })
+}
+
+/// Revokes a session; its access and refresh tokens stop working immediately.
+pub fn revoke_session(pool: &DbPool, session_id: Uuid) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(
+        sessions::table
+            .filter(sessions::id.eq(session_id))
+            .filter(sessions::revoked_at.is_null()),
+    )
+    .set(sessions::revoked_at.eq(chrono::Utc::now().naive_utc()))
+    .execute(&mut conn)
+}
+
+/// Revokes every active session of a user, except `keep` if given.
+pub fn revoke_user_sessions(
+    pool: &DbPool,
+    owner: &str,
+    keep: Option<Uuid>,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    revoke_user_sessions_on(&mut conn, owner, keep)
+}
+
+/// `revoke_user_sessions` on an open connection, so it can be part of a
+/// larger transaction.
+pub fn revoke_user_sessions_on(
+    conn: &mut PgConnection,
+    owner: &str,
+    keep: Option<Uuid>,
+) -> Result<usize, diesel::result::Error> {
+    let now = chrono::Utc::now().naive_utc();
+    let target = sessions::table
+        .filter(sessions::user_id.eq(owner))
+        .filter(sessions::revoked_at.is_null());
+
+    match keep {
+        Some(keep) => diesel::update(target.filter(sessions::id.ne(keep)))
+            .set(sessions::revoked_at.eq(now))
+            .execute(conn),
+        None => diesel::update(target)
+            .set(sessions::revoked_at.eq(now))
+            .execute(conn),
+    }
+}
+
+/// Lists the sessions of a user that are active at `now`, most recently used first.
+pub fn list_active_sessions(
+    pool: &DbPool,
+    owner: &str,
+    now: NaiveDateTime,
+) -> Result<Vec<Session>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    sessions::table
+        .filter(sessions::user_id.eq(owner))
+        .filter(sessions::revoked_at.is_null())
+        .filter(sessions::expires_at.gt(now))
+        .order(sessions::last_seen_at.desc())
+        .load::<Session>(&mut conn)
+}
+
+/// Records that a session has just been used.
+pub fn touch_session(pool: &DbPool, session_id: Uuid) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(sessions::table.filter(sessions::id.eq(session_id)))
+        .set(sessions::last_seen_at.eq(chrono::Utc::now().naive_utc()))
+        .execute(&mut conn)
+}
+
+/// Counts sessions that are neither revoked nor expired at `now`.
+pub fn count_active_sessions(
+    pool: &DbPool,
+    now: NaiveDateTime,
+) -> Result<i64, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    sessions::table
+        .filter(sessions::revoked_at.is_null())
+        .filter(sessions::expires_at.gt(now))
+        .count()
+        .get_result(&mut conn)
+}
@@ -0,0 +1,209 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::two_factor::{NewRecoveryCode, NewTotpCredential, TotpCredential};
+use crate::schema::{recovery_codes, totp_credentials};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+/// Finds the TOTP credential of a user, enabled or pending.
+pub fn find_totp_credential(
+    pool: &DbPool,
+    owner: &str,
+) -> Result<Option<TotpCredential>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    totp_credentials::table
+        .filter(totp_credentials::user_id.eq(owner))
+        .select(TotpCredential::as_select())
+        .first::<TotpCredential>(&mut conn)
+        .optional()
+}
+
+/// Stores a new pending secret for a user, replacing any earlier one.
+/// Callers must check that two-factor authentication is not enabled yet.
+pub fn save_pending_totp_credential(
+    pool: &DbPool,
+    new: &NewTotpCredential,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::insert_into(totp_credentials::table)
+        .values(new)
+        .on_conflict(totp_credentials::user_id)
+        .do_update()
+        .set((
+            totp_credentials::secret.eq(&new.secret),
+            totp_credentials::last_used_step.eq(None::<i64>),
+            totp_credentials::failed_attempts.eq(0),
+            totp_credentials::locked_until.eq(None::<NaiveDateTime>),
+        ))
+        .execute(&mut conn)
+}
+
+/// Enables a pending credential and replaces the user's recovery codes.
+pub fn enable_totp_credential(
+    pool: &DbPool,
+    owner: &str,
+    step: i64,
+    code_hashes: &[String],
+) -> Result<(), diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        diesel::update(totp_credentials::table.filter(totp_credentials::user_id.eq(owner)))
+            .set((
+                totp_credentials::enabled_at.eq(chrono::Utc::now().naive_utc()),
+                totp_credentials::last_used_step.eq(step),
+                totp_credentials::failed_attempts.eq(0),
+                totp_credentials::locked_until.eq(None::<NaiveDateTime>),
+            ))
+            .execute(conn)?;
+
+        insert_recovery_codes(conn, owner, code_hashes)
+    })
+}
+
+/// Records an accepted code so it cannot be replayed, and clears failed attempts.
+/// A code's step is only recorded if it is newer than the last accepted one, so
+/// of two requests racing with the same code only one gets a row back; returns
+/// the number of rows updated.
+pub fn record_totp_success(
+    pool: &DbPool,
+    owner: &str,
+    step: Option<i64>,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+    let target = totp_credentials::table.filter(totp_credentials::user_id.eq(owner));
+
+    match step {
+        Some(step) => diesel::update(
+            target.filter(
+                totp_credentials::last_used_step
+                    .is_null()
+                    .or(totp_credentials::last_used_step.lt(step)),
+            ),
+        )
+        .set((
+            totp_credentials::last_used_step.eq(step),
+            totp_credentials::failed_attempts.eq(0),
+            totp_credentials::locked_until.eq(None::<NaiveDateTime>),
+        ))
+        .execute(&mut conn),
+        None => diesel::update(target)
+            .set((
+                totp_credentials::failed_attempts.eq(0),
+                totp_credentials::locked_until.eq(None::<NaiveDateTime>),
+            ))
+            .execute(&mut conn),
+    }
+}
+
+/// Counts a wrong code. Once `max_attempts` is reached the credential is
+/// locked until `lock_until` and the counter starts over.
+pub fn record_totp_failure(
+    pool: &DbPool,
+    owner: &str,
+    max_attempts: i32,
+    lock_until: NaiveDateTime,
+) -> Result<(), diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        let attempts =
+            diesel::update(totp_credentials::table.filter(totp_credentials::user_id.eq(owner)))
+                .set(totp_credentials::failed_attempts.eq(totp_credentials::failed_attempts + 1))
+                .returning(totp_credentials::failed_attempts)
+                .get_result::<i32>(conn)?;
+
+        if attempts >= max_attempts {
+            diesel::update(totp_credentials::table.filter(totp_credentials::user_id.eq(owner)))
+                .set((
+                    totp_credentials::failed_attempts.eq(0),
+                    totp_credentials::locked_until.eq(lock_until),
+                ))
+                .execute(conn)?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Removes the user's TOTP credential and recovery codes.
+pub fn delete_totp_credential(pool: &DbPool, owner: &str) -> Result<(), diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(owner)))
+            .execute(conn)?;
+        diesel::delete(totp_credentials::table.filter(totp_credentials::user_id.eq(owner)))
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+/// Replaces all recovery codes of a user.
+pub fn replace_recovery_codes(
+    pool: &DbPool,
+    owner: &str,
+    code_hashes: &[String],
+) -> Result<(), diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| insert_recovery_codes(conn, owner, code_hashes))
+}
+
+fn insert_recovery_codes(
+    conn: &mut PgConnection,
+    owner: &str,
+    code_hashes: &[String],
+) -> Result<(), diesel::result::Error> {
+    diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(owner)))
+        .execute(conn)?;
+
+    let new_codes: Vec<NewRecoveryCode> = code_hashes
+        .iter()
+        .map(|hash| NewRecoveryCode {
+            user_id: owner.to_string(),
+            code_hash: hash.clone(),
+        })
+        .collect();
+
+    diesel::insert_into(recovery_codes::table)
+        .values(&new_codes)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Marks an unused recovery code as used. Returns false if no such code exists.
+pub fn use_recovery_code(
+    pool: &DbPool,
+    owner: &str,
+    hash: &str,
+) -> Result<bool, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let updated = diesel::update(
+        recovery_codes::table
+            .filter(recovery_codes::user_id.eq(owner))
+            .filter(recovery_codes::code_hash.eq(hash))
+            .filter(recovery_codes::used_at.is_null()),
+    )
+    .set(recovery_codes::used_at.eq(chrono::Utc::now().naive_utc()))
+    .execute(&mut conn)?;
+
+    Ok(updated == 1)
+}
+
+/// Counts the recovery codes a user has not used yet.
+pub fn count_unused_recovery_codes(
+    pool: &DbPool,
+    owner: &str,
+) -> Result<i64, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    recovery_codes::table
+        .filter(recovery_codes::user_id.eq(owner))
+        .filter(recovery_codes::used_at.is_null())
+        .count()
+        .get_result(&mut conn)
+}
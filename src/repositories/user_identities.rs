@@ -0,0 +1,129 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::user_identities::{NewUserIdentity, UserIdentity};
+use crate::schema::user_identities::dsl::*;
+use crate::schema::users;
+use diesel::prelude::*;
+
+/// Result of linking a provider identity to an account.
+pub enum LinkOutcome {
+    /// The identity is now linked to the account.
+    Linked(UserIdentity),
+    /// The identity was already linked to this account.
+    AlreadyLinked,
+    /// The identity belongs to another account.
+    InUse,
+}
+
+/// Result of unlinking an identity from an account.
+pub enum UnlinkOutcome {
+    Unlinked(UserIdentity),
+    /// No such identity on this account.
+    NotFound,
+    /// It is the only way to sign in to the account, so it was kept.
+    LastIdentity,
+}
+
+/// Finds the identity a provider knows a user by, if it is linked to an account.
+pub fn find_identity(
+    pool: &DbPool,
+    provider_val: &str,
+    subject_val: &str,
+) -> Result<Option<UserIdentity>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    user_identities
+        .filter(provider.eq(provider_val))
+        .filter(subject.eq(subject_val))
+        .first::<UserIdentity>(&mut conn)
+        .optional()
+}
+
+/// Lists the identities linked to a user, oldest first.
+pub fn list_user_identities(
+    pool: &DbPool,
+    owner: i32,
+) -> Result<Vec<UserIdentity>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    user_identities
+        .filter(user_id.eq(owner))
+        .order(created_at.asc())
+        .load::<UserIdentity>(&mut conn)
+}
+
+/// Records a login with an identity and the email the provider reported.
+pub fn record_identity_login(
+    pool: &DbPool,
+    identity_id: i32,
+    email_val: Option<&str>,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(user_identities.filter(id.eq(identity_id)))
+        .set((
+            email.eq(email_val),
+            last_login_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(&mut conn)
+}
+
+/// Links an identity to an account unless another account already uses it.
+pub fn link_identity(
+    pool: &DbPool,
+    new: &NewUserIdentity,
+) -> Result<LinkOutcome, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        let existing = user_identities
+            .filter(provider.eq(&new.provider))
+            .filter(subject.eq(&new.subject))
+            .first::<UserIdentity>(conn)
+            .optional()?;
+
+        match existing {
+            Some(identity) if identity.user_id == new.user_id => Ok(LinkOutcome::AlreadyLinked),
+            Some(_) => Ok(LinkOutcome::InUse),
+            None => diesel::insert_into(user_identities)
+                .values(new)
+                .get_result(conn)
+                .map(LinkOutcome::Linked),
+        }
+    })
+}
+
+/// Unlinks an identity from an account, keeping at least one identity.
+/// The user row is locked so concurrent unlinks cannot remove the last one.
+pub fn unlink_identity(
+    pool: &DbPool,
+    owner: i32,
+    identity_id: i32,
+) -> Result<UnlinkOutcome, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        users::table
+            .filter(users::id.eq(owner))
+            .select(users::id)
+            .for_update()
+            .first::<i32>(conn)?;
+
+        let identities = user_identities
+            .filter(user_id.eq(owner))
+            .load::<UserIdentity>(conn)?;
+
+        let Some(identity) = identities
+            .iter()
+            .find(|identity| identity.id == identity_id)
+        else {
+            return Ok(UnlinkOutcome::NotFound);
+        };
+        if identities.len() == 1 {
+            return Ok(UnlinkOutcome::LastIdentity);
+        }
+
+        diesel::delete(user_identities.filter(id.eq(identity.id)))
+            .get_result(conn)
+            .map(UnlinkOutcome::Unlinked)
+    })
+}
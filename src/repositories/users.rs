@@ -1,30 +1,332 @@
 use crate::database::{DbPool, get_db_conn};
+use crate::models::file_changes::{CHANGE_DELETED, CHANGE_SHARED, CHANGE_UPDATED, NewFileChange};
+use crate::models::user_identities::NewUserIdentity;
 use crate::models::users::{NewUser, User};
+use crate::repositories::file_changes::record_file_changes;
 use crate::schema::users::dsl::*;
+use diesel::expression::functions::declare_sql_function;
 use diesel::prelude::*;
+use diesel::sql_types::{Nullable, Text};
+
+#[declare_sql_function]
+extern "SQL" {
+    fn lower(text: Nullable<Text>) -> Nullable<Text>;
+}
+
+/// Creates a user together with the identity they first signed in with.
+pub fn insert_user_with_identity(
+    pool: &DbPool,
+    new_user: &NewUser,
+    provider: &str,
+    subject: &str,
+) -> Result<User, diesel::result::Error> {
+    use crate::schema::user_identities;
 
-/// Inserts a new user and returns the created user
-pub fn insert_user(pool: &DbPool, new_user: &NewUser) -> Result<User, diesel::result::Error> {
     let mut conn = get_db_conn(pool)?;
 
-    diesel::insert_into(users)
-        .values(new_user)
-        .get_result(&mut conn)
+    conn.transaction(|conn| {
+        let user: User = diesel::insert_into(users)
+            .values(new_user)
+            .get_result(conn)?;
+
+        diesel::insert_into(user_identities::table)
+            .values(&NewUserIdentity {
+                user_id: user.id,
+                provider: provider.to_string(),
+                subject: subject.to_string(),
+                email: new_user.email.clone(),
+            })
+            .execute(conn)?;
+
+        Ok(user)
+    })
+}
+
+/// Finds a user by ID.
+pub fn find_user_by_id(pool: &DbPool, user_id: i32) -> Result<User, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    users.filter(id.eq(user_id)).first::<User>(&mut conn)
+}
+
+/// Finds a user by the string form of their ID, as stored in JWTs and `s3_files.user_id`.
+pub fn find_user_by_id_str(pool: &DbPool, user_id: &str) -> Result<User, diesel::result::Error> {
+    let user_id = user_id
+        .parse::<i32>()
+        .map_err(|_| diesel::result::Error::NotFound)?;
+
+    find_user_by_id(pool, user_id)
 }
 
-/// Finds a user by oauth provider and oauth user id.
-pub fn find_user_by_oauth(
+/// Finds the account registered under a verified `email`, ignoring case.
+/// Returns `None` unless exactly one active account has the address, so an
+/// address claimed by several accounts never resolves to any of them.
+pub fn find_user_by_email(
     pool: &DbPool,
-    provider: &str,
-    oauth_id: &str,
+    email_val: &str,
 ) -> Result<Option<User>, diesel::result::Error> {
     let mut conn = get_db_conn(pool)?;
 
-    let user_opt = users
-        .filter(oauth_provider.eq(provider))
-        .filter(oauth_user_id.eq(oauth_id))
-        .first::<User>(&mut conn)
-        .optional()?;
+    let mut found = users
+        .filter(lower(email).eq(lower(email_val)))
+        .filter(email_verified.eq(true))
+        .filter(deleted_at.is_null())
+        .limit(2)
+        .load::<User>(&mut conn)?;
+
+    Ok(if found.len() == 1 { found.pop() } else { None })
+}
+
+/// Updates a user's profile from their login provider and returns the updated user.
+pub fn update_user_profile(
+    pool: &DbPool,
+    user_id: i32,
+    profile: &NewUser,
+) -> Result<User, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    // An empty changeset is an error in diesel, and there is nothing to update
+    if profile.email.is_none() && profile.username.is_none() && profile.avatar_url.is_none() {
+        return users.filter(id.eq(user_id)).first::<User>(&mut conn);
+    }
+
+    diesel::update(users.filter(id.eq(user_id)))
+        .set(profile)
+        .get_result(&mut conn)
+}
+
+/// Enables or disables email notifications for a user and returns the updated user.
+pub fn update_email_notifications(
+    pool: &DbPool,
+    user_id: i32,
+    enabled: bool,
+) -> Result<User, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(users.filter(id.eq(user_id)))
+        .set(email_notifications.eq(enabled))
+        .get_result(&mut conn)
+}
+
+/// Lists all users, oldest first.
+pub fn list_users(pool: &DbPool) -> Result<Vec<User>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    users.order(id.asc()).load::<User>(&mut conn)
+}
+
+/// Suspends a user (`Some(time)`) or reactivates them (`None`) and returns the updated user.
+pub fn set_user_suspended(
+    conn: &mut PgConnection,
+    user_id: i32,
+    suspended: Option<chrono::NaiveDateTime>,
+) -> Result<User, diesel::result::Error> {
+    diesel::update(users.filter(id.eq(user_id)))
+        .set(suspended_at.eq(suspended))
+        .get_result(conn)
+}
+
+/// Changes the role of a user and returns the updated user.
+pub fn update_user_role(
+    conn: &mut PgConnection,
+    user_id: i32,
+    new_role: &str,
+) -> Result<User, diesel::result::Error> {
+    diesel::update(users.filter(id.eq(user_id)))
+        .set(role.eq(new_role))
+        .get_result(conn)
+}
+
+/// Allows or forbids a user to create links and returns the updated user.
+pub fn set_links_disabled(
+    conn: &mut PgConnection,
+    user_id: i32,
+    disabled: bool,
+) -> Result<User, diesel::result::Error> {
+    diesel::update(users.filter(id.eq(user_id)))
+        .set(links_disabled.eq(disabled))
+        .get_result(conn)
+}
+
+/// Counts all users, administrators and suspended users.
+pub fn count_users(pool: &DbPool) -> Result<(i64, i64, i64), diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let total = users.count().get_result(&mut conn)?;
+    let admins = users
+        .filter(role.eq(crate::models::users::ROLE_ADMIN))
+        .count()
+        .get_result(&mut conn)?;
+    let suspended = users
+        .filter(suspended_at.is_not_null())
+        .count()
+        .get_result(&mut conn)?;
+
+    Ok((total, admins, suspended))
+}
+
+/// Removes everything stored about a user and anonymises their row, in one
+/// transaction. Their files are handed to `transfer_to` or deleted; the S3
+/// objects of deleted files must have been removed beforehand. Fails with
+/// `NotFound` if `transfer_to` can no longer receive files.
+pub fn erase_user(
+    pool: &DbPool,
+    user_id: i32,
+    transfer_to: Option<&str>,
+) -> Result<(), diesel::result::Error> {
+    use crate::schema::{
+        access_requests, email_outbox, file_changes, file_permissions, file_requests,
+        personal_access_tokens, recovery_codes, s3_files, saved_search_shares, saved_searches,
+        service_accounts, sessions, totp_credentials, user_identities,
+    };
+
+    let mut conn = get_db_conn(pool)?;
+    let owner = user_id.to_string();
+
+    conn.transaction(|conn| {
+        let user = users.filter(id.eq(user_id)).first::<User>(conn)?;
+        let owned_files = s3_files::table
+            .filter(s3_files::user_id.eq(&owner))
+            .select(s3_files::file_id);
+
+        // Who sees the files changing hands or disappearing, and the owners
+        // of the files the user loses access to
+        let owned_files_shares = file_permissions::table
+            .filter(file_permissions::file_id.eq_any(owned_files))
+            .select((file_permissions::file_id, file_permissions::user_id))
+            .load::<(i32, String)>(conn)?;
+        let shared_with_user = file_permissions::table
+            .inner_join(s3_files::table)
+            .filter(file_permissions::user_id.eq(&owner))
+            .select((s3_files::file_id, s3_files::user_id))
+            .load::<(i32, String)>(conn)?;
+
+        let mut changes: Vec<NewFileChange> = shared_with_user
+            .iter()
+            .map(|(file, file_owner)| NewFileChange::new(file_owner, *file, CHANGE_UPDATED))
+            .collect();
+
+        match transfer_to {
+            Some(new_owner) => {
+                // Locked so the target cannot be deleted or suspended meanwhile
+                let target = new_owner
+                    .parse::<i32>()
+                    .map_err(|_| diesel::result::Error::NotFound)?;
+                let target = users
+                    .filter(id.eq(target))
+                    .for_update()
+                    .first::<User>(conn)?;
+                if !target.can_receive_transfer() {
+                    return Err(diesel::result::Error::NotFound);
+                }
+
+                let transferred = s3_files::table
+                    .filter(s3_files::user_id.eq(&owner))
+                    .select(s3_files::file_id)
+                    .load::<i32>(conn)?;
+                for file in transferred {
+                    let was_shared = owned_files_shares
+                        .iter()
+                        .any(|(shared, grantee)| *shared == file && grantee == new_owner);
+                    let change = if was_shared {
+                        CHANGE_UPDATED
+                    } else {
+                        CHANGE_SHARED
+                    };
+                    changes.push(NewFileChange::new(new_owner, file, change));
+                }
+                changes.extend(
+                    owned_files_shares
+                        .iter()
+                        .filter(|(_, grantee)| grantee != new_owner)
+                        .map(|(file, grantee)| NewFileChange::new(grantee, *file, CHANGE_UPDATED)),
+                );
+
+                // The new owner does not need permissions or requests on their own files
+                diesel::delete(
+                    file_permissions::table
+                        .filter(file_permissions::user_id.eq(new_owner))
+                        .filter(file_permissions::file_id.eq_any(owned_files)),
+                )
+                .execute(conn)?;
+                diesel::delete(
+                    access_requests::table
+                        .filter(access_requests::requester_id.eq(new_owner))
+                        .filter(access_requests::file_id.eq_any(owned_files)),
+                )
+                .execute(conn)?;
+                diesel::update(s3_files::table.filter(s3_files::user_id.eq(&owner)))
+                    .set((
+                        s3_files::user_id.eq(new_owner),
+                        s3_files::file_request_id.eq(None::<i32>),
+                        s3_files::modified_at.eq(diesel::dsl::now),
+                    ))
+                    .execute(conn)?;
+            }
+            // Permissions and access requests on the files are removed by cascade
+            None => {
+                changes.extend(
+                    owned_files_shares
+                        .iter()
+                        .map(|(file, grantee)| NewFileChange::new(grantee, *file, CHANGE_DELETED)),
+                );
+                diesel::delete(s3_files::table.filter(s3_files::user_id.eq(&owner)))
+                    .execute(conn)?;
+            }
+        }
+
+        diesel::delete(file_permissions::table.filter(file_permissions::user_id.eq(&owner)))
+            .execute(conn)?;
+        diesel::delete(access_requests::table.filter(access_requests::requester_id.eq(&owner)))
+            .execute(conn)?;
+        diesel::delete(file_requests::table.filter(file_requests::owner_id.eq(&owner)))
+            .execute(conn)?;
+        // Shares of the user's own searches are removed by cascade
+        diesel::delete(saved_searches::table.filter(saved_searches::owner_id.eq(&owner)))
+            .execute(conn)?;
+        diesel::delete(saved_search_shares::table.filter(saved_search_shares::user_id.eq(&owner)))
+            .execute(conn)?;
+        diesel::delete(
+            personal_access_tokens::table.filter(personal_access_tokens::user_id.eq(&owner)),
+        )
+        .execute(conn)?;
+        diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(&owner)))
+            .execute(conn)?;
+        diesel::delete(totp_credentials::table.filter(totp_credentials::user_id.eq(&owner)))
+            .execute(conn)?;
+        diesel::delete(sessions::table.filter(sessions::user_id.eq(&owner))).execute(conn)?;
+        // Erasing a service account removes its keys and used assertions by cascade
+        diesel::delete(service_accounts::table.filter(service_accounts::user_id.eq(user_id)))
+            .execute(conn)?;
+        diesel::delete(user_identities::table.filter(user_identities::user_id.eq(user_id)))
+            .execute(conn)?;
+        diesel::delete(file_changes::table.filter(file_changes::user_id.eq(&owner)))
+            .execute(conn)?;
+        record_file_changes(conn, &changes)?;
+
+        if let Some(address) = &user.email {
+            diesel::delete(
+                email_outbox::table
+                    .filter(email_outbox::recipient.eq(address))
+                    .filter(email_outbox::status.eq(crate::models::email_outbox::STATUS_PENDING)),
+            )
+            .execute(conn)?;
+        }
+
+        diesel::update(users.filter(id.eq(user_id)))
+            .set((
+                email.eq(None::<String>),
+                email_verified.eq(false),
+                username.eq(None::<String>),
+                avatar_url.eq(None::<String>),
+                email_notifications.eq(false),
+                role.eq(crate::models::users::ROLE_USER),
+                deleted_at.eq(user
+                    .deleted_at
+                    .unwrap_or_else(|| chrono::Utc::now().naive_utc())),
+            ))
+            .execute(conn)?;
 
-    Ok(user_opt)
+        Ok(())
+    })
 }
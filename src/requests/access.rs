@@ -0,0 +1,11 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAccessRequest {
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AccessRequestFilter {
+    pub status: Option<String>,
+}
@@ -0,0 +1,11 @@
+use crate::requests::two_factor::SecondFactor;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccount {
+    /// ID of the user who receives the files; when absent, the files are deleted.
+    pub transfer_to: Option<i32>,
+    /// Required when two-factor authentication is enabled.
+    #[serde(flatten)]
+    pub second_factor: SecondFactor,
+}
@@ -0,0 +1,17 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeUserRole {
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetLinksDisabled {
+    pub links_disabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
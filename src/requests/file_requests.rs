@@ -0,0 +1,11 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateFileRequest {
+    pub title: String,
+    pub description: Option<String>,
+    pub max_file_size: Option<i64>,
+    pub allowed_mime_types: Option<Vec<String>>,
+    pub max_files: Option<i32>,
+    pub expires_in_hours: Option<i64>,
+}
@@ -1,2 +1,11 @@
+pub mod access;
+pub mod account;
+pub mod admin;
+pub mod file_requests;
 pub mod oauth;
 pub mod query;
+pub mod saved_searches;
+pub mod service_accounts;
+pub mod tokens;
+pub mod two_factor;
+pub mod users;
@@ -3,13 +3,6 @@ use serde::Deserialize;
 #[derive(Debug, Deserialize)]
 pub struct OAuthCallbackQuery {
     pub code: Option<String>,
+    pub state: Option<String>,
     pub error: Option<String>,
 }
-
-#[derive(Debug, Deserialize)]
-pub struct GoogleUserInfo {
-    pub sub: String,
-    pub email: Option<String>,
-    pub name: Option<String>,
-    pub picture: Option<String>,
-}
@@ -4,3 +4,37 @@ use serde::Deserialize;
 pub struct SearchQuery {
     pub q: String,
 }
+
+/// Partially typed file name to complete, and how many names to return.
+#[derive(Debug, Deserialize)]
+pub struct SuggestQuery {
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
+/// Paging, sorting and projection of file listings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListQuery {
+    pub page_size: Option<i64>,
+    pub page_token: Option<String>,
+    pub order_by: Option<String>,
+    pub fields: Option<String>,
+    pub include_total: Option<bool>,
+}
+
+/// Position and page size of a changes feed request.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangesQuery {
+    pub page_token: String,
+    pub page_size: Option<i64>,
+}
+
+/// Resume position of an event stream, for clients that cannot send the
+/// `Last-Event-ID` header.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventsQuery {
+    pub last_event_id: Option<String>,
+}
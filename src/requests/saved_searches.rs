@@ -0,0 +1,23 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSavedSearch {
+    pub name: String,
+    pub q: String,
+    pub order_by: Option<String>,
+}
+
+/// Changes made with `PATCH /api/saved-searches/{id}`; omitted fields are
+/// kept, and an empty `order_by` restores the default order.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateSavedSearch {
+    pub name: Option<String>,
+    pub q: Option<String>,
+    pub order_by: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShareSavedSearch {
+    pub email: String,
+}
@@ -0,0 +1,23 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateServiceAccount {
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddServiceAccountKey {
+    pub algorithm: String,
+    /// PEM-encoded public key.
+    pub public_key: String,
+}
+
+/// Form body of `POST /auth/token` (RFC 6749 section 4.5 / RFC 7523).
+#[derive(Debug, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub assertion: Option<String>,
+    /// Space-separated scopes; defaults to all scopes of the service account.
+    pub scope: Option<String>,
+}
@@ -0,0 +1,13 @@
+use serde::Deserialize;
+
+/// A second factor: either a code from the authenticator app or a recovery code.
+#[derive(Debug, Deserialize)]
+pub struct SecondFactor {
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EnableTwoFactor {
+    pub code: String,
+}
@@ -0,0 +1,9 @@
+use serde::Deserialize;
+
+/// Preferences changed with `PATCH /api/me`; omitted fields are kept.
+/// The profile itself comes from the login provider and cannot be edited.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateProfile {
+    pub email_notifications: Option<bool>,
+}
@@ -1,14 +1,24 @@
 // @generated automatically by Diesel CLI.
 
+pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "tsvector", schema = "pg_catalog"))]
+    pub struct Tsvector;
+}
+
 diesel::table! {
     users (id) {
         id -> Int4,
-        oauth_provider -> Varchar,
-        oauth_user_id -> Varchar,
         email -> Nullable<Varchar>,
         username -> Nullable<Varchar>,
         avatar_url -> Nullable<Varchar>,
         created_at -> Timestamp,
+        email_notifications -> Bool,
+        links_disabled -> Bool,
+        role -> Varchar,
+        suspended_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+        email_verified -> Bool,
     }
 }
 
@@ -22,7 +32,291 @@ diesel::table! {
         s3_key -> Varchar,
         etag -> Nullable<Varchar>,
         user_id -> Varchar,
+        uploader_name -> Nullable<Varchar>,
+        uploader_email -> Nullable<Varchar>,
+        file_request_id -> Nullable<Int4>,
+        modified_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    file_permissions (id) {
+        id -> Int4,
+        file_id -> Int4,
+        user_id -> Varchar,
+        role -> Varchar,
+        granted_by -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    access_requests (id) {
+        id -> Int4,
+        file_id -> Int4,
+        requester_id -> Varchar,
+        message -> Nullable<Text>,
+        status -> Varchar,
+        role -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        resolved_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    email_outbox (id) {
+        id -> Int4,
+        recipient -> Varchar,
+        subject -> Varchar,
+        body_text -> Text,
+        body_html -> Text,
+        status -> Varchar,
+        attempts -> Int4,
+        last_error -> Nullable<Text>,
+        next_attempt_at -> Timestamp,
+        created_at -> Timestamp,
+        sent_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    file_requests (id) {
+        id -> Int4,
+        token -> Varchar,
+        owner_id -> Varchar,
+        title -> Varchar,
+        description -> Nullable<Text>,
+        max_file_size -> Nullable<Int8>,
+        allowed_mime_types -> Array<Text>,
+        max_files -> Nullable<Int4>,
+        upload_count -> Int4,
+        expires_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    sessions (id) {
+        id -> Uuid,
+        user_id -> Varchar,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+        user_agent -> Nullable<Varchar>,
+        ip_address -> Nullable<Varchar>,
+        last_seen_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    refresh_tokens (id) {
+        id -> Int4,
+        session_id -> Uuid,
+        token_hash -> Varchar,
+        created_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    personal_access_tokens (id) {
+        id -> Int4,
+        user_id -> Varchar,
+        name -> Varchar,
+        token_hash -> Varchar,
+        token_prefix -> Varchar,
+        scopes -> Array<Text>,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    totp_credentials (user_id) {
+        user_id -> Varchar,
+        secret -> Varchar,
+        enabled_at -> Nullable<Timestamp>,
+        last_used_step -> Nullable<Int8>,
+        failed_attempts -> Int4,
+        locked_until -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    recovery_codes (id) {
+        id -> Int4,
+        user_id -> Varchar,
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    admin_audit_log (id) {
+        id -> Int4,
+        admin_id -> Varchar,
+        action -> Varchar,
+        target_type -> Varchar,
+        target_id -> Varchar,
+        details -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    account_jobs (id) {
+        id -> Uuid,
+        user_id -> Varchar,
+        kind -> Varchar,
+        status -> Varchar,
+        transfer_to -> Nullable<Varchar>,
+        archive_key -> Nullable<Varchar>,
+        archive_size -> Nullable<Int8>,
+        attempts -> Int4,
+        last_error -> Nullable<Text>,
+        next_attempt_at -> Timestamp,
+        created_at -> Timestamp,
+        completed_at -> Nullable<Timestamp>,
+        expires_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    user_identities (id) {
+        id -> Int4,
+        user_id -> Int4,
+        provider -> Varchar,
+        subject -> Varchar,
+        email -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        last_login_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    service_accounts (id) {
+        id -> Int4,
+        user_id -> Int4,
+        owner_id -> Varchar,
+        name -> Varchar,
+        scopes -> Array<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    service_account_keys (id) {
+        id -> Int4,
+        service_account_id -> Int4,
+        key_id -> Varchar,
+        algorithm -> Varchar,
+        public_key -> Text,
+        created_at -> Timestamp,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    service_account_assertions (service_account_id, jti) {
+        service_account_id -> Int4,
+        jti -> Varchar,
+        expires_at -> Timestamp,
     }
 }
 
-diesel::allow_tables_to_appear_in_same_query!(users, s3_files,);
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Tsvector;
+
+    file_contents (file_id) {
+        file_id -> Int4,
+        search_vector -> Tsvector,
+        content -> Nullable<Text>,
+        extraction_error -> Nullable<Text>,
+        attempts -> Int4,
+        next_attempt_at -> Timestamp,
+        indexed_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    saved_searches (id) {
+        id -> Int4,
+        owner_id -> Varchar,
+        name -> Varchar,
+        query -> Text,
+        order_by -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    saved_search_shares (saved_search_id, user_id) {
+        saved_search_id -> Int4,
+        user_id -> Varchar,
+        shared_by -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    file_changes (id) {
+        id -> Int8,
+        tx_id -> Int8,
+        user_id -> Varchar,
+        file_id -> Int4,
+        change_type -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    instance_settings (name) {
+        name -> Varchar,
+        value -> Text,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::joinable!(file_contents -> s3_files (file_id));
+diesel::joinable!(file_permissions -> s3_files (file_id));
+diesel::joinable!(access_requests -> s3_files (file_id));
+diesel::joinable!(s3_files -> file_requests (file_request_id));
+diesel::joinable!(refresh_tokens -> sessions (session_id));
+diesel::joinable!(user_identities -> users (user_id));
+diesel::joinable!(service_accounts -> users (user_id));
+diesel::joinable!(service_account_keys -> service_accounts (service_account_id));
+diesel::joinable!(service_account_assertions -> service_accounts (service_account_id));
+diesel::joinable!(saved_search_shares -> saved_searches (saved_search_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    users,
+    s3_files,
+    file_permissions,
+    access_requests,
+    email_outbox,
+    file_requests,
+    sessions,
+    refresh_tokens,
+    personal_access_tokens,
+    totp_credentials,
+    recovery_codes,
+    admin_audit_log,
+    account_jobs,
+    user_identities,
+    service_accounts,
+    service_account_keys,
+    service_account_assertions,
+    file_contents,
+    saved_searches,
+    saved_search_shares,
+    file_changes,
+    instance_settings,
+);
@@ -0,0 +1,332 @@
+//! Plain text extraction from uploaded documents, for the search index.
+
+use calamine::{Data, Reader as _, Xlsx};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use std::io::{Cursor, Read, Write};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+/// At most this many characters of a document are indexed.
+const MAX_CONTENT_CHARS: usize = 200_000;
+/// Spreadsheet markup is verbose, so a workbook may unpack to this many
+/// times `MAX_CONTENT_CHARS` bytes of XML in total.
+const SPREADSHEET_XML_FACTOR: usize = 64;
+
+const PDF: &str = "application/pdf";
+const DOCX: &str = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+const ODT: &str = "application/vnd.oasis.opendocument.text";
+const XLSX: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+/// Document formats text can be extracted from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    PlainText,
+    Pdf,
+    Docx,
+    Odt,
+    Xlsx,
+}
+
+impl DocumentKind {
+    /// Picks the format from the MIME type, falling back to the file
+    /// extension for uploads stored as `application/octet-stream`.
+    pub fn detect(mime_type: &str, name: &str) -> Option<Self> {
+        let mime_type = mime_type.split(';').next().unwrap_or("").trim();
+        let extension = name.rsplit_once('.').map(|(_, ext)| ext.to_lowercase());
+
+        match (mime_type, extension.as_deref()) {
+            (m, _) if m.starts_with("text/") => Some(DocumentKind::PlainText),
+            (PDF, _) | (_, Some("pdf")) => Some(DocumentKind::Pdf),
+            (DOCX, _) | (_, Some("docx")) => Some(DocumentKind::Docx),
+            (ODT, _) | (_, Some("odt")) => Some(DocumentKind::Odt),
+            (XLSX, _) | (_, Some("xlsx")) => Some(DocumentKind::Xlsx),
+            _ => None,
+        }
+    }
+}
+
+/// Extracts the text of a document. The result is cleaned of control
+/// characters and cut to `MAX_CONTENT_CHARS`.
+pub fn extract_text(kind: DocumentKind, data: &[u8]) -> Result<String, String> {
+    let text = match kind {
+        DocumentKind::PlainText => String::from_utf8_lossy(data).into_owned(),
+        // The PDF parser panics on some malformed files
+        DocumentKind::Pdf => catch_unwind(AssertUnwindSafe(|| {
+            pdf_extract::extract_text_from_mem(data)
+        }))
+        .map_err(|_| "PDF parser crashed".to_string())?
+        .map_err(|e| format!("Invalid PDF: {}", e))?,
+        DocumentKind::Docx => xml_text(&zip_entry(data, "word/document.xml")?)?,
+        DocumentKind::Odt => xml_text(&zip_entry(data, "content.xml")?)?,
+        DocumentKind::Xlsx => spreadsheet_text(data)?,
+    };
+
+    Ok(clean(&text))
+}
+
+/// Reads one entry of a zip based document, refusing entries that unpack
+/// to more than `MAX_CONTENT_CHARS` times eight bytes of markup.
+fn zip_entry(data: &[u8], name: &str) -> Result<Vec<u8>, String> {
+    let mut archive =
+        zip::ZipArchive::new(Cursor::new(data)).map_err(|e| format!("Invalid archive: {}", e))?;
+    let entry = archive
+        .by_name(name)
+        .map_err(|e| format!("Missing {}: {}", name, e))?;
+
+    let limit = (MAX_CONTENT_CHARS * 8) as u64;
+    let mut xml = Vec::new();
+    entry
+        .take(limit + 1)
+        .read_to_end(&mut xml)
+        .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+    if xml.len() as u64 > limit {
+        return Err(format!("{} is too large", name));
+    }
+
+    Ok(xml)
+}
+
+/// Collects the text of a WordprocessingML or OpenDocument body, with a line
+/// break after every paragraph and heading.
+fn xml_text(xml: &[u8]) -> Result<String, String> {
+    let mut reader = Reader::from_reader(xml);
+    let mut buf = Vec::new();
+    let mut text = String::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Text(e)) => {
+                text.push_str(&e.unescape().map_err(|e| format!("Invalid XML: {}", e))?)
+            }
+            Ok(Event::End(e)) if matches!(e.local_name().as_ref(), b"p" | b"h") => text.push('\n'),
+            Ok(Event::Empty(e)) => match e.local_name().as_ref() {
+                b"tab" => text.push('\t'),
+                b"s" => text.push(' '),
+                b"br" | b"cr" | b"line-break" => text.push('\n'),
+                _ => {}
+            },
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => return Err(format!("Invalid XML: {}", e)),
+        }
+        if text.len() > MAX_CONTENT_CHARS * 4 {
+            break;
+        }
+        buf.clear();
+    }
+
+    Ok(text)
+}
+
+/// Copies the XML parts of a zip based document into a new, uncompressed
+/// archive, refusing documents whose parts unpack to more than `limit` bytes
+/// together. The sizes in the archive's directory are not trusted: every
+/// part is read, and reading stops at the limit.
+fn bounded_xml_archive(data: &[u8], limit: u64) -> Result<Vec<u8>, String> {
+    let mut archive =
+        zip::ZipArchive::new(Cursor::new(data)).map_err(|e| format!("Invalid archive: {}", e))?;
+    let mut copy = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options =
+        zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let mut remaining = limit;
+
+    for index in 0..archive.len() {
+        let entry = archive
+            .by_index(index)
+            .map_err(|e| format!("Invalid archive: {}", e))?;
+        let name = entry.name().to_string();
+        // Images and other embedded files hold no text
+        if !(name.ends_with(".xml") || name.ends_with(".rels")) {
+            continue;
+        }
+
+        let mut xml = Vec::new();
+        entry
+            .take(remaining + 1)
+            .read_to_end(&mut xml)
+            .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+        if xml.len() as u64 > remaining {
+            return Err("Document is too large".to_string());
+        }
+        remaining -= xml.len() as u64;
+
+        copy.start_file(name.as_str(), options)
+            .map_err(|e| format!("Failed to copy {}: {}", name, e))?;
+        copy.write_all(&xml)
+            .map_err(|e| format!("Failed to copy {}: {}", name, e))?;
+    }
+
+    copy.finish()
+        .map(Cursor::into_inner)
+        .map_err(|e| format!("Failed to copy archive: {}", e))
+}
+
+/// Collects the cell values of every sheet, one row per line.
+fn spreadsheet_text(data: &[u8]) -> Result<String, String> {
+    // The upload limit applies to the compressed file, so the sheets are
+    // unpacked under a limit of their own before they are parsed
+    let data = bounded_xml_archive(data, (MAX_CONTENT_CHARS * SPREADSHEET_XML_FACTOR) as u64)?;
+    let mut workbook: Xlsx<_> =
+        Xlsx::new(Cursor::new(data)).map_err(|e| format!("Invalid spreadsheet: {}", e))?;
+    let mut text = String::new();
+
+    for sheet in workbook.sheet_names() {
+        let range = workbook
+            .worksheet_range(&sheet)
+            .map_err(|e| format!("Invalid sheet '{}': {}", sheet, e))?;
+        text.push_str(&sheet);
+        text.push('\n');
+
+        for row in range.rows() {
+            let cells: Vec<String> = row
+                .iter()
+                .filter(|cell| !matches!(cell, Data::Empty))
+                .map(|cell| cell.to_string())
+                .collect();
+            if !cells.is_empty() {
+                text.push_str(&cells.join("\t"));
+                text.push('\n');
+            }
+        }
+        if text.len() > MAX_CONTENT_CHARS * 4 {
+            break;
+        }
+    }
+
+    Ok(text)
+}
+
+/// Replaces control characters (which include the snippet highlight
+/// markers) with spaces and cuts the text to `MAX_CONTENT_CHARS`.
+fn clean(text: &str) -> String {
+    text.chars()
+        .take(MAX_CONTENT_CHARS)
+        .map(|c| match c {
+            '\n' | '\t' => c,
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a deflated zip archive of `entries`.
+    fn archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        for (name, data) in entries {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn kinds_are_detected_by_mime_type_then_extension() {
+        assert_eq!(
+            DocumentKind::detect("text/plain; charset=utf-8", "notes"),
+            Some(DocumentKind::PlainText)
+        );
+        assert_eq!(DocumentKind::detect(PDF, "scan"), Some(DocumentKind::Pdf));
+        assert_eq!(
+            DocumentKind::detect("application/octet-stream", "Report.DOCX"),
+            Some(DocumentKind::Docx)
+        );
+        assert_eq!(
+            DocumentKind::detect("application/octet-stream", "budget.xlsx"),
+            Some(DocumentKind::Xlsx)
+        );
+        assert_eq!(DocumentKind::detect("image/png", "photo.png"), None);
+    }
+
+    #[test]
+    fn document_xml_becomes_lines_of_text() {
+        let xml = br#"<w:document xmlns:w="w"><w:body>
+            <w:p><w:r><w:t>Hello</w:t></w:r><w:r><w:tab/><w:t>world &amp; all</w:t></w:r></w:p>
+            <w:p><w:r><w:t>Second</w:t></w:r></w:p>
+        </w:body></w:document>"#;
+
+        let text = xml_text(xml).unwrap();
+        let lines: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect();
+        assert_eq!(lines, ["Hello\tworld & all", "Second"]);
+    }
+
+    #[test]
+    fn docx_text_is_read_from_the_document_part() {
+        let docx = archive(&[(
+            "word/document.xml",
+            b"<w:document xmlns:w=\"w\"><w:p><w:t>Quarterly plan</w:t></w:p></w:document>",
+        )]);
+
+        assert_eq!(
+            extract_text(DocumentKind::Docx, &docx).unwrap().trim(),
+            "Quarterly plan"
+        );
+    }
+
+    #[test]
+    fn oversized_zip_entries_are_refused() {
+        let bomb = vec![b' '; MAX_CONTENT_CHARS * 8 + 1];
+        let docx = archive(&[("word/document.xml", &bomb)]);
+
+        assert!(docx.len() < bomb.len() / 100);
+        assert!(zip_entry(&docx, "word/document.xml").is_err());
+    }
+
+    #[test]
+    fn bounded_archive_keeps_only_xml_parts() {
+        let data = archive(&[
+            ("xl/workbook.xml", b"<workbook/>"),
+            ("_rels/.rels", b"<Relationships/>"),
+            ("xl/media/image1.png", b"not text"),
+        ]);
+
+        let copy = bounded_xml_archive(&data, 1024).unwrap();
+        let mut names: Vec<String> = zip::ZipArchive::new(Cursor::new(copy))
+            .unwrap()
+            .file_names()
+            .map(str::to_string)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["_rels/.rels", "xl/workbook.xml"]);
+    }
+
+    #[test]
+    fn bounded_archive_refuses_parts_over_the_limit_together() {
+        let sheet = vec![b' '; 600];
+        let data = archive(&[
+            ("xl/worksheets/sheet1.xml", &sheet),
+            ("xl/worksheets/sheet2.xml", &sheet),
+        ]);
+
+        assert!(bounded_xml_archive(&data, 1200).is_ok());
+        assert!(bounded_xml_archive(&data, 1199).is_err());
+    }
+
+    #[test]
+    fn spreadsheet_bombs_are_refused_before_parsing() {
+        let sheet = vec![b' '; MAX_CONTENT_CHARS * SPREADSHEET_XML_FACTOR + 1];
+        let xlsx = archive(&[("xl/worksheets/sheet1.xml", &sheet)]);
+
+        assert_eq!(
+            extract_text(DocumentKind::Xlsx, &xlsx),
+            Err("Document is too large".to_string())
+        );
+    }
+
+    #[test]
+    fn text_is_cleaned_and_cut() {
+        assert_eq!(clean("a\u{1}b\tc\nd"), "a b\tc\nd");
+
+        let long = "x".repeat(MAX_CONTENT_CHARS + 10);
+        assert_eq!(clean(&long).chars().count(), MAX_CONTENT_CHARS);
+    }
+}
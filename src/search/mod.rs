@@ -0,0 +1,135 @@
+pub mod extract;
+pub mod query;
+pub mod sql;
+pub mod worker;
+
+use self::sql::{
+    Concat, Regconfig, TsvectorExpressionMethods, Weight, set_config, setweight, to_tsvector,
+    websearch_to_tsquery,
+};
+use diesel::dsl::{AsExprOf, Cast};
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Text};
+use std::env;
+use std::sync::OnceLock;
+
+/// Highlighted terms are wrapped in these control characters by `ts_headline`
+/// and turned into `<mark>` tags once the rest of the snippet is escaped.
+const MARK_START: char = '\u{2}';
+const MARK_END: char = '\u{3}';
+
+/// `ts_headline` options for search result snippets.
+pub const HEADLINE_OPTIONS: &str =
+    "StartSel=\u{2}, StopSel=\u{3}, MaxWords=30, MinWords=10, MaxFragments=2";
+
+pub type Config = Cast<AsExprOf<&'static str, Text>, Regconfig>;
+pub type WeightedVector = setweight<
+    to_tsvector<Config, AsExprOf<String, Text>>,
+    Cast<AsExprOf<&'static str, Text>, Weight>,
+>;
+pub type DocumentVector = Concat<WeightedVector, WeightedVector>;
+pub type SetFuzzyThreshold =
+    set_config<AsExprOf<&'static str, Text>, AsExprOf<String, Text>, AsExprOf<bool, Bool>>;
+
+/// The Postgres text search configuration used to index and query files
+/// (`SEARCH_LANGUAGE`, `english` by default).
+pub fn language() -> &'static str {
+    static LANGUAGE: OnceLock<String> = OnceLock::new();
+
+    LANGUAGE.get_or_init(|| {
+        env::var("SEARCH_LANGUAGE")
+            .ok()
+            .map(|v| v.trim().to_lowercase())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| "english".to_string())
+    })
+}
+
+/// Files larger than this are indexed by name only.
+pub fn max_extract_bytes() -> i64 {
+    env::var("SEARCH_MAX_EXTRACT_BYTES")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(20 * 1024 * 1024)
+}
+
+/// How similar a word of a file name must be to the search text for the file
+/// to match despite typos (`SEARCH_FUZZY_THRESHOLD`, 0 to 1, default 0.3).
+/// "reciept" is 0.375 similar to "receipt".
+pub fn fuzzy_threshold() -> f32 {
+    env::var("SEARCH_FUZZY_THRESHOLD")
+        .ok()
+        .and_then(|v| v.parse::<f32>().ok())
+        .filter(|v| (0.0..=1.0).contains(v))
+        .unwrap_or(0.3)
+}
+
+/// Sets `fuzzy_threshold` as the threshold of the `<%` operator for the rest
+/// of the current transaction.
+pub fn set_fuzzy_threshold() -> SetFuzzyThreshold {
+    set_config(
+        "pg_trgm.word_similarity_threshold",
+        fuzzy_threshold().to_string(),
+        true,
+    )
+}
+
+/// The words of a search box query that file names are fuzzily matched
+/// against: quotes are dropped, and so are `or` and excluded `-words`.
+pub fn fuzzy_terms(text: &str) -> String {
+    text.split_whitespace()
+        .filter(|word| !word.starts_with('-') && !word.eq_ignore_ascii_case("or"))
+        .map(|word| word.trim_matches('"'))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn config() -> Config {
+    language().into_sql::<Text>().cast::<Regconfig>()
+}
+
+fn weighted(text: String, weight: &'static str) -> WeightedVector {
+    setweight(
+        to_tsvector(config(), text),
+        weight.into_sql::<Text>().cast::<Weight>(),
+    )
+}
+
+/// The search vector of a file: its name weighted above its contents, so
+/// name matches rank first. Punctuation in the name is indexed as spaces,
+/// so "report" finds `quarterly_report.pdf`.
+pub fn document_vector(name: &str, content: String) -> DocumentVector {
+    let name_terms = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+
+    weighted(name_terms, "A").concat(weighted(content, "B"))
+}
+
+/// Parses a search box query (`"exact phrase"`, `or`, `-excluded`).
+pub fn search_query(text: String) -> websearch_to_tsquery<Config, AsExprOf<String, Text>> {
+    websearch_to_tsquery(config(), text)
+}
+
+/// HTML-escapes a `ts_headline` snippet and marks the matched terms with
+/// `<mark>`, so the result can be inserted into a page as is.
+pub fn render_highlight(headline: &str) -> String {
+    let mut html = String::with_capacity(headline.len());
+
+    for c in headline.chars() {
+        match c {
+            MARK_START => html.push_str("<mark>"),
+            MARK_END => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+
+    html
+}
@@ -0,0 +1,574 @@
+//! The search box grammar: free text mixed with `operator:value` filters.
+//!
+//! ```text
+//! quarterly report name:budget type:pdf size>10MB modified>=2025-01-01 owner:me
+//! ```
+//!
+//! Free text (including `"phrases"`, `or` and `-excluded` words) is matched
+//! against the full-text index; every operator narrows the results further.
+
+use chrono::{NaiveDate, NaiveDateTime};
+use std::fmt;
+
+/// Operators the grammar knows about, in the order they are documented.
+const OPERATORS: &[&str] = &["name", "type", "size", "modified", "owner", "is", "in"];
+
+/// A parsed search query.
+#[derive(Debug, Default, PartialEq)]
+pub struct ParsedQuery {
+    /// Free text for `websearch_to_tsquery`, if there was any.
+    pub text: Option<String>,
+    pub filters: Vec<Filter>,
+}
+
+impl ParsedQuery {
+    pub fn is_empty(&self) -> bool {
+        self.text.is_none() && self.filters.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Owner {
+    Me,
+    Email(String),
+}
+
+/// One operator of a query, ready to be turned into a database filter.
+#[derive(Debug, PartialEq)]
+pub enum Filter {
+    /// `name:` — the file name contains the value, ignoring case.
+    Name(String),
+    /// `type:` — the MIME type matches one of these `LIKE` patterns.
+    MimeType(Vec<String>),
+    /// `size>` and friends, in bytes.
+    Size(Comparison, i64),
+    /// `modified>` and friends, compared with the last change to the file's
+    /// metadata.
+    Modified(Comparison, NaiveDateTime),
+    /// `owner:`.
+    Owner(Owner),
+}
+
+/// Why a query could not be parsed, and where.
+#[derive(Debug, PartialEq)]
+pub struct QueryError {
+    /// Character offset of the offending token in the query.
+    pub position: usize,
+    pub token: String,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (at '{}', position {})",
+            self.message, self.token, self.position
+        )
+    }
+}
+
+/// A whitespace separated piece of the query. Quoted parts may contain
+/// spaces; `value` is the raw text with the quotes removed.
+#[derive(Debug)]
+struct Token {
+    position: usize,
+    raw: String,
+    value: String,
+}
+
+impl Token {
+    fn error(&self, message: impl Into<String>) -> QueryError {
+        QueryError {
+            position: self.position,
+            token: self.raw.clone(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Parses a search box query.
+pub fn parse(query: &str) -> Result<ParsedQuery, QueryError> {
+    let mut parsed = ParsedQuery::default();
+    let mut text = Vec::new();
+
+    for token in tokenize(query)? {
+        match split_operator(&token) {
+            Some((key, comparison, value)) => parsed
+                .filters
+                .extend(parse_operator(&token, &key, comparison, &value)?),
+            None => text.push(token.raw),
+        }
+    }
+
+    if !text.is_empty() {
+        parsed.text = Some(text.join(" "));
+    }
+    Ok(parsed)
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().enumerate().peekable();
+
+    while let Some(&(position, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut raw = String::new();
+        let mut value = String::new();
+        let mut quote_start = None;
+
+        while let Some(&(i, c)) = chars.peek() {
+            if c.is_whitespace() && quote_start.is_none() {
+                break;
+            }
+            chars.next();
+            raw.push(c);
+
+            if c == '"' {
+                quote_start = match quote_start {
+                    Some(_) => None,
+                    None => Some(i),
+                };
+            } else {
+                value.push(c);
+            }
+        }
+
+        if let Some(quote) = quote_start {
+            return Err(QueryError {
+                position: quote,
+                token: raw,
+                message: "Unterminated quote".to_string(),
+            });
+        }
+        tokens.push(Token {
+            position,
+            raw,
+            value,
+        });
+    }
+
+    Ok(tokens)
+}
+
+/// Splits `key:value`, `key>value`, `key>=value`, `key<value` and
+/// `key<=value`. Tokens without an alphabetic key before the operator, and
+/// quoted phrases, are free text.
+fn split_operator(token: &Token) -> Option<(String, Option<Comparison>, String)> {
+    let operator_at = token.raw.find([':', '<', '>', '"'])?;
+    let key = &token.raw[..operator_at];
+    let key_chars = key.strip_prefix('-').unwrap_or(key);
+    if key_chars.is_empty() || !key_chars.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let rest = &token.raw[operator_at..];
+    let (comparison, operator_len) = match rest.as_bytes() {
+        [b'>', b'=', ..] => (Some(Comparison::GreaterOrEqual), 2),
+        [b'<', b'=', ..] => (Some(Comparison::LessOrEqual), 2),
+        [b'>', ..] => (Some(Comparison::Greater), 1),
+        [b'<', ..] => (Some(Comparison::Less), 1),
+        [b':', ..] => (None, 1),
+        _ => return None,
+    };
+
+    // The value is everything after the operator, without its quotes
+    let value = token.value[operator_at + operator_len..].to_string();
+    Some((key.to_lowercase(), comparison, value))
+}
+
+fn parse_operator(
+    token: &Token,
+    key: &str,
+    comparison: Option<Comparison>,
+    value: &str,
+) -> Result<Vec<Filter>, QueryError> {
+    if key.starts_with('-') {
+        return Err(token.error("Operators cannot be negated"));
+    }
+    if !OPERATORS.contains(&key) {
+        return Err(token.error(format!(
+            "Unknown operator '{}', expected one of: {}",
+            key,
+            OPERATORS.join(", ")
+        )));
+    }
+    if value.trim().is_empty() {
+        return Err(token.error(format!("Missing value for '{}'", key)));
+    }
+
+    if comparison.is_some() && !matches!(key, "size" | "modified") {
+        return Err(token.error(format!("'{}' only supports '{}:'", key, key)));
+    }
+
+    match key {
+        "name" => Ok(vec![Filter::Name(value.to_string())]),
+        "type" => parse_type(token, value).map(|patterns| vec![Filter::MimeType(patterns)]),
+        "size" => match comparison {
+            Some(comparison) => {
+                parse_size(token, value).map(|size| vec![Filter::Size(comparison, size)])
+            }
+            None => Err(token.error("Use 'size>' or 'size<' to filter by size")),
+        },
+        "modified" => parse_modified(token, comparison, value),
+        "owner" => parse_owner(token, value).map(|owner| vec![Filter::Owner(owner)]),
+        "is" if value.eq_ignore_ascii_case("starred") => {
+            Err(token.error("Starring files is not supported"))
+        }
+        "is" => Err(token.error(format!("Unknown filter 'is:{}'", value))),
+        "in" => Err(token.error("Files are not organised in folders, so 'in:' is not supported")),
+        _ => unreachable!("every operator in OPERATORS is handled"),
+    }
+}
+
+/// Maps a `type:` value to `LIKE` patterns over the MIME type.
+fn parse_type(token: &Token, value: &str) -> Result<Vec<String>, QueryError> {
+    let value = value.to_lowercase();
+    let patterns: &[&str] = match value.as_str() {
+        "pdf" => &["application/pdf"],
+        "document" => &[
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            "application/msword",
+            "application/vnd.oasis.opendocument.text",
+            "application/rtf",
+        ],
+        "spreadsheet" => &[
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            "application/vnd.ms-excel",
+            "application/vnd.oasis.opendocument.spreadsheet",
+            "text/csv",
+        ],
+        "presentation" => &[
+            "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+            "application/vnd.ms-powerpoint",
+            "application/vnd.oasis.opendocument.presentation",
+        ],
+        "archive" => &[
+            "application/zip",
+            "application/gzip",
+            "application/x-tar",
+            "application/x-7z-compressed",
+            "application/vnd.rar",
+        ],
+        "image" | "video" | "audio" | "text" => {
+            return Ok(vec![format!("{}/%", value)]);
+        }
+        "folder" => return Err(token.error("Files are not organised in folders")),
+        mime if is_mime_pattern(mime) => {
+            return Ok(vec![mime.replace('*', "%")]);
+        }
+        _ => {
+            return Err(token.error(format!(
+                "Unknown file type '{}', expected pdf, document, spreadsheet, presentation, \
+                 archive, image, video, audio, text or a MIME type such as image/png",
+                value
+            )));
+        }
+    };
+
+    Ok(patterns.iter().map(|p| p.to_string()).collect())
+}
+
+/// `type/subtype` or `type/*`, with no characters that mean something to `LIKE`.
+fn is_mime_pattern(value: &str) -> bool {
+    let Some((kind, subtype)) = value.split_once('/') else {
+        return false;
+    };
+    let is_name = |s: &str| {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-'))
+    };
+
+    is_name(kind) && (subtype == "*" || is_name(subtype))
+}
+
+/// Parses `10MB`, `1.5GB` or `512` (bytes). Units are binary: 1KB is 1024 bytes.
+fn parse_size(token: &Token, value: &str) -> Result<i64, QueryError> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| token.error(format!("Invalid size '{}'", value)))?;
+    let multiplier: f64 = match unit.to_uppercase().as_str() {
+        "" | "B" => 1.0,
+        "K" | "KB" => 1024.0,
+        "M" | "MB" => 1024.0 * 1024.0,
+        "G" | "GB" => 1024.0 * 1024.0 * 1024.0,
+        "T" | "TB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => {
+            return Err(token.error(format!(
+                "Unknown size unit '{}', expected B, KB, MB, GB or TB",
+                unit
+            )));
+        }
+    };
+
+    let bytes = (number * multiplier).round();
+    if bytes > i64::MAX as f64 {
+        return Err(token.error(format!("Size '{}' is too large", value)));
+    }
+    Ok(bytes as i64)
+}
+
+/// Parses a `YYYY-MM-DD` date. Bounds are whole days: `modified>2025-01-01`
+/// starts on January 2nd and `modified:2025-01-01` means during that day.
+fn parse_modified(
+    token: &Token,
+    comparison: Option<Comparison>,
+    value: &str,
+) -> Result<Vec<Filter>, QueryError> {
+    let day = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| token.error(format!("Invalid date '{}', expected YYYY-MM-DD", value)))?;
+    let start = day.and_hms_opt(0, 0, 0).expect("midnight is a valid time");
+    let next_day = day
+        .succ_opt()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .ok_or_else(|| token.error(format!("Date '{}' is out of range", value)))?;
+
+    Ok(match comparison {
+        Some(Comparison::Less) => vec![Filter::Modified(Comparison::Less, start)],
+        Some(Comparison::LessOrEqual) => vec![Filter::Modified(Comparison::Less, next_day)],
+        Some(Comparison::Greater) => vec![Filter::Modified(Comparison::GreaterOrEqual, next_day)],
+        Some(Comparison::GreaterOrEqual) => {
+            vec![Filter::Modified(Comparison::GreaterOrEqual, start)]
+        }
+        None => vec![
+            Filter::Modified(Comparison::GreaterOrEqual, start),
+            Filter::Modified(Comparison::Less, next_day),
+        ],
+    })
+}
+
+fn parse_owner(token: &Token, value: &str) -> Result<Owner, QueryError> {
+    if value.eq_ignore_ascii_case("me") {
+        return Ok(Owner::Me);
+    }
+    if value.contains('@') {
+        return Ok(Owner::Email(value.to_string()));
+    }
+
+    Err(token.error("'owner:' expects 'me' or an email address"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDateTime {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    }
+
+    fn error_at(query: &str) -> (usize, String) {
+        let error = parse(query).unwrap_err();
+        (error.position, error.token)
+    }
+
+    #[test]
+    fn free_text_only() {
+        let parsed = parse("  quarterly   report ").unwrap();
+        assert_eq!(parsed.text.as_deref(), Some("quarterly report"));
+        assert!(parsed.filters.is_empty());
+    }
+
+    #[test]
+    fn empty_query() {
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("   ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn web_search_syntax_stays_free_text() {
+        let parsed = parse(r#""exact phrase" cats or dogs -birds"#).unwrap();
+        assert_eq!(
+            parsed.text.as_deref(),
+            Some(r#""exact phrase" cats or dogs -birds"#)
+        );
+        assert!(parsed.filters.is_empty());
+    }
+
+    #[test]
+    fn text_that_only_looks_like_an_operator() {
+        let parsed = parse("meeting 10:30 \"re: budget\"").unwrap();
+        assert_eq!(parsed.text.as_deref(), Some("meeting 10:30 \"re: budget\""));
+        assert!(parsed.filters.is_empty());
+    }
+
+    #[test]
+    fn full_example() {
+        let parsed =
+            parse("report name:budget type:pdf size>10MB modified>2025-01-01 owner:me").unwrap();
+        assert_eq!(parsed.text.as_deref(), Some("report"));
+        assert_eq!(
+            parsed.filters,
+            vec![
+                Filter::Name("budget".to_string()),
+                Filter::MimeType(vec!["application/pdf".to_string()]),
+                Filter::Size(Comparison::Greater, 10 * 1024 * 1024),
+                Filter::Modified(Comparison::GreaterOrEqual, date("2025-01-02")),
+                Filter::Owner(Owner::Me),
+            ]
+        );
+    }
+
+    #[test]
+    fn operator_keys_ignore_case() {
+        let parsed = parse("NAME:Budget Owner:ME").unwrap();
+        assert_eq!(
+            parsed.filters,
+            vec![Filter::Name("Budget".to_string()), Filter::Owner(Owner::Me)]
+        );
+    }
+
+    #[test]
+    fn quoted_values() {
+        let parsed = parse(r#"name:"q2 budget" owner:"alice@example.com""#).unwrap();
+        assert_eq!(parsed.text, None);
+        assert_eq!(
+            parsed.filters,
+            vec![
+                Filter::Name("q2 budget".to_string()),
+                Filter::Owner(Owner::Email("alice@example.com".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_quote() {
+        let error = parse(r#"report name:"q2 budget"#).unwrap_err();
+        assert_eq!(error.position, 12);
+        assert_eq!(error.message, "Unterminated quote");
+    }
+
+    #[test]
+    fn types() {
+        let patterns = |q: &str| match parse(q).unwrap().filters.remove(0) {
+            Filter::MimeType(patterns) => patterns,
+            other => panic!("unexpected filter {:?}", other),
+        };
+
+        assert_eq!(patterns("type:image"), vec!["image/%"]);
+        assert_eq!(patterns("type:PDF"), vec!["application/pdf"]);
+        assert_eq!(patterns("type:image/png"), vec!["image/png"]);
+        assert_eq!(patterns("type:video/*"), vec!["video/%"]);
+        assert!(patterns("type:spreadsheet").contains(&"text/csv".to_string()));
+    }
+
+    #[test]
+    fn invalid_types() {
+        assert_eq!(error_at("a type:movie"), (2, "type:movie".to_string()));
+        assert_eq!(error_at("type:folder").0, 0);
+        assert_eq!(error_at("type:image/p%g").0, 0);
+        assert_eq!(error_at("type:image/").0, 0);
+    }
+
+    #[test]
+    fn sizes() {
+        let size = |q: &str| match parse(q).unwrap().filters.remove(0) {
+            Filter::Size(comparison, bytes) => (comparison, bytes),
+            other => panic!("unexpected filter {:?}", other),
+        };
+
+        assert_eq!(size("size>512"), (Comparison::Greater, 512));
+        assert_eq!(size("size<1kb"), (Comparison::Less, 1024));
+        assert_eq!(
+            size("size>=1.5GB"),
+            (Comparison::GreaterOrEqual, 1610612736)
+        );
+        assert_eq!(size("size<=2T"), (Comparison::LessOrEqual, 2 << 40));
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        assert_eq!(error_at("big size>10XB"), (4, "size>10XB".to_string()));
+        assert_eq!(error_at("size>MB").1, "size>MB");
+        assert_eq!(error_at("size>1.2.3").1, "size>1.2.3");
+        assert_eq!(error_at("size>99999999TB").1, "size>99999999TB");
+        assert_eq!(error_at("size:10MB").1, "size:10MB");
+    }
+
+    #[test]
+    fn modified_dates_are_whole_days() {
+        let filters = |q: &str| parse(q).unwrap().filters;
+
+        assert_eq!(
+            filters("modified<2025-03-01"),
+            vec![Filter::Modified(Comparison::Less, date("2025-03-01"))]
+        );
+        assert_eq!(
+            filters("modified<=2025-03-01"),
+            vec![Filter::Modified(Comparison::Less, date("2025-03-02"))]
+        );
+        assert_eq!(
+            filters("modified>=2025-03-01"),
+            vec![Filter::Modified(
+                Comparison::GreaterOrEqual,
+                date("2025-03-01")
+            )]
+        );
+        assert_eq!(
+            filters("modified:2024-12-31"),
+            vec![
+                Filter::Modified(Comparison::GreaterOrEqual, date("2024-12-31")),
+                Filter::Modified(Comparison::Less, date("2025-01-01")),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_dates() {
+        assert_eq!(error_at("modified>yesterday").1, "modified>yesterday");
+        assert_eq!(error_at("modified>2025-02-30").1, "modified>2025-02-30");
+    }
+
+    #[test]
+    fn owners() {
+        assert_eq!(error_at("owner:bob").1, "owner:bob");
+        assert_eq!(error_at("owner:").1, "owner:");
+    }
+
+    #[test]
+    fn unsupported_operators() {
+        let error = parse("budget is:starred").unwrap_err();
+        assert_eq!(error.position, 7);
+        assert_eq!(error.token, "is:starred");
+        assert_eq!(error.message, "Starring files is not supported");
+
+        let error = parse(r#"report in:"Projects""#).unwrap_err();
+        assert_eq!(error.position, 7);
+        assert_eq!(error.token, r#"in:"Projects""#);
+        assert!(error.message.contains("folders"));
+
+        assert_eq!(error_at("is:trashed").1, "is:trashed");
+    }
+
+    #[test]
+    fn unknown_and_misused_operators() {
+        assert_eq!(error_at("report color:red"), (7, "color:red".to_string()));
+        assert_eq!(error_at("name>budget").1, "name>budget");
+        assert_eq!(error_at("-type:pdf").1, "-type:pdf");
+    }
+
+    #[test]
+    fn positions_count_characters() {
+        assert_eq!(error_at("отчёт size>1XB").0, 6);
+    }
+}
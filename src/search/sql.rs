@@ -0,0 +1,80 @@
+//! Postgres full-text and trigram search types, functions and operators for
+//! Diesel.
+
+use crate::schema::sql_types::Tsvector;
+use diesel::expression::functions::declare_sql_function;
+use diesel::expression::{AsExpression, CastsTo, FallibleCastsTo, KnownCastSqlTypeName};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Nullable, Text};
+
+/// A text search configuration, bound as text and cast to `regconfig`.
+#[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+#[diesel(postgres_type(name = "regconfig", schema = "pg_catalog"))]
+pub struct Regconfig;
+
+#[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+#[diesel(postgres_type(name = "tsquery", schema = "pg_catalog"))]
+pub struct Tsquery;
+
+/// The single-byte `"char"` type `setweight` takes its weight as.
+#[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+#[diesel(postgres_type(name = "char", schema = "pg_catalog"))]
+pub struct Weight;
+
+impl KnownCastSqlTypeName<Pg> for Regconfig {
+    const SQL_TYPE_NAME: &'static str = "regconfig";
+}
+impl FallibleCastsTo<Regconfig> for Text {}
+impl CastsTo<Regconfig> for Text {}
+
+impl KnownCastSqlTypeName<Pg> for Weight {
+    const SQL_TYPE_NAME: &'static str = "\"char\"";
+}
+impl FallibleCastsTo<Weight> for Text {}
+impl CastsTo<Weight> for Text {}
+
+#[declare_sql_function]
+extern "SQL" {
+    fn to_tsvector(config: Regconfig, document: Text) -> Tsvector;
+    fn setweight(vector: Tsvector, weight: Weight) -> Tsvector;
+    fn websearch_to_tsquery(config: Regconfig, query: Text) -> Tsquery;
+    fn ts_rank(vector: Tsvector, query: Tsquery) -> Float4;
+    fn ts_headline(
+        config: Regconfig,
+        document: Nullable<Text>,
+        query: Tsquery,
+        options: Text,
+    ) -> Nullable<Text>;
+    /// Lowercases and unaccents text (defined by a migration).
+    fn search_normalize(text: Text) -> Text;
+    fn word_similarity(query: Text, text: Text) -> Float4;
+    fn set_config(setting_name: Text, new_value: Text, is_local: Bool) -> Text;
+}
+
+diesel::infix_operator!(Matches, " @@ ", backend: Pg);
+diesel::infix_operator!(Concat, " || ", Tsvector, backend: Pg);
+diesel::infix_operator!(WordSimilar, " <% ", backend: Pg);
+
+/// `vector @@ query` and `vector || vector` for `tsvector` expressions.
+pub trait TsvectorExpressionMethods: Expression<SqlType = Tsvector> + Sized {
+    fn matches<Q: AsExpression<Tsquery>>(self, query: Q) -> Matches<Self, Q::Expression> {
+        Matches::new(self, query.as_expression())
+    }
+
+    fn concat<V: AsExpression<Tsvector>>(self, other: V) -> Concat<Self, V::Expression> {
+        Concat::new(self, other.as_expression())
+    }
+}
+
+impl<T: Expression<SqlType = Tsvector>> TsvectorExpressionMethods for T {}
+
+/// `query <% text`: some word of `text` is similar to `query`, by at least
+/// `pg_trgm.word_similarity_threshold`.
+pub trait TrigramExpressionMethods: Expression<SqlType = Text> + Sized {
+    fn word_similar_to<T: AsExpression<Text>>(self, text: T) -> WordSimilar<Self, T::Expression> {
+        WordSimilar::new(self, text.as_expression())
+    }
+}
+
+impl<T: Expression<SqlType = Text>> TrigramExpressionMethods for T {}
@@ -0,0 +1,137 @@
+use super::extract::{DocumentKind, extract_text};
+use super::max_extract_bytes;
+use crate::database::DbPool;
+use crate::models::file_contents::PendingFileContent;
+use crate::repositories::file_contents::{
+    claim_due_file_content, mark_file_content_failed, save_file_content,
+};
+use crate::repositories::s3_files::find_s3_file_by_id;
+use crate::storage::S3Storage;
+use actix_web::web;
+use chrono::{Duration, Utc};
+use log::{debug, error, info, warn};
+
+/// How often the index queue is polled for new files.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// How long a claimed file is hidden from other workers.
+const CLAIM_LEASE_SECS: i64 = 600;
+/// Attempts before a file is left indexed by name only.
+const MAX_ATTEMPTS: i32 = 5;
+/// Delay before the first retry; doubled on every further attempt.
+const RETRY_BASE_SECS: i64 = 60;
+
+/// Extracts and indexes the text of uploaded files until the process exits.
+pub async fn run_index_worker(pool: DbPool, storage: S3Storage) {
+    info!("Search index worker started");
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        loop {
+            let lease_until = (Utc::now() + Duration::seconds(CLAIM_LEASE_SECS)).naive_utc();
+            match claim_due_file_content(&pool, lease_until) {
+                Ok(Some(pending)) => index_file(&pool, &storage, pending).await,
+                Ok(None) => break,
+                Err(e) => {
+                    error!("Failed to claim file for indexing: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn index_file(pool: &DbPool, storage: &S3Storage, pending: PendingFileContent) {
+    let file = match find_s3_file_by_id(pool, pending.file_id) {
+        Ok(file) => file,
+        Err(diesel::result::Error::NotFound) => return,
+        Err(e) => {
+            error!(
+                "Failed to load file {} for indexing: {}",
+                pending.file_id, e
+            );
+            return;
+        }
+    };
+
+    let Some(kind) = DocumentKind::detect(&file.mime_type, &file.name) else {
+        debug!("File {} has no extractable text", file.file_id);
+        if let Err(e) = save_file_content(pool, &file, None, None) {
+            error!("Failed to index file {}: {}", file.file_id, e);
+        }
+        return;
+    };
+    if file.size > max_extract_bytes() {
+        let reason = "File is too large to extract text from";
+        if let Err(e) = save_file_content(pool, &file, None, Some(reason)) {
+            error!("Failed to index file {}: {}", file.file_id, e);
+        }
+        return;
+    }
+
+    let downloaded = match storage.download_file(&file.s3_key).await {
+        Ok(stream) => stream.collect().await.map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    let data = match downloaded {
+        Ok(data) => data.into_bytes(),
+        Err(e) => {
+            retry_later(pool, &pending, &e);
+            return;
+        }
+    };
+
+    let extracted = match web::block(move || extract_text(kind, &data)).await {
+        Ok(extracted) => extracted,
+        Err(e) => {
+            retry_later(pool, &pending, &e.to_string());
+            return;
+        }
+    };
+
+    let saved = match extracted {
+        Ok(text) => {
+            info!(
+                "Indexed {} characters of file {}",
+                text.chars().count(),
+                file.file_id
+            );
+            save_file_content(pool, &file, Some(text), None)
+        }
+        Err(reason) => {
+            warn!("Cannot extract text of file {}: {}", file.file_id, reason);
+            save_file_content(pool, &file, None, Some(&reason))
+        }
+    };
+    if let Err(e) = saved {
+        retry_later(pool, &pending, &e.to_string());
+    }
+}
+
+/// Schedules another attempt after a storage or database error. Once the
+/// attempts are used up the file is left searchable by name only.
+fn retry_later(pool: &DbPool, pending: &PendingFileContent, reason: &str) {
+    let attempts = pending.attempts + 1;
+    warn!(
+        "Indexing file {} failed (attempt {}/{}): {}",
+        pending.file_id, attempts, MAX_ATTEMPTS, reason
+    );
+
+    let update = if attempts < MAX_ATTEMPTS {
+        let delay = RETRY_BASE_SECS * 2_i64.pow(pending.attempts as u32);
+        let retry_at = (Utc::now() + Duration::seconds(delay)).naive_utc();
+        mark_file_content_failed(pool, pending.file_id, reason, retry_at)
+    } else {
+        find_s3_file_by_id(pool, pending.file_id)
+            .and_then(|file| save_file_content(pool, &file, None, Some(reason)))
+    };
+
+    if let Err(e) = update {
+        error!(
+            "Failed to update index entry of file {}: {}",
+            pending.file_id, e
+        );
+    }
+}
@@ -20,10 +20,13 @@ impl S3Storage {
         }
     }
 
+    /// Streams the request payload to S3 and returns the original name, key,
+    /// size and MIME type. Fails with 413 once the payload exceeds `max_size`.
     pub async fn save_file(
         &self,
         req: &HttpRequest,
         mut payload: actix_web::web::Payload,
+        max_size: Option<i64>,
     ) -> Result<(String, String, i64, Option<String>), Error> {
         // Get original filename from header
         let original_name = req
@@ -53,6 +56,12 @@ impl S3Storage {
                 actix_web::error::ErrorInternalServerError(format!("Stream error: {}", e))
             })?;
             bytes += chunk.len() as i64;
+            if let Some(max_size) = max_size.filter(|max| bytes > *max) {
+                return Err(actix_web::error::ErrorPayloadTooLarge(format!(
+                    "File exceeds the maximum size of {} bytes",
+                    max_size
+                )));
+            }
             chunks.push(chunk);
         }
 
@@ -83,6 +92,25 @@ impl S3Storage {
         Ok((original_name.to_string(), key, bytes, mime_type_val))
     }
 
+    /// Uploads a local file to S3 under `key`, streaming it from disk.
+    pub async fn upload_path(&self, key: impl AsRef<str>, path: &Path) -> Result<(), Error> {
+        let body = ByteStream::from_path(path).await.map_err(|e| {
+            actix_web::error::ErrorInternalServerError(format!("Failed to read {:?}: {}", path, e))
+        })?;
+
+        self.client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(key.as_ref())
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| {
+                actix_web::error::ErrorInternalServerError(format!("S3 upload error: {}", e))
+            })?;
+        Ok(())
+    }
+
     pub async fn delete_file(&self, key: impl AsRef<str>) -> Result<(), Error> {
         self.client
             .delete_object()